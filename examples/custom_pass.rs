@@ -1,3 +1,5 @@
+use std::process::ExitCode;
+
 use nprs::{pixel::*, render_graph::ANY_IMAGE, FromParsedValue, Image, ParsePass, Pass};
 
 // The `ParsePass` and `FromParsedValue` macros make your pass available in render graph descriptor
@@ -40,9 +42,12 @@ impl Pass for Desaturate {
     }
 }
 
-fn main() {
+fn main() -> ExitCode {
     match nprs::run_cli() {
-        Ok(_) => (),
-        Err(err) => println!("error: {}", err),
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => {
+            println!("error: {}", err);
+            ExitCode::FAILURE
+        },
     }
 }
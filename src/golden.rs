@@ -0,0 +1,284 @@
+//! Golden image tests, which render effects over fixed inputs and compare the results against
+//! reference images so that changes to passes don't silently alter existing effects.
+//!
+//! A manifest lists the test cases, each under a `[name]` header:
+//!
+//! ```text
+//! # Defaults for every case.
+//! metric = psnr
+//! threshold = 45
+//!
+//! [edge-detect]
+//! effect = effects/dog/edge-detect.nprs
+//! input = inputs/portrait.png
+//! reference = golden/edge-detect.png
+//! arg = sigma_c=0.8
+//! arg = phi=20.0
+//! ```
+//!
+//! Paths are relative to the manifest. Each `arg` is one `NAME=VALUE` argument to the effect, and
+//! `metric` and `threshold` may also be given per case.
+
+use std::{fs, io::Cursor, num::NonZeroUsize, path::{Path, PathBuf}, time::Duration};
+
+use clap::ValueEnum;
+
+use crate::{
+    image::{animation::{Animation, Frame}, color_management::ColorEncoding, pixel::rgba::Rgba, Image, ImageError, ImageFormat},
+    check_threshold, parser::cli::PassArg, pass::luminance::LuminanceMethod, CompareMetric, NprsError, OutputArgs, OutputSettings, RawRenderGraph,
+};
+
+/// A single golden image test.
+struct TestCase {
+    name: String,
+    effect: PathBuf,
+    input: PathBuf,
+    reference: PathBuf,
+    args: Vec<PassArg>,
+    metric: CompareMetric,
+    threshold: Option<f32>,
+}
+
+/// The test cases of a manifest, in the order they are listed.
+pub(crate) struct Manifest {
+    cases: Vec<TestCase>,
+}
+
+impl Manifest {
+    /// Reads the manifest at `path`, resolving the paths in it relative to its directory.
+    pub(crate) fn read(path: &Path) -> Result<Manifest, NprsError> {
+        let source = fs::read_to_string(path).map_err(|err| invalid(path, 0, err.to_string()))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+
+        let mut cases = Vec::new();
+        let mut metric = CompareMetric::Psnr;
+        let mut threshold = None;
+
+        // The case being read, along with the line its header is on.
+        let mut current: Option<(usize, PartialCase)> = None;
+
+        for (i, line) in source.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                if let Some((header, case)) = current.take() {
+                    cases.push(case.finish().map_err(|message| invalid(path, header, message))?);
+                }
+
+                let name = name.trim();
+
+                if name.is_empty() || cases.iter().any(|case: &TestCase| case.name == name) {
+                    return Err(invalid(path, line_number, format!("invalid or duplicate test name `{name}`")));
+                }
+
+                current = Some((line_number, PartialCase::new(name, metric, threshold)));
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(invalid(path, line_number, format!("expected `[name]` or `key = value`, found `{line}`")));
+            };
+
+            let (key, value) = (key.trim(), value.trim());
+
+            if key == "metric" || key == "threshold" {
+                let (metric, threshold) = match &mut current {
+                    Some((_, case)) => (&mut case.metric, &mut case.threshold),
+                    None => (&mut metric, &mut threshold),
+                };
+
+                if key == "metric" {
+                    *metric = CompareMetric::from_str(value, true)
+                        .map_err(|_| invalid(path, line_number, format!("unknown metric `{value}`")))?;
+                } else {
+                    let value = value.parse().map_err(|_| invalid(path, line_number, format!("invalid threshold `{value}`")))?;
+                    *threshold = Some(value);
+                }
+
+                continue;
+            }
+
+            match (key, &mut current) {
+                ("effect", Some((_, case))) => case.effect = Some(dir.join(value)),
+                ("input", Some((_, case))) => case.input = Some(dir.join(value)),
+                ("reference", Some((_, case))) => case.reference = Some(dir.join(value)),
+                ("arg", Some((_, case))) => {
+                    let arg = value.parse().map_err(|_| invalid(path, line_number, format!("invalid argument `{value}`, expected NAME=VALUE")))?;
+                    case.args.push(arg);
+                },
+                _ => return Err(invalid(path, line_number, format!("unexpected key `{key}`"))),
+            }
+        }
+
+        if let Some((header, case)) = current {
+            cases.push(case.finish().map_err(|message| invalid(path, header, message))?);
+        }
+
+        Ok(Manifest { cases })
+    }
+
+    /// Runs every case whose name contains `filter`, printing the result of each.
+    ///
+    /// Failed cases write what they rendered and a heatmap of its differences from the reference
+    /// to `diff_dir`. With `bless`, the references are overwritten with the rendered images
+    /// instead. Each render has up to `parallelism` passes rendering at once.
+    pub(crate) fn run(&self, filter: Option<&str>, diff_dir: &Path, bless: bool, parallelism: NonZeroUsize) -> Result<(), NprsError> {
+        let cases: Vec<&TestCase> = self.cases.iter()
+            .filter(|case| filter.is_none_or(|filter| case.name.contains(filter)))
+            .collect();
+
+        println!("running {} golden tests", cases.len());
+
+        let mut failed = Vec::new();
+
+        for case in &cases {
+            let result = if bless { case.bless(parallelism) } else { case.check(diff_dir, parallelism) };
+
+            match result {
+                Ok(summary) => println!("test {} ... ok ({summary})", case.name),
+                Err(err) => {
+                    println!("test {} ... FAILED: {err}", case.name);
+                    failed.push(case.name.as_str());
+                },
+            }
+        }
+
+        if failed.is_empty() {
+            return Ok(());
+        }
+
+        println!("\nfailures:");
+
+        for name in &failed {
+            println!("    {name}");
+        }
+
+        Err(NprsError::TestsFailed(failed.len(), cases.len()))
+    }
+}
+
+impl TestCase {
+    /// Compares the rendered image against the reference, writing the image and a diff to
+    /// `diff_dir` if they differ. Returns the value of the metric.
+    fn check(&self, diff_dir: &Path, parallelism: NonZeroUsize) -> Result<String, NprsError> {
+        let encoded = self.render(parallelism)?;
+        let (actual, _) = Image::<4, f32, Rgba<f32>>::read_from(Cursor::new(&encoded))?;
+        let expected = Image::<4, f32, Rgba<f32>>::read(&self.reference)?;
+
+        if expected.resolution() != actual.resolution() {
+            return Err(NprsError::MismatchedResolutions(expected.resolution(), actual.resolution()));
+        }
+
+        let mse = expected.mse(&actual);
+        let mean_mse = mse.iter().sum::<f32>() / mse.len() as f32;
+        let ssim = if self.metric == CompareMetric::Ssim { expected.luminance_ssim(&actual, LuminanceMethod::Standard) } else { 1.0 };
+
+        let result = check_threshold(self.metric, self.threshold, mean_mse, ssim);
+
+        if result.is_err() {
+            fs::create_dir_all(diff_dir).map_err(ImageError::from)?;
+
+            let extension = self.reference.extension().unwrap_or_default().to_string_lossy();
+            fs::write(diff_dir.join(format!("{}.{extension}", self.name)), encoded).map_err(ImageError::from)?;
+            expected.diff_heatmap(&actual, 0.1).write(diff_dir.join(format!("{}.diff.png", self.name)))?;
+        }
+
+        result
+    }
+
+    /// Overwrites the reference with the rendered image.
+    fn bless(&self, parallelism: NonZeroUsize) -> Result<String, NprsError> {
+        let encoded = self.render(parallelism)?;
+
+        if let Some(parent) = self.reference.parent() {
+            fs::create_dir_all(parent).map_err(ImageError::from)?;
+        }
+
+        fs::write(&self.reference, encoded).map_err(ImageError::from)?;
+
+        Ok(format!("wrote {}", self.reference.display()))
+    }
+
+    /// Renders the effect over the first frame of the input and encodes it the way the CLI would
+    /// write it in the format of the reference.
+    fn render(&self, parallelism: NonZeroUsize) -> Result<Vec<u8>, NprsError> {
+        let (mut input, metadata) = Image::<4, f32, Rgba<f32>>::read_with_metadata(&self.input)?;
+        input.decode_transfer(metadata.transfer_function());
+
+        let (mut render_graph, display_node) = RawRenderGraph::read(&self.effect, self.args.clone())?.build(input)?;
+        render_graph.set_source_encoding(ColorEncoding::Linear);
+        render_graph.set_parallelism(parallelism);
+
+        render_graph.verify()?;
+        render_graph.render();
+
+        let encoding = render_graph.image_encoding(display_node);
+        let mut image = render_graph.image(display_node).unwrap().to_rgba();
+        image.convert_encoding(encoding, ColorEncoding::Linear);
+
+        let format = ImageFormat::from_path(&self.reference)?;
+        let settings = OutputSettings::new(format, OutputArgs { output_format: Some(format), alpha: None, depth: None })?;
+
+        let animation = Animation { frames: vec![Frame { image, delay: Duration::ZERO }], loop_count: 0 };
+
+        let mut encoded = Vec::new();
+        settings.write(&animation, &mut encoded)?;
+
+        Ok(encoded)
+    }
+}
+
+/// A test case whose keys are still being read.
+struct PartialCase {
+    name: String,
+    effect: Option<PathBuf>,
+    input: Option<PathBuf>,
+    reference: Option<PathBuf>,
+    args: Vec<PassArg>,
+    metric: CompareMetric,
+    threshold: Option<f32>,
+}
+
+impl PartialCase {
+    fn new(name: &str, metric: CompareMetric, threshold: Option<f32>) -> PartialCase {
+        PartialCase { name: name.to_string(), effect: None, input: None, reference: None, args: Vec::new(), metric, threshold }
+    }
+
+    fn finish(self) -> Result<TestCase, String> {
+        let missing = |key: &str| format!("test `{}` is missing `{key}`", self.name);
+
+        Ok(TestCase {
+            effect: self.effect.clone().ok_or_else(|| missing("effect"))?,
+            input: self.input.clone().ok_or_else(|| missing("input"))?,
+            reference: self.reference.clone().ok_or_else(|| missing("reference"))?,
+            name: self.name,
+            args: self.args,
+            metric: self.metric,
+            threshold: self.threshold,
+        })
+    }
+}
+
+fn invalid(path: &Path, line: usize, message: String) -> NprsError {
+    NprsError::InvalidManifest(format!("{}:{line}", path.display()), message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{num::NonZeroUsize, path::Path};
+
+    use super::Manifest;
+
+    #[test]
+    fn bundled_effects_match_their_golden_images() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+        let manifest = Manifest::read(&dir.join("golden.manifest")).unwrap();
+
+        manifest.run(None, &dir.join("diff"), false, NonZeroUsize::MIN).unwrap();
+    }
+}
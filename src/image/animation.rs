@@ -0,0 +1,231 @@
+use std::{fs::File, io::{BufWriter, Read, Write}, path::Path, time::Duration};
+
+use glam::UVec2;
+
+use super::{format::PixelFormat, metadata::ImageMetadata, pixel::{rgba::Rgba, Pixel}, y4m::{Y4mHeader, Y4mReader, Y4mWriter}, Image, ImageError, ImageFormat, WriteOptions};
+
+/// A sequence of images, each shown for its own delay.
+#[derive(Clone)]
+pub struct Animation<const CHANNELS: usize, F, P>
+where
+    F: PixelFormat,
+    P: Pixel<CHANNELS, Format = F>,
+{
+    pub frames: Vec<Frame<CHANNELS, F, P>>,
+    /// The number of times the animation plays, where `0` loops forever.
+    pub loop_count: u32,
+}
+
+/// A single image of an [`Animation`].
+#[derive(Clone)]
+pub struct Frame<const CHANNELS: usize, F, P>
+where
+    F: PixelFormat,
+    P: Pixel<CHANNELS, Format = F>,
+{
+    pub image: Image<CHANNELS, F, P>,
+    /// How long the frame is shown before the next one.
+    pub delay: Duration,
+}
+
+impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Animation<CHANNELS, F, P> {
+    /// Read an animation from `path`, along with the [`ImageMetadata`] stored in the file.
+    ///
+    /// See [`Self::read_from`].
+    pub fn read_with_metadata<S: AsRef<Path>>(path: S) -> Result<(Animation<CHANNELS, F, P>, ImageMetadata), ImageError> {
+        Self::read_from(File::open(path)?)
+    }
+
+    /// Read an animation from `reader`, detecting the format from the magic bytes at the start of
+    /// the stream.
+    ///
+    /// Frames of GIFs and APNGs are composited onto the full canvas, so every frame has the same
+    /// resolution. YUV4MPEG2 streams are read whole, see [`Y4mReader`] to read one frame at a
+    /// time. Any other image is read as a single frame.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<(Animation<CHANNELS, F, P>, ImageMetadata), ImageError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        let (animation, metadata) = match ImageFormat::detect(&data) {
+            Some(ImageFormat::Gif) => (Self::read_gif(&data)?, ImageMetadata::default()),
+            Some(ImageFormat::Png) => Self::read_apng(&data)?,
+            Some(ImageFormat::Y4m) => (Self::read_y4m(&data)?, ImageMetadata::default()),
+            _ => {
+                let (image, metadata) = Image::read_from(data.as_slice())?;
+                (Animation::from(image), metadata)
+            },
+        };
+
+        if animation.frames.is_empty() {
+            return Err(ImageError::NoFrames);
+        }
+
+        Ok((animation, metadata))
+    }
+
+    /// Write this animation to `path`, with format-specific settings taken from `options`.
+    ///
+    /// The format is chosen from the extension of `path`.
+    pub fn write_with_options<S: AsRef<Path>>(&self, path: S, options: &WriteOptions) -> Result<(), ImageError> {
+        let path = path.as_ref();
+        let format = ImageFormat::from_path(path)?;
+
+        self.write_to(BufWriter::new(File::create(path)?), format, options)
+    }
+
+    /// Write this animation to `writer` as `format`, with format-specific settings taken from
+    /// `options`.
+    ///
+    /// PNGs with more than one frame are written as APNGs. Formats without
+    /// [animation support](ImageFormat::supports_animation) can only write a single frame.
+    pub fn write_to<W: Write>(&self, mut writer: W, format: ImageFormat, options: &WriteOptions) -> Result<(), ImageError> {
+        match (format, self.frames.as_slice()) {
+            (_, []) => return Err(ImageError::NoFrames),
+            (ImageFormat::Gif, _) => self.write_gif(&mut writer)?,
+            (ImageFormat::Y4m, _) => self.write_y4m(&mut writer)?,
+            (_, [frame]) => return frame.image.write_to(writer, format, options),
+            (ImageFormat::Png, _) => self.write_apng(&mut writer, &options.metadata)?,
+            _ => return Err(ImageError::NotAnimated(format)),
+        }
+
+        Ok(writer.flush()?)
+    }
+
+    /// Reads every frame of a YUV4MPEG2 stream, timed by its frame rate.
+    fn read_y4m(data: &[u8]) -> Result<Animation<CHANNELS, F, P>, ImageError> {
+        let mut reader = Y4mReader::new(data)?;
+
+        let (num, den) = reader.header().frame_rate;
+        let delay = if num == 0 { Duration::ZERO } else { Duration::from_secs_f64(den as f64 / num as f64) };
+
+        let mut frames = Vec::new();
+
+        while let Some(image) = reader.read_frame()? {
+            frames.push(Frame { image, delay });
+        }
+
+        Ok(Animation { frames, loop_count: 0 })
+    }
+
+    /// Writes every frame to a YUV4MPEG2 stream, at the frame rate given by the delay of the first
+    /// frame.
+    fn write_y4m<W: Write>(&self, writer: W) -> Result<(), ImageError> {
+        let mut header = Y4mHeader::new(self.frames[0].image.resolution);
+
+        let millis = self.frames[0].delay.as_millis();
+        if millis > 0 {
+            header.frame_rate = (1000, millis.min(u32::MAX as u128) as u32);
+        }
+
+        let mut writer = Y4mWriter::new(writer, header)?;
+
+        for frame in self.frames.iter() {
+            writer.write_frame(&frame.image)?;
+        }
+
+        writer.flush()
+    }
+}
+
+impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> From<Image<CHANNELS, F, P>> for Animation<CHANNELS, F, P> {
+    fn from(image: Image<CHANNELS, F, P>) -> Self {
+        Animation {
+            frames: vec![Frame { image, delay: Duration::ZERO }],
+            loop_count: 0,
+        }
+    }
+}
+
+/// How a frame is drawn over the canvas.
+pub(super) enum Blend {
+    /// The frame replaces the canvas.
+    Source,
+    /// The frame is alpha composited over the canvas.
+    Over,
+}
+
+/// What happens to the area of a frame after it has been shown.
+#[derive(PartialEq, Eq)]
+pub(super) enum Disposal {
+    /// The frame is left in place.
+    Keep,
+    /// The area is cleared to transparent black.
+    Background,
+    /// The area is restored to what it was before the frame.
+    Previous,
+}
+
+/// Builds the full frames of formats like GIF and APNG, where each frame only stores the region of
+/// the canvas that changed.
+pub(super) struct Compositor {
+    canvas: Image<4, f32, Rgba<f32>>,
+    frames: Vec<Frame<4, f32, Rgba<f32>>>,
+}
+
+impl Compositor {
+    pub(super) fn new(resolution: UVec2) -> Compositor {
+        Compositor {
+            canvas: Image::new_fill(resolution, Rgba::splat_with_alpha(0.0)),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Draws `patch` at `offset` and records the canvas as the next frame.
+    pub(super) fn push(&mut self, patch: &Image<4, f32, Rgba<f32>>, offset: UVec2, blend: Blend, disposal: Disposal, delay: Duration) {
+        let previous = (disposal == Disposal::Previous).then(|| self.canvas.clone());
+        let resolution = self.canvas.resolution();
+
+        let area = patch.iter_pixels_with_positions()
+            .map(|(pixel, pos)| (pixel, pos + offset))
+            .filter(|(_, pos)| pos.x < resolution.x && pos.y < resolution.y);
+
+        for (&pixel, pos) in area {
+            let below = self.canvas.get_mut(pos);
+
+            *below = match blend {
+                Blend::Source => pixel,
+                Blend::Over => over(pixel, *below),
+            };
+        }
+
+        self.frames.push(Frame { image: self.canvas.clone(), delay });
+
+        match disposal {
+            Disposal::Keep => (),
+            Disposal::Background => {
+                let end = (offset + patch.resolution()).min(resolution);
+
+                for y in offset.y..end.y {
+                    for x in offset.x..end.x {
+                        self.canvas.store(UVec2::new(x, y), Rgba::splat_with_alpha(0.0));
+                    }
+                }
+            },
+            Disposal::Previous => self.canvas = previous.unwrap(),
+        }
+    }
+
+    pub(super) fn finish<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>>(self, loop_count: u32) -> Animation<CHANNELS, F, P> {
+        let frames = self.frames.into_iter()
+            .map(|frame| Frame {
+                image: frame.image.to_format::<F, Rgba<F>>().map(|pixel| P::from_pixel(*pixel)),
+                delay: frame.delay,
+            })
+            .collect();
+
+        Animation { frames, loop_count }
+    }
+}
+
+/// Composites the straight alpha pixel `top` over `bottom`.
+fn over(top: Rgba<f32>, bottom: Rgba<f32>) -> Rgba<f32> {
+    let a = top.a + bottom.a * (1.0 - top.a);
+
+    if a <= 0.0 {
+        return Rgba::splat_with_alpha(0.0);
+    }
+
+    let mix = |t: f32, b: f32| (t * top.a + b * bottom.a * (1.0 - top.a)) / a;
+
+    Rgba::new(mix(top.r, bottom.r), mix(top.g, bottom.g), mix(top.b, bottom.b), a)
+}
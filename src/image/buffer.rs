@@ -0,0 +1,167 @@
+use glam::UVec2;
+use half::f16;
+
+use super::{format::PixelFormat, pixel::{luma::Luma, luma_alpha::LumaAlpha, rgb::Rgb, rgba::Rgba, Pixel}, Image};
+
+/// The channels of a [`BufferFormat`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PixelLayout {
+    Luma,
+    LumaAlpha,
+    Rgb,
+    Rgba,
+}
+
+/// The sample format of a [`BufferFormat`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Precision {
+    /// Half floats, which keep about three significant digits.
+    F16,
+    F32,
+}
+
+/// The layout and precision an image is stored in between passes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BufferFormat {
+    pub layout: PixelLayout,
+    pub precision: Precision,
+}
+
+impl BufferFormat {
+    /// The format passes work in, which holds anything without loss.
+    pub const RGBA_F32: BufferFormat = BufferFormat::new(PixelLayout::Rgba, Precision::F32);
+
+    pub const fn new(layout: PixelLayout, precision: Precision) -> BufferFormat {
+        BufferFormat { layout, precision }
+    }
+
+    pub fn channels(&self) -> usize {
+        match self.layout {
+            PixelLayout::Luma => 1,
+            PixelLayout::LumaAlpha => 2,
+            PixelLayout::Rgb => 3,
+            PixelLayout::Rgba => 4,
+        }
+    }
+
+    /// The size of a single pixel, in bytes.
+    pub fn pixel_bytes(&self) -> usize {
+        let sample_bytes = match self.precision {
+            Precision::F16 => 2,
+            Precision::F32 => 4,
+        };
+
+        self.channels() * sample_bytes
+    }
+}
+
+/// An image stored in any [`BufferFormat`].
+///
+/// Passes always read and write RGBA `f32` images, so buffers are converted to and from that at
+/// the edges of the render graph through [`FromPixel`](super::pixel::FromPixel). Narrower formats
+/// drop whatever the conversion drops, like the color of a pixel stored as [`PixelLayout::Luma`].
+#[derive(Clone)]
+pub enum Buffer {
+    LumaF16(Image<1, f16, Luma<f16>>),
+    LumaF32(Image<1, f32, Luma<f32>>),
+    LumaAlphaF16(Image<2, f16, LumaAlpha<f16>>),
+    LumaAlphaF32(Image<2, f32, LumaAlpha<f32>>),
+    RgbF16(Image<3, f16, Rgb<f16>>),
+    RgbF32(Image<3, f32, Rgb<f32>>),
+    RgbaF16(Image<4, f16, Rgba<f16>>),
+    RgbaF32(Image<4, f32, Rgba<f32>>),
+}
+
+impl Buffer {
+    /// Converts `image` to `format`.
+    pub fn from_rgba(image: Image<4, f32, Rgba<f32>>, format: BufferFormat) -> Buffer {
+        match (format.layout, format.precision) {
+            (PixelLayout::Luma, Precision::F16) => Buffer::LumaF16(image.map(|p| p.convert::<Luma<f32>>()).to_format()),
+            (PixelLayout::Luma, Precision::F32) => Buffer::LumaF32(image.map(|p| p.convert())),
+            (PixelLayout::LumaAlpha, Precision::F16) => Buffer::LumaAlphaF16(image.map(|p| p.convert::<LumaAlpha<f32>>()).to_format()),
+            (PixelLayout::LumaAlpha, Precision::F32) => Buffer::LumaAlphaF32(image.map(|p| p.convert())),
+            (PixelLayout::Rgb, Precision::F16) => Buffer::RgbF16(image.map(|p| p.convert::<Rgb<f32>>()).to_format()),
+            (PixelLayout::Rgb, Precision::F32) => Buffer::RgbF32(image.map(|p| p.convert())),
+            (PixelLayout::Rgba, Precision::F16) => Buffer::RgbaF16(image.to_format()),
+            (PixelLayout::Rgba, Precision::F32) => Buffer::RgbaF32(image),
+        }
+    }
+
+    /// Converts this buffer to the RGBA `f32` image passes work with.
+    pub fn to_rgba(&self) -> Image<4, f32, Rgba<f32>> {
+        match self {
+            Buffer::LumaF16(image) => widen(image),
+            Buffer::LumaF32(image) => widen(image),
+            Buffer::LumaAlphaF16(image) => widen(image),
+            Buffer::LumaAlphaF32(image) => widen(image),
+            Buffer::RgbF16(image) => widen(image),
+            Buffer::RgbF32(image) => widen(image),
+            Buffer::RgbaF16(image) => widen(image),
+            Buffer::RgbaF32(image) => image.clone(),
+        }
+    }
+
+    /// Like [`Self::to_rgba`], but without copying buffers that are already RGBA `f32`.
+    pub fn into_rgba(self) -> Image<4, f32, Rgba<f32>> {
+        match self {
+            Buffer::RgbaF32(image) => image,
+            buffer => buffer.to_rgba(),
+        }
+    }
+
+    /// The RGBA `f32` image in this buffer, if that is the format it is stored in.
+    pub fn as_rgba(&self) -> Option<&Image<4, f32, Rgba<f32>>> {
+        match self {
+            Buffer::RgbaF32(image) => Some(image),
+            _ => None,
+        }
+    }
+
+    pub fn format(&self) -> BufferFormat {
+        let (layout, precision) = match self {
+            Buffer::LumaF16(_) => (PixelLayout::Luma, Precision::F16),
+            Buffer::LumaF32(_) => (PixelLayout::Luma, Precision::F32),
+            Buffer::LumaAlphaF16(_) => (PixelLayout::LumaAlpha, Precision::F16),
+            Buffer::LumaAlphaF32(_) => (PixelLayout::LumaAlpha, Precision::F32),
+            Buffer::RgbF16(_) => (PixelLayout::Rgb, Precision::F16),
+            Buffer::RgbF32(_) => (PixelLayout::Rgb, Precision::F32),
+            Buffer::RgbaF16(_) => (PixelLayout::Rgba, Precision::F16),
+            Buffer::RgbaF32(_) => (PixelLayout::Rgba, Precision::F32),
+        };
+
+        BufferFormat { layout, precision }
+    }
+
+    pub fn resolution(&self) -> UVec2 {
+        match self {
+            Buffer::LumaF16(image) => image.resolution(),
+            Buffer::LumaF32(image) => image.resolution(),
+            Buffer::LumaAlphaF16(image) => image.resolution(),
+            Buffer::LumaAlphaF32(image) => image.resolution(),
+            Buffer::RgbF16(image) => image.resolution(),
+            Buffer::RgbF32(image) => image.resolution(),
+            Buffer::RgbaF16(image) => image.resolution(),
+            Buffer::RgbaF32(image) => image.resolution(),
+        }
+    }
+
+    /// The memory taken up by the pixels of this buffer, in bytes.
+    pub fn size(&self) -> usize {
+        let resolution = self.resolution();
+        resolution.x as usize * resolution.y as usize * self.format().pixel_bytes()
+    }
+}
+
+impl From<Image<4, f32, Rgba<f32>>> for Buffer {
+    fn from(image: Image<4, f32, Rgba<f32>>) -> Self {
+        Buffer::RgbaF32(image)
+    }
+}
+
+fn widen<const CHANNELS: usize, F, P>(image: &Image<CHANNELS, F, P>) -> Image<4, f32, Rgba<f32>>
+where
+    F: PixelFormat,
+    P: Pixel<CHANNELS, Format = F>,
+{
+    image.map(|pixel| Rgba::from_channels(pixel.convert::<Rgba<F>>().channels().map(F::to_scaled_float)))
+}
@@ -0,0 +1,134 @@
+use nprs_derive::FromParsedValue;
+
+use super::{metadata::ImageMetadata, pixel::Pixel, Image};
+
+/// How the samples of an image relate to linear light.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransferFunction {
+    /// Samples are proportional to light intensity.
+    Linear,
+    /// The piecewise sRGB curve.
+    Srgb,
+    /// A pure power curve, where `sample = linear ^ gamma`. This is how the PNG `gAMA` chunk
+    /// describes its data, so a typical value is `1.0 / 2.2`.
+    Gamma(f32),
+}
+
+impl TransferFunction {
+    /// Converts an encoded sample to linear light.
+    ///
+    /// Negative samples are mirrored so that out-of-gamut values survive a round trip.
+    pub fn decode(self, v: f32) -> f32 {
+        let a = v.abs();
+
+        let linear = match self {
+            TransferFunction::Linear => a,
+            TransferFunction::Srgb => if a <= 0.04045 {
+                a / 12.92
+            } else {
+                ((a + 0.055) / 1.055).powf(2.4)
+            },
+            TransferFunction::Gamma(gamma) => a.powf(1.0 / gamma),
+        };
+
+        linear.copysign(v)
+    }
+
+    /// Converts a linear light sample to this encoding.
+    ///
+    /// Negative samples are mirrored so that out-of-gamut values survive a round trip.
+    pub fn encode(self, v: f32) -> f32 {
+        let a = v.abs();
+
+        let encoded = match self {
+            TransferFunction::Linear => a,
+            TransferFunction::Srgb => if a <= 0.003_130_8 {
+                a * 12.92
+            } else {
+                1.055 * a.powf(1.0 / 2.4) - 0.055
+            },
+            TransferFunction::Gamma(gamma) => a.powf(gamma),
+        };
+
+        encoded.copysign(v)
+    }
+}
+
+/// The encoding a pass wants its input images in.
+#[derive(FromParsedValue, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorEncoding {
+    /// Linear light. Blurring, blending and any other averaging of colors should happen here.
+    Linear,
+    /// sRGB encoded, display-referred values. Thresholds and quantization tuned by eye
+    /// usually expect this.
+    #[default]
+    Display,
+}
+
+impl ColorEncoding {
+    pub fn transfer_function(self) -> TransferFunction {
+        match self {
+            ColorEncoding::Linear => TransferFunction::Linear,
+            ColorEncoding::Display => TransferFunction::Srgb,
+        }
+    }
+}
+
+impl ImageMetadata {
+    /// The transfer function the samples of the file were encoded with.
+    ///
+    /// The `sRGB` chunk takes precedence over `gAMA`, following the PNG specification. ICC
+    /// profiles aren't interpreted, so images with only a profile (or no color information at all)
+    /// are assumed to be sRGB.
+    pub fn transfer_function(&self) -> TransferFunction {
+        if self.srgb.is_some() || self.icc_profile.is_some() {
+            return TransferFunction::Srgb;
+        }
+
+        match self.gamma {
+            Some(gamma) if (gamma - 1.0).abs() < 1e-3 => TransferFunction::Linear,
+            Some(gamma) => TransferFunction::Gamma(gamma),
+            None => TransferFunction::Srgb,
+        }
+    }
+}
+
+impl<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>> Image<CHANNELS, f32, P> {
+    /// Converts the color channels of this image from `transfer` to linear light. Alpha is left
+    /// untouched.
+    pub fn decode_transfer(&mut self, transfer: TransferFunction) {
+        if transfer != TransferFunction::Linear {
+            self.for_each_color_channel(|v| transfer.decode(v));
+        }
+    }
+
+    /// Converts the color channels of this image from linear light to `transfer`. Alpha is left
+    /// untouched.
+    pub fn encode_transfer(&mut self, transfer: TransferFunction) {
+        if transfer != TransferFunction::Linear {
+            self.for_each_color_channel(|v| transfer.encode(v));
+        }
+    }
+
+    /// Converts this image from the encoding `from` to the encoding `to`.
+    pub fn convert_encoding(&mut self, from: ColorEncoding, to: ColorEncoding) {
+        if from != to {
+            self.decode_transfer(from.transfer_function());
+            self.encode_transfer(to.transfer_function());
+        }
+    }
+
+    fn for_each_color_channel<Convert>(&mut self, f: Convert)
+    where
+        Convert: Fn(f32) -> f32 + Sync,
+    {
+        // Two and four channel pixels carry alpha in their last channel.
+        let color_channels = if CHANNELS.is_multiple_of(2) { CHANNELS - 1 } else { CHANNELS };
+
+        self.for_each(|pixel| {
+            let mut channels = pixel.channels();
+            channels.iter_mut().take(color_channels).for_each(|v| *v = f(*v));
+            *pixel = P::from_channels(channels);
+        });
+    }
+}
@@ -0,0 +1,225 @@
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+
+use crate::pass::luminance::LuminanceMethod;
+
+use super::{pixel::{luma::Luma, rgb::Rgb, rgba::Rgba, Pixel}, sampler::WrapMode2D, Image};
+
+/// The standard deviation of the gaussian window SSIM compares local statistics over.
+const SSIM_SIGMA: f32 = 1.5;
+
+/// The stabilizing constants of SSIM, for a dynamic range of `1.0`.
+const SSIM_C1: f32 = 0.01 * 0.01;
+const SSIM_C2: f32 = 0.03 * 0.03;
+
+impl<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>> Image<CHANNELS, f32, P> {
+    /// The mean squared error between each channel of this image and `other`.
+    ///
+    /// # Panics
+    ///
+    /// If the images have different resolutions. This holds for every comparison.
+    pub fn mse(&self, other: &Image<CHANNELS, f32, P>) -> [f32; CHANNELS] {
+        self.assert_same_resolution(other);
+
+        let sums = self.pixels
+            .par_iter()
+            .zip(&other.pixels)
+            .fold(
+                || [0.0f64; CHANNELS],
+                |mut sums, (a, b)| {
+                    for (sum, (a, b)) in sums.iter_mut().zip(a.channels().into_iter().zip(b.channels())) {
+                        *sum += ((a - b) as f64).powi(2);
+                    }
+
+                    sums
+                },
+            )
+            .reduce(|| [0.0; CHANNELS], |a, b| std::array::from_fn(|c| a[c] + b[c]));
+
+        let count = self.pixels.len().max(1) as f64;
+        sums.map(|sum| (sum / count) as f32)
+    }
+
+    /// The peak signal-to-noise ratio of each channel of `other` against this image, in decibels,
+    /// for values in `[0, 1]`. Identical channels are infinite.
+    pub fn psnr(&self, other: &Image<CHANNELS, f32, P>) -> [f32; CHANNELS] {
+        self.mse(other).map(psnr)
+    }
+
+    /// The mean structural similarity of each channel of this image and `other`, from `1.0` for
+    /// identical channels down to `-1.0`.
+    ///
+    /// Local statistics are gathered over a gaussian window with a standard deviation of `1.5`
+    /// pixels, following Wang et al.
+    pub fn ssim(&self, other: &Image<CHANNELS, f32, P>) -> [f32; CHANNELS] {
+        self.assert_same_resolution(other);
+
+        let map = self.ssim_map(other);
+        let sums = map.pixels
+            .par_iter()
+            .fold(
+                || [0.0f64; CHANNELS],
+                |mut sums, pixel| {
+                    sums.iter_mut().zip(pixel.channels()).for_each(|(sum, v)| *sum += v as f64);
+                    sums
+                },
+            )
+            .reduce(|| [0.0; CHANNELS], |a, b| std::array::from_fn(|c| a[c] + b[c]));
+
+        let count = map.pixels.len().max(1) as f64;
+        sums.map(|sum| (sum / count) as f32)
+    }
+
+    /// The mean structural similarity of the luminance of this image and `other`, computed with
+    /// `method`.
+    ///
+    /// See [`Self::ssim`].
+    pub fn luminance_ssim(&self, other: &Image<CHANNELS, f32, P>, method: LuminanceMethod) -> f32 {
+        let [ssim] = luminance(self, method).ssim(&luminance(other, method));
+        ssim
+    }
+
+    /// The structural similarity of each channel around every pixel.
+    pub fn ssim_map(&self, other: &Image<CHANNELS, f32, P>) -> Image<CHANNELS, f32, P> {
+        self.assert_same_resolution(other);
+
+        let radius = (3.0 * SSIM_SIGMA).ceil() as i32;
+        let kernel: Vec<f32> = (-radius..=radius).map(|x| (-(x * x) as f32 / (2.0 * SSIM_SIGMA * SSIM_SIGMA)).exp()).collect();
+        let total: f32 = kernel.iter().sum();
+        let kernel: Vec<f32> = kernel.into_iter().map(|w| w / total).collect();
+
+        let window = |image: &Image<CHANNELS, f32, P>| image.convolve_separable(&kernel, &kernel, WrapMode2D::MIRRORED_REPEAT);
+        let product = |a: &Image<CHANNELS, f32, P>, b: &Image<CHANNELS, f32, P>| a.map_with_positions(|&p, pos| p * b.load(pos));
+
+        let (mean_a, mean_b) = (window(self), window(other));
+        let (square_a, square_b, cross) = (window(&product(self, self)), window(&product(other, other)), window(&product(self, other)));
+
+        let c1 = P::from_channels([SSIM_C1; CHANNELS]);
+        let c2 = P::from_channels([SSIM_C2; CHANNELS]);
+        let two = P::from_channels([2.0; CHANNELS]);
+
+        mean_a.map_with_positions(|&mu_a, pos| {
+            let mu_b = mean_b.load(pos);
+            let (mu_aa, mu_bb, mu_ab) = (mu_a * mu_a, mu_b * mu_b, mu_a * mu_b);
+
+            let var_a = square_a.load(pos) - mu_aa;
+            let var_b = square_b.load(pos) - mu_bb;
+            let covariance = cross.load(pos) - mu_ab;
+
+            ((two * mu_ab + c1) * (two * covariance + c2)) / ((mu_aa + mu_bb + c1) * (var_a + var_b + c2))
+        })
+    }
+
+    /// A heatmap of the largest difference between the channels of this image and `other` at
+    /// every pixel, going from black through red and yellow to white at `max_difference`.
+    pub fn diff_heatmap(&self, other: &Image<CHANNELS, f32, P>, max_difference: f32) -> Image<4, f32, Rgba<f32>> {
+        self.assert_same_resolution(other);
+
+        Image::<4, f32, Rgba<f32>>::new_fill(self.resolution, Rgba::BLACK).map_with_positions(|_, pos| {
+            let difference = self.load(pos).channels().into_iter()
+                .zip(other.load(pos).channels())
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f32::max);
+
+            let t = (difference / max_difference).clamp(0.0, 1.0) * 3.0;
+            Rgba::new(t.min(1.0), (t - 1.0).clamp(0.0, 1.0), (t - 2.0).clamp(0.0, 1.0), 1.0)
+        })
+    }
+
+    fn assert_same_resolution(&self, other: &Image<CHANNELS, f32, P>) {
+        assert_eq!(self.resolution, other.resolution, "compared images have different resolutions");
+    }
+}
+
+/// The peak signal-to-noise ratio, in decibels, of a mean squared error for values in `[0, 1]`.
+pub fn psnr(mse: f32) -> f32 {
+    -10.0 * mse.log10()
+}
+
+fn luminance<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>>(image: &Image<CHANNELS, f32, P>, method: LuminanceMethod) -> Image<1, f32, Luma<f32>> {
+    image.map(|pixel| {
+        let rgb: Rgb<f32> = pixel.convert();
+        Luma { v: method.luminance(rgb.r, rgb.g, rgb.b) }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::UVec2;
+
+    use crate::{image::{pixel::{luma::Luma, rgba::Rgba}, Image}, pass::luminance::LuminanceMethod};
+
+    use super::psnr;
+
+    /// A smooth test pattern with some texture, so SSIM has structure to compare.
+    fn pattern(resolution: UVec2) -> Image<1, f32, Luma<f32>> {
+        let pixels = (0..resolution.y)
+            .flat_map(|y| (0..resolution.x).map(move |x| Luma { v: 0.5 + 0.3 * (x as f32 * 0.4).sin() * (y as f32 * 0.3).cos() }))
+            .collect();
+
+        Image::new(resolution, pixels)
+    }
+
+    /// Adds repeatable noise of up to `amplitude` either way.
+    fn noisy(image: &Image<1, f32, Luma<f32>>, amplitude: f32) -> Image<1, f32, Luma<f32>> {
+        image.map_with_positions(|pixel, pos| {
+            let mut state = (pos.y * 7919 + pos.x).wrapping_mul(0x9e3779b9) | 1;
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            Luma { v: pixel.v + amplitude * ((state % 2001) as f32 / 1000.0 - 1.0) }
+        })
+    }
+
+    #[test]
+    fn identical_images_are_perfectly_similar() {
+        let image = pattern(UVec2::new(24, 17));
+
+        assert_eq!(image.mse(&image), [0.0]);
+        assert_eq!(image.psnr(&image), [f32::INFINITY]);
+        assert!((image.ssim(&image)[0] - 1.0).abs() < 1e-5);
+
+        let flat = Image::<4, f32, Rgba<f32>>::new_fill(UVec2::new(8, 8), Rgba::new(0.2, 0.4, 0.6, 1.0));
+        assert!(flat.ssim(&flat).iter().all(|ssim| (ssim - 1.0).abs() < 1e-5));
+        assert!((flat.luminance_ssim(&flat, LuminanceMethod::Standard) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn offsets_give_known_errors() {
+        let image = pattern(UVec2::new(24, 17));
+        let offset = image.map(|pixel| Luma { v: pixel.v + 0.1 });
+
+        let [mse] = image.mse(&offset);
+        let [decibels] = image.psnr(&offset);
+
+        assert!((mse - 0.01).abs() < 1e-6, "{mse}");
+        assert!((decibels - 20.0).abs() < 1e-3, "{decibels}");
+        assert!((psnr(0.001) - 30.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn noise_lowers_similarity() {
+        let image = pattern(UVec2::new(32, 32));
+
+        let ssims: Vec<f32> = [0.0, 0.02, 0.1, 0.3].into_iter().map(|amplitude| image.ssim(&noisy(&image, amplitude))[0]).collect();
+
+        assert!((ssims[0] - 1.0).abs() < 1e-5);
+        assert!(ssims.windows(2).all(|w| w[1] < w[0]), "{ssims:?}");
+        assert!(ssims[3] < 0.8, "{ssims:?}");
+    }
+
+    #[test]
+    fn heatmaps_go_from_black_to_white() {
+        let a = Image::<1, f32, Luma<f32>>::new(UVec2::new(4, 1), vec![Luma { v: 0.0 }; 4]);
+        let b = Image::new(UVec2::new(4, 1), [0.0, 0.15, 0.25, 0.5].map(|v| Luma { v }).to_vec());
+
+        let heatmap = a.diff_heatmap(&b, 0.3);
+        let colors: Vec<Rgba<f32>> = heatmap.iter_pixels().copied().collect();
+
+        assert_eq!(colors[0], Rgba::new(0.0, 0.0, 0.0, 1.0));
+        assert!(colors[1].r == 1.0 && (colors[1].g - 0.5).abs() < 1e-5 && colors[1].b == 0.0, "{:?}", colors[1]);
+        assert!(colors[2].r == 1.0 && colors[2].g == 1.0 && colors[2].b > 0.0 && colors[2].b < 1.0, "{:?}", colors[2]);
+        assert_eq!(colors[3], Rgba::new(1.0, 1.0, 1.0, 1.0));
+
+        assert_eq!(psnr(0.0), f32::INFINITY);
+    }
+}
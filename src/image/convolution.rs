@@ -0,0 +1,308 @@
+use std::sync::Arc;
+
+use glam::{IVec2, UVec2};
+use rayon::{iter::{IndexedParallelIterator, ParallelIterator}, slice::ParallelSliceMut};
+use rustfft::{num_complex::Complex32, Fft, FftPlanner};
+
+use super::{pixel::Pixel, sampler::{WrapMode, WrapMode2D}, Image};
+
+/// The cost of one FFT butterfly relative to one tap of a direct convolution, which also pays for
+/// a wrapped load. A rough heuristic rather than a measurement; the crossover it gives depends on
+/// the machine and the image size.
+const FFT_COST: f32 = 0.4;
+
+impl<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>> Image<CHANNELS, f32, P> {
+    /// Convolves this image with a `kernel_size` kernel, stored row by row, clamping samples at
+    /// the edges of the image.
+    pub fn convolve(&self, kernel: &[f32], kernel_size: UVec2) -> Image<CHANNELS, f32, P> {
+        self.convolve_wrapped(kernel, kernel_size, WrapMode2D::CLAMP)
+    }
+
+    /// Convolves this image with a `kernel_size` kernel, stored row by row, sampling outside the
+    /// image with `wrap_mode`.
+    ///
+    /// Large kernels are applied through the FFT, which is much faster than looping over every
+    /// tap once the kernel is more than a few pixels wide.
+    pub fn convolve_wrapped(&self, kernel: &[f32], kernel_size: UVec2, wrap_mode: WrapMode2D) -> Image<CHANNELS, f32, P> {
+        assert!(kernel.len() as u32 == kernel_size.x * kernel_size.y);
+
+        let taps = (kernel_size.x * kernel_size.y) as f32;
+        let fft_size = self.fft_size(kernel_size, wrap_mode);
+        let butterflies = (fft_size.x * fft_size.y) as f32 * ((fft_size.x * fft_size.y) as f32).log2();
+
+        // Both paths are linear in the pixel count, apart from the transform's log factor. The
+        // FFT is also padded, runs forwards and backwards, and has the kernel to transform.
+        let direct_cost = (self.resolution.x * self.resolution.y) as f32 * taps;
+        let fft_cost = FFT_COST * butterflies * (2.0 + 1.0 / CHANNELS as f32);
+
+        if fft_cost < direct_cost {
+            self.convolve_fft(kernel, kernel_size, wrap_mode)
+        } else {
+            self.convolve_direct(kernel, kernel_size, wrap_mode)
+        }
+    }
+
+    /// Convolves this image with the outer product of `kernel_x` and `kernel_y`, sampling outside
+    /// the image with `wrap_mode`. Both kernels should have an odd length.
+    ///
+    /// This costs one pass per axis, so it is far cheaper than [`Self::convolve_wrapped`] for
+    /// separable kernels like Gaussians and boxes.
+    pub fn convolve_separable(&self, kernel_x: &[f32], kernel_y: &[f32], wrap_mode: WrapMode2D) -> Image<CHANNELS, f32, P> {
+        let width = self.resolution.x as usize;
+        let radius = IVec2::new(kernel_x.len() as i32 / 2, kernel_y.len() as i32 / 2);
+
+        // Each row is read through a padded copy, so the inner loop never has to wrap.
+        let mut rows = vec![P::BLACK; self.pixels.len()];
+
+        rows.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+            let padded: Vec<P> = (-radius.x..width as i32 + radius.x)
+                .map(|x| self.load_wrapped(IVec2::new(x, y as i32), wrap_mode))
+                .collect();
+
+            for (x, c) in row.iter_mut().enumerate() {
+                *c = padded[x..x + kernel_x.len()].iter()
+                    .zip(kernel_x)
+                    .fold(P::BLACK, |c, (&v, &w)| c + v * w);
+            }
+        });
+
+        // Columns are accumulated a whole row at a time, which reads memory in order.
+        let mut pixels = vec![P::BLACK; self.pixels.len()];
+        let kernel_x_sum: f32 = kernel_x.iter().sum();
+
+        pixels.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+            for (j, &w) in kernel_y.iter().enumerate() {
+                let p = IVec2::new(0, y as i32 + j as i32 - radius.y);
+
+                // Rows outside the image are filled with a single color, which the first pass
+                // would have scaled by the sum of its kernel.
+                let Some(p) = wrap_mode.remap(p, self.resolution.as_ivec2()) else {
+                    let fill = self.load_wrapped(p, wrap_mode) * (kernel_x_sum * w);
+                    row.iter_mut().for_each(|c| *c = *c + fill);
+                    continue;
+                };
+
+                let source = &rows[p.y as usize * width..(p.y as usize + 1) * width];
+                row.iter_mut().zip(source).for_each(|(c, &v)| *c = *c + v * w);
+            }
+        });
+
+        Image::new(self.resolution, pixels)
+    }
+
+    fn convolve_direct(&self, kernel: &[f32], kernel_size: UVec2, wrap_mode: WrapMode2D) -> Image<CHANNELS, f32, P> {
+        let kernel_size = kernel_size.as_ivec2();
+
+        self.map_with_positions(|_pixel, pos| {
+            let mut c = P::BLACK;
+
+            for i in -(kernel_size.x / 2)..=(kernel_size.x / 2) {
+                for j in -(kernel_size.y / 2)..=(kernel_size.y / 2) {
+                    let p = IVec2::new(pos.x as i32 + i, pos.y as i32 + j);
+                    let v = self.load_wrapped(p, wrap_mode);
+                    let w = kernel[((j + kernel_size.y / 2) * kernel_size.x + (i + kernel_size.x / 2)) as usize];
+                    c = c + (v * w);
+                }
+            }
+
+            c
+        })
+    }
+
+    /// The size of the transform used to convolve this image with a `kernel_size` kernel.
+    ///
+    /// The transform wraps around, which is exactly [`WrapMode::Repeat`]. Any other edge needs
+    /// room for the kernel on both sides, filled in by `wrap_mode`.
+    fn fft_size(&self, kernel_size: UVec2, wrap_mode: WrapMode2D) -> UVec2 {
+        let padding = kernel_size / 2;
+
+        UVec2::new(
+            match wrap_mode.x {
+                WrapMode::Repeat => self.resolution.x,
+                _ => fast_fft_len(self.resolution.x + 2 * padding.x),
+            },
+            match wrap_mode.y {
+                WrapMode::Repeat => self.resolution.y,
+                _ => fast_fft_len(self.resolution.y + 2 * padding.y),
+            },
+        )
+    }
+
+    fn convolve_fft(&self, kernel: &[f32], kernel_size: UVec2, wrap_mode: WrapMode2D) -> Image<CHANNELS, f32, P> {
+        let size = self.fft_size(kernel_size, wrap_mode);
+        let (width, height) = (size.x as usize, size.y as usize);
+
+        // The image is shifted so its first pixel lands after the padding.
+        let offset = (size - self.resolution).min(kernel_size / 2).as_ivec2();
+
+        let mut planner = FftPlanner::new();
+        let forward = Transform2D::new(&mut planner, width, height, false);
+        let inverse = Transform2D::new(&mut planner, width, height, true);
+
+        // The kernel is applied as a correlation, so each tap is placed at the negated offset.
+        let mut kernel_spectrum = vec![Complex32::ZERO; width * height];
+        let half = (kernel_size / 2).as_ivec2();
+
+        for j in 0..kernel_size.y as i32 {
+            for i in 0..kernel_size.x as i32 {
+                let x = (-(i - half.x)).rem_euclid(width as i32) as usize;
+                let y = (-(j - half.y)).rem_euclid(height as i32) as usize;
+                kernel_spectrum[y * width + x] += kernel[(j * kernel_size.x as i32 + i) as usize];
+            }
+        }
+
+        let kernel_spectrum = forward.process(kernel_spectrum);
+
+        let padded: Vec<P> = (0..height as i32)
+            .flat_map(|y| (0..width as i32).map(move |x| IVec2::new(x, y) - offset))
+            .map(|p| self.load_wrapped(p, wrap_mode))
+            .collect();
+
+        let channels: Vec<Vec<f32>> = (0..CHANNELS)
+            .map(|c| {
+                let data = padded.iter().map(|p| Complex32::new(p.channels()[c], 0.0)).collect();
+                let mut spectrum = forward.process(data);
+
+                spectrum.iter_mut().zip(kernel_spectrum.iter()).for_each(|(v, k)| *v *= k);
+
+                let scale = 1.0 / (width * height) as f32;
+                inverse.process(spectrum).into_iter().map(|v| v.re * scale).collect()
+            })
+            .collect();
+
+        self.map_with_positions(|_pixel, pos| {
+            let p = pos.as_ivec2() + offset;
+            let i = p.y as usize * width + p.x as usize;
+
+            P::from_channels(std::array::from_fn(|c| channels[c][i]))
+        })
+    }
+}
+
+/// A 2D transform of a row by row grid, done as a pass over the rows and then the columns.
+///
+/// Forward transforms leave their result transposed, and inverse transforms expect a transposed
+/// input, which saves transposing back and forth around the pointwise product.
+struct Transform2D {
+    rows: Arc<dyn Fft<f32>>,
+    columns: Arc<dyn Fft<f32>>,
+    width: usize,
+    height: usize,
+    inverse: bool,
+}
+
+impl Transform2D {
+    fn new(planner: &mut FftPlanner<f32>, width: usize, height: usize, inverse: bool) -> Transform2D {
+        let (rows, columns) = if inverse {
+            (planner.plan_fft_inverse(width), planner.plan_fft_inverse(height))
+        } else {
+            (planner.plan_fft_forward(width), planner.plan_fft_forward(height))
+        };
+
+        Transform2D { rows, columns, width, height, inverse }
+    }
+
+    fn process(&self, mut data: Vec<Complex32>) -> Vec<Complex32> {
+        if self.inverse {
+            data.par_chunks_mut(self.height).for_each(|column| self.columns.process(column));
+            let mut data = transpose(&data, self.height, self.width);
+            data.par_chunks_mut(self.width).for_each(|row| self.rows.process(row));
+            data
+        } else {
+            data.par_chunks_mut(self.width).for_each(|row| self.rows.process(row));
+            let mut data = transpose(&data, self.width, self.height);
+            data.par_chunks_mut(self.height).for_each(|column| self.columns.process(column));
+            data
+        }
+    }
+}
+
+/// Transposes a grid of `height` rows of `width` values.
+fn transpose(data: &[Complex32], width: usize, height: usize) -> Vec<Complex32> {
+    let mut transposed = vec![Complex32::ZERO; data.len()];
+
+    transposed.par_chunks_mut(height).enumerate().for_each(|(x, column)| {
+        for (y, v) in column.iter_mut().enumerate() {
+            *v = data[y * width + x];
+        }
+    });
+
+    transposed
+}
+
+/// The smallest length of at least `len` with no prime factors above 5, which transforms much
+/// faster than an arbitrary length.
+fn fast_fft_len(len: u32) -> u32 {
+    (len..)
+        .find(|&n| {
+            let mut n = n;
+            for p in [2, 3, 5] {
+                while n.is_multiple_of(p) {
+                    n /= p;
+                }
+            }
+            n == 1
+        })
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::UVec2;
+
+    use crate::image::{pixel::{rgba::Rgba, Pixel}, sampler::{WrapMode, WrapMode2D}, Image};
+
+    use super::fast_fft_len;
+
+    fn noise(resolution: UVec2) -> Image<4, f32, Rgba<f32>> {
+        let mut state = 0x2545f491u32;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state % 1000) as f32 / 1000.0
+        };
+
+        let pixels = (0..resolution.x * resolution.y).map(|_| Rgba::new(next(), next(), next(), next())).collect();
+        Image::new(resolution, pixels)
+    }
+
+    #[test]
+    fn fft_matches_direct_convolution() {
+        // Odd and even sizes, a kernel that isn't symmetric, and an image smaller than the
+        // kernel in one direction.
+        let image = noise(UVec2::new(13, 6));
+        let kernel_size = UVec2::new(5, 7);
+        let kernel: Vec<f32> = (0..35).map(|i| ((i * 7) % 11) as f32 / 50.0 - 0.05).collect();
+
+        let wrap_modes = [
+            WrapMode2D::BLACK,
+            WrapMode2D::CLAMP,
+            WrapMode2D::REPEAT,
+            WrapMode2D::MIRRORED_REPEAT,
+            WrapMode2D::MIRROR_ONCE,
+            WrapMode2D::new(WrapMode::Repeat, WrapMode::Clamp),
+            WrapMode2D::bordered(Rgba::new(0.2, 0.4, 0.6, 1.0)),
+        ];
+
+        for wrap_mode in wrap_modes {
+            let direct = image.convolve_direct(&kernel, kernel_size, wrap_mode);
+            let fft = image.convolve_fft(&kernel, kernel_size, wrap_mode);
+
+            for (pos, (a, b)) in direct.iter_pixels().zip(fft.iter_pixels()).enumerate() {
+                for (a, b) in a.channels().iter().zip(b.channels()) {
+                    assert!((a - b).abs() < 1e-4, "{wrap_mode:?} at {pos}: {a} != {b}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fast_fft_lengths_have_small_factors() {
+        assert_eq!(fast_fft_len(1), 1);
+        assert_eq!(fast_fft_len(7), 8);
+        assert_eq!(fast_fft_len(61), 64);
+        assert_eq!(fast_fft_len(97), 100);
+        assert_eq!(fast_fft_len(121), 125);
+    }
+}
@@ -0,0 +1,308 @@
+use glam::{IVec2, UVec2};
+use rayon::{iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator}, slice::ParallelSlice};
+
+use super::{pixel::{luma::Luma, Pixel}, Image};
+
+/// The nearest seed of every pixel of an image, found by [`Image::distance_transform`] or
+/// [`Image::jump_flood`].
+///
+/// With seeds at the sites of a Voronoi diagram, the pixels sharing a nearest seed make up its
+/// cells.
+#[derive(Clone)]
+pub struct NearestSeeds {
+    nearest: Vec<Option<UVec2>>,
+    resolution: UVec2,
+}
+
+impl NearestSeeds {
+    /// Finds the nearest of `seeds` for every pixel of an image of `resolution` by jump flooding.
+    ///
+    /// Jump flooding takes a fixed `log2` of the resolution passes over the image regardless of
+    /// the number of seeds, but may pick a seed slightly farther than the nearest one for a few
+    /// pixels.
+    pub fn jump_flood(resolution: UVec2, seeds: &[UVec2]) -> NearestSeeds {
+        let index = |pos: UVec2| (pos.y * resolution.x + pos.x) as usize;
+
+        let mut nearest = vec![None; (resolution.x * resolution.y) as usize];
+
+        for &seed in seeds.iter().filter(|seed| seed.cmplt(resolution).all()) {
+            nearest[index(seed)] = Some(seed);
+        }
+
+        // Steps halve down to a single pixel, followed by another single pixel step that fixes
+        // most of the errors left behind.
+        let first_step = resolution.max_element().next_power_of_two() / 2;
+        let steps = std::iter::successors(Some(first_step), |step| Some(step / 2))
+            .take_while(|&step| step > 0)
+            .chain([1]);
+
+        for step in steps {
+            let step = step as i32;
+
+            nearest = (0..nearest.len())
+                .into_par_iter()
+                .map(|i| {
+                    let pos = UVec2::new(i as u32 % resolution.x, i as u32 / resolution.x);
+                    let mut best = nearest[i];
+
+                    for y in -1..=1 {
+                        for x in -1..=1 {
+                            let neighbor = pos.as_ivec2() + IVec2::new(x, y) * step;
+
+                            if neighbor.cmplt(IVec2::ZERO).any() || neighbor.cmpge(resolution.as_ivec2()).any() {
+                                continue;
+                            }
+
+                            let Some(seed) = nearest[index(neighbor.as_uvec2())] else {
+                                continue;
+                            };
+
+                            if best.is_none_or(|best| distance_squared(pos, seed) < distance_squared(pos, best)) {
+                                best = Some(seed);
+                            }
+                        }
+                    }
+
+                    best
+                })
+                .collect();
+        }
+
+        NearestSeeds { nearest, resolution }
+    }
+
+    pub fn resolution(&self) -> UVec2 {
+        self.resolution
+    }
+
+    /// The position of the seed nearest to `pos`, or `None` if there are no seeds.
+    pub fn nearest(&self, pos: UVec2) -> Option<UVec2> {
+        self.nearest[(pos.y * self.resolution.x + pos.x) as usize]
+    }
+
+    /// The distance from `pos` to its nearest seed, in pixels. Infinite if there are no seeds.
+    pub fn distance(&self, pos: UVec2) -> f32 {
+        match self.nearest(pos) {
+            Some(seed) => distance_squared(pos, seed).sqrt(),
+            None => f32::INFINITY,
+        }
+    }
+
+    /// The distance from every pixel to its nearest seed, in pixels.
+    pub fn distances(&self) -> Image<1, f32, Luma<f32>> {
+        Image::<1, f32, Luma<f32>>::new_fill(self.resolution, Luma::BLACK).map_with_positions(|_, pos| Luma { v: self.distance(pos) })
+    }
+}
+
+impl<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>> Image<CHANNELS, f32, P> {
+    /// Finds the exact nearest seed of every pixel, where seeds are the pixels for which
+    /// `is_seed` is true.
+    ///
+    /// This is the linear time Euclidean distance transform of Felzenszwalb and Huttenlocher,
+    /// which finds the distance along each row and then combines the rows along each column.
+    pub fn distance_transform<Seed>(&self, is_seed: Seed) -> NearestSeeds
+    where
+        Seed: Fn(&P) -> bool + Sync,
+    {
+        let resolution = self.resolution;
+        let width = resolution.x as usize;
+
+        if width == 0 || resolution.y == 0 {
+            return NearestSeeds { nearest: Vec::new(), resolution };
+        }
+
+        // The nearest seed within each row.
+        let rows: Vec<Option<(f64, usize)>> = self.pixels
+            .par_chunks(width)
+            .flat_map_iter(|row| {
+                let f: Vec<f64> = row.iter().map(|p| if is_seed(p) { 0.0 } else { f64::INFINITY }).collect();
+                lower_envelope(&f)
+            })
+            .collect();
+
+        // The nearest row of each column, measured to the nearest seed within that row.
+        let columns: Vec<Vec<Option<(f64, usize)>>> = (0..width)
+            .into_par_iter()
+            .map(|x| {
+                let f: Vec<f64> = rows.iter()
+                    .skip(x)
+                    .step_by(width)
+                    .map(|nearest| nearest.map_or(f64::INFINITY, |(d, _)| d))
+                    .collect();
+
+                lower_envelope(&f)
+            })
+            .collect();
+
+        let nearest = (0..self.pixels.len())
+            .into_par_iter()
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+
+                columns[x][y].map(|(_, row)| {
+                    let (_, column) = rows[row * width + x].unwrap();
+                    UVec2::new(column as u32, row as u32)
+                })
+            })
+            .collect();
+
+        NearestSeeds { nearest, resolution }
+    }
+
+    /// Finds the approximate nearest seed of every pixel by jump flooding, where seeds are the
+    /// pixels for which `is_seed` is true.
+    ///
+    /// See [`NearestSeeds::jump_flood`].
+    pub fn jump_flood<Seed>(&self, is_seed: Seed) -> NearestSeeds
+    where
+        Seed: Fn(&P) -> bool + Sync,
+    {
+        let seeds: Vec<UVec2> = self.iter_pixels_with_positions()
+            .filter(|(pixel, _)| is_seed(pixel))
+            .map(|(_, pos)| pos)
+            .collect();
+
+        NearestSeeds::jump_flood(self.resolution, &seeds)
+    }
+}
+
+fn distance_squared(a: UVec2, b: UVec2) -> f32 {
+    a.as_vec2().distance_squared(b.as_vec2())
+}
+
+/// The lower envelope of the parabolas `(q - p)^2 + f[p]`, giving for every `q` the lowest value
+/// and the `p` it comes from, or `None` if every `f` is infinite.
+fn lower_envelope(f: &[f64]) -> Vec<Option<(f64, usize)>> {
+    // The parabolas making up the envelope, and where each of them starts.
+    let mut parabolas: Vec<usize> = Vec::new();
+    let mut starts: Vec<f64> = Vec::new();
+
+    let intersection = |p: usize, q: usize| {
+        let (fp, fq) = (f[p], f[q]);
+        let (p, q) = (p as f64, q as f64);
+        ((fq + q * q) - (fp + p * p)) / (2.0 * (q - p))
+    };
+
+    for q in (0..f.len()).filter(|&q| f[q].is_finite()) {
+        let mut start = f64::NEG_INFINITY;
+
+        // Parabolas hidden by the new one are dropped.
+        while let Some(&p) = parabolas.last() {
+            start = intersection(p, q);
+
+            if start > *starts.last().unwrap() {
+                break;
+            }
+
+            parabolas.pop();
+            starts.pop();
+            start = f64::NEG_INFINITY;
+        }
+
+        parabolas.push(q);
+        starts.push(start);
+    }
+
+    if parabolas.is_empty() {
+        return vec![None; f.len()];
+    }
+
+    let mut k = 0;
+
+    (0..f.len())
+        .map(|q| {
+            while k + 1 < parabolas.len() && starts[k + 1] < q as f64 {
+                k += 1;
+            }
+
+            let p = parabolas[k];
+            let d = q as f64 - p as f64;
+            Some((d * d + f[p], p))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::UVec2;
+
+    use crate::image::{pixel::luma::Luma, Image};
+
+    use super::{distance_squared, lower_envelope};
+
+    /// A small xorshift generator, so that the tests are repeatable.
+    fn random(seed: u32) -> impl FnMut() -> u32 {
+        let mut state = seed;
+
+        move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        }
+    }
+
+    #[test]
+    fn lower_envelope_matches_brute_force() {
+        let mut next = random(0x9e3779b9);
+
+        for len in [1, 2, 5, 17, 64] {
+            for _ in 0..20 {
+                let f: Vec<f64> = (0..len)
+                    .map(|_| match next() % 4 {
+                        0 => f64::INFINITY,
+                        _ => (next() % 50) as f64,
+                    })
+                    .collect();
+
+                let envelope = lower_envelope(&f);
+
+                for (q, nearest) in envelope.into_iter().enumerate() {
+                    let lowest = (0..len).map(|p| (q as f64 - p as f64).powi(2) + f[p]).fold(f64::INFINITY, f64::min);
+
+                    match nearest {
+                        Some((value, p)) => {
+                            assert_eq!(value, lowest, "f = {f:?}, q = {q}");
+                            assert_eq!((q as f64 - p as f64).powi(2) + f[p], lowest, "f = {f:?}, q = {q}");
+                        },
+                        None => assert!(lowest.is_infinite(), "f = {f:?}, q = {q}"),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn distance_transform_matches_brute_force() {
+        let mut next = random(0x51ed270b);
+        let resolution = UVec2::new(23, 17);
+
+        for seed_chance in [2, 20, 200] {
+            let pixels = (0..resolution.x * resolution.y).map(|_| Luma { v: next().is_multiple_of(seed_chance) as u32 as f32 }).collect();
+            let image = Image::<1, f32, Luma<f32>>::new(resolution, pixels);
+
+            let seeds: Vec<UVec2> = image.iter_pixels_with_positions().filter(|(p, _)| p.v > 0.0).map(|(_, pos)| pos).collect();
+            let nearest = image.distance_transform(|p| p.v > 0.0);
+
+            for (_, pos) in image.iter_pixels_with_positions() {
+                let expected = seeds.iter().map(|&seed| distance_squared(pos, seed)).fold(f32::INFINITY, f32::min);
+
+                match nearest.nearest(pos) {
+                    Some(seed) => {
+                        assert!(seeds.contains(&seed));
+                        assert_eq!(distance_squared(pos, seed), expected, "at {pos}");
+                    },
+                    None => assert!(seeds.is_empty()),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn no_seeds_are_infinitely_far() {
+        let image = Image::<1, f32, Luma<f32>>::new_fill(UVec2::new(4, 3), Luma { v: 0.0 });
+        let nearest = image.distance_transform(|p| p.v > 0.0);
+
+        assert!(nearest.distances().iter_pixels().all(|d| d.v.is_infinite()));
+    }
+}
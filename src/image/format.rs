@@ -18,8 +18,12 @@ pub trait PixelFormat:
 
     fn bytes() -> u8;
 
+    /// Decode a single sample. One byte is read as an 8-bit unsigned integer and two bytes as
+    /// a big-endian 16-bit unsigned integer, both normalized to `[0, 1]`. Formats that are four
+    /// bytes wide also accept their own big-endian encoding, as written by [`Self::to_bytes`].
     fn from_bytes(bytes: &[u8]) -> Self;
 
+    /// Encode a single sample in [`Self::bytes`] big-endian bytes.
     fn to_bytes(self) -> Vec<u8>;
 
     fn from_scaled_float(v: f32) -> Self;
@@ -67,11 +71,7 @@ impl PixelFormat for f16 {
     }
 
     fn from_bytes(bytes: &[u8]) -> Self {
-        match bytes.len() {
-            1 => f16::from_f32(bytes[0].to_scaled_float()),
-            2 => f16::from_le_bytes([bytes[0], bytes[1]]),
-            _ => panic!(),
-        }
+        f16::from_f32(f32::from_bytes(bytes))
     }
 
     fn to_bytes(self) -> Vec<u8> {
@@ -102,8 +102,8 @@ impl PixelFormat for f32 {
     fn from_bytes(bytes: &[u8]) -> Self {
         match bytes.len() {
             1 => (bytes[0] as f32) / 255.0,
-            2 => f16::from_le_bytes([bytes[0], bytes[1]]).to_f32(),
-            4 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            2 => u16::from_be_bytes([bytes[0], bytes[1]]) as f32 / u16::MAX as f32,
+            4 => f32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
             _ => panic!(),
         }
     }
@@ -0,0 +1,88 @@
+use std::{io::Write, time::Duration};
+
+use glam::UVec2;
+
+use super::{animation::{Animation, Blend, Compositor, Disposal}, format::PixelFormat, pixel::{rgba::Rgba, Pixel}, Image, ImageError};
+
+impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Animation<CHANNELS, F, P> {
+    /// Reads every frame of a GIF.
+    pub(super) fn read_gif(data: &[u8]) -> Result<Animation<CHANNELS, F, P>, ImageError> {
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+
+        let mut decoder = options.read_info(data)?;
+        let mut compositor = Compositor::new(UVec2::new(decoder.width() as u32, decoder.height() as u32));
+
+        // GIFs count repetitions after the first play, and play once without a loop extension.
+        let loop_count = match decoder.repeat() {
+            gif::Repeat::Infinite => 0,
+            gif::Repeat::Finite(repetitions) => repetitions as u32 + 1,
+        };
+
+        while let Some(frame) = decoder.read_next_frame()? {
+            let pixels = frame.buffer
+                .chunks_exact(4)
+                .map(|c| Rgba::new(c[0], c[1], c[2], c[3]))
+                .collect();
+
+            let patch = Image::<4, u8, Rgba<u8>>::new(UVec2::new(frame.width as u32, frame.height as u32), pixels);
+
+            compositor.push(
+                &patch.to_format(),
+                UVec2::new(frame.left as u32, frame.top as u32),
+                // Transparent pixels leave the canvas untouched.
+                Blend::Over,
+                match frame.dispose {
+                    gif::DisposalMethod::Any | gif::DisposalMethod::Keep => Disposal::Keep,
+                    gif::DisposalMethod::Background => Disposal::Background,
+                    gif::DisposalMethod::Previous => Disposal::Previous,
+                },
+                Duration::from_millis(frame.delay as u64 * 10),
+            );
+        }
+
+        Ok(compositor.finish(loop_count))
+    }
+
+    /// Writes every frame as a GIF, quantizing each to its own 256 color palette. Pixels with an
+    /// alpha below one half are transparent.
+    pub(super) fn write_gif<W: Write>(&self, writer: W) -> Result<(), ImageError> {
+        let resolution = self.frames[0].image.resolution;
+
+        if resolution.x > u16::MAX as u32 || resolution.y > u16::MAX as u32 {
+            return Err(ImageError::BadResolution(resolution, String::from("gif")));
+        }
+
+        let mut encoder = gif::Encoder::new(writer, resolution.x as u16, resolution.y as u16, &[])?;
+
+        encoder.set_repeat(match self.loop_count {
+            0 => gif::Repeat::Infinite,
+            plays => gif::Repeat::Finite((plays - 1).min(u16::MAX as u32) as u16),
+        })?;
+
+        for frame in self.frames.iter() {
+            if frame.image.resolution != resolution {
+                return Err(ImageError::BadResolution(frame.image.resolution, String::from("gif frames of different sizes")));
+            }
+
+            let mut data: Vec<u8> = frame.image.pixels.iter()
+                .map(|pixel| pixel.convert::<Rgba<F>>())
+                .flat_map(|pixel| {
+                    let to_byte = |v: F| (v.to_scaled_float().clamp(0.0, 1.0) * 255.0).round() as u8;
+                    let alpha = if pixel.a.to_scaled_float() < 0.5 { 0 } else { 255 };
+
+                    [to_byte(pixel.r), to_byte(pixel.g), to_byte(pixel.b), alpha]
+                })
+                .collect();
+
+            let mut gif_frame = gif::Frame::from_rgba_speed(resolution.x as u16, resolution.y as u16, &mut data, 10);
+            gif_frame.delay = (frame.delay.as_millis() / 10).min(u16::MAX as u128) as u16;
+            // Every frame covers the whole canvas, so transparent areas must not show the last one.
+            gif_frame.dispose = gif::DisposalMethod::Background;
+
+            encoder.write_frame(&gif_frame)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::pass::luminance::LuminanceMethod;
+
+use super::{pixel::{rgb::Rgb, Pixel}, Image};
+
+/// Counts of values in `[0, 1]`, split into equally sized bins. Values outside that range are
+/// counted in the first or last bin.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    counts: Vec<u32>,
+}
+
+impl Histogram {
+    /// Creates an empty histogram with `bins` bins.
+    ///
+    /// # Panics
+    ///
+    /// If `bins` is zero.
+    pub fn new(bins: usize) -> Histogram {
+        assert!(bins > 0, "histogram needs at least one bin");
+
+        Histogram { counts: vec![0; bins] }
+    }
+
+    /// Creates a histogram with `bins` bins of `values`.
+    pub fn from_values(bins: usize, values: impl IntoIterator<Item = f32>) -> Histogram {
+        let mut histogram = Histogram::new(bins);
+        values.into_iter().for_each(|v| histogram.add(v));
+        histogram
+    }
+
+    /// Counts `value` in its bin.
+    pub fn add(&mut self, value: f32) {
+        let bin = self.bin(value);
+        self.counts[bin] += 1;
+    }
+
+    /// The index of the bin `value` falls in.
+    pub fn bin(&self, value: f32) -> usize {
+        let bins = self.counts.len();
+
+        if value.is_nan() {
+            return 0;
+        }
+
+        ((value * bins as f32) as usize).min(bins - 1)
+    }
+
+    /// The number of bins.
+    pub fn bins(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn counts(&self) -> &[u32] {
+        &self.counts
+    }
+
+    /// The number of values counted in total.
+    pub fn total(&self) -> u32 {
+        self.counts.iter().sum()
+    }
+
+    /// Limits every bin to `limit` values, spreading what is cut off evenly over all bins.
+    ///
+    /// Bins may end up slightly above `limit` after the spread, like in most implementations of
+    /// contrast-limited equalization.
+    pub fn clip(&mut self, limit: u32) {
+        let excess: u32 = self.counts.iter_mut()
+            .map(|count| {
+                let cut = count.saturating_sub(limit);
+                *count -= cut;
+                cut
+            })
+            .sum();
+
+        let bins = self.counts.len() as u64;
+        let (share, remainder) = (excess / bins as u32, excess as u64 % bins);
+
+        // The remainder goes to bins spread across the whole range, rather than the first few.
+        for (i, count) in (0..).zip(self.counts.iter_mut()) {
+            *count += share;
+
+            if (i + 1) * remainder / bins > i * remainder / bins {
+                *count += 1;
+            }
+        }
+    }
+
+    /// The fraction of values in each bin or any bin before it.
+    pub fn cumulative(&self) -> Vec<f32> {
+        let total = self.total().max(1) as f32;
+        let mut sum = 0;
+
+        self.counts.iter()
+            .map(|count| {
+                sum += count;
+                sum as f32 / total
+            })
+            .collect()
+    }
+
+    /// The mapping from each bin to the value that spreads the counted values evenly over
+    /// `[0, 1]`, which is what histogram equalization applies.
+    pub fn equalization(&self) -> Vec<f32> {
+        let cumulative = self.cumulative();
+
+        // The lowest occupied bin maps to black, so the output uses the full range.
+        let min = cumulative.iter().copied().find(|&c| c > 0.0).unwrap_or(0.0);
+
+        // With a single occupied bin there is nothing to spread, so each bin maps to its centre.
+        if min >= 1.0 {
+            let bins = self.bins() as f32;
+            return (0..self.bins()).map(|i| (i as f32 + 0.5) / bins).collect();
+        }
+
+        cumulative.iter().map(|c| ((c - min) / (1.0 - min)).max(0.0)).collect()
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        self.counts.iter_mut().zip(&other.counts).for_each(|(a, b)| *a += b);
+    }
+}
+
+impl<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>> Image<CHANNELS, f32, P> {
+    /// A [`Histogram`] with `bins` bins of each channel of this image.
+    pub fn histograms(&self, bins: usize) -> [Histogram; CHANNELS] {
+        self.pixels
+            .par_iter()
+            .fold(
+                || std::array::from_fn(|_| Histogram::new(bins)),
+                |mut histograms: [Histogram; CHANNELS], pixel| {
+                    histograms.iter_mut().zip(pixel.channels()).for_each(|(h, v)| h.add(v));
+                    histograms
+                },
+            )
+            .reduce(
+                || std::array::from_fn(|_| Histogram::new(bins)),
+                |mut a, b| {
+                    a.iter_mut().zip(b).for_each(|(a, b)| a.merge(&b));
+                    a
+                },
+            )
+    }
+
+    /// A [`Histogram`] with `bins` bins of the luminance of this image, computed with `method`.
+    pub fn luminance_histogram(&self, bins: usize, method: LuminanceMethod) -> Histogram {
+        self.pixels
+            .par_iter()
+            .fold(
+                || Histogram::new(bins),
+                |mut histogram, pixel| {
+                    let rgb: Rgb<f32> = pixel.convert();
+                    histogram.add(method.luminance(rgb.r, rgb.g, rgb.b));
+                    histogram
+                },
+            )
+            .reduce(
+                || Histogram::new(bins),
+                |mut a, b| {
+                    a.merge(&b);
+                    a
+                },
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Histogram;
+
+    /// A histogram with the given counts in its bins.
+    fn with_counts(counts: &[u32]) -> Histogram {
+        let bins = counts.len();
+        let values = counts.iter().enumerate().flat_map(|(i, &count)| std::iter::repeat_n((i as f32 + 0.5) / bins as f32, count as usize));
+
+        Histogram::from_values(bins, values)
+    }
+
+    #[test]
+    fn bins_values() {
+        let histogram = Histogram::from_values(4, [0.0, 0.24, 0.25, 0.99, 1.0, 7.0, -1.0, f32::NAN]);
+        assert_eq!(histogram.counts(), [4, 1, 0, 3]);
+    }
+
+    #[test]
+    fn clipping_keeps_the_total() {
+        for (counts, limit) in [
+            (vec![100, 0, 3, 50, 7, 0, 0, 2], 10),
+            (vec![1000, 0, 0, 0, 0, 0, 0], 1),
+            (vec![5, 5, 5, 5], 5),
+            (vec![9, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], 2),
+        ] {
+            let mut histogram = with_counts(&counts);
+            assert_eq!(histogram.counts(), counts);
+
+            let total = histogram.total();
+            histogram.clip(limit);
+
+            assert_eq!(histogram.total(), total, "{counts:?}");
+
+            // What is cut off is spread evenly, so bins end up at most one share above the limit.
+            let excess: u32 = counts.iter().map(|c| c.saturating_sub(limit)).sum();
+            let share = excess.div_ceil(counts.len() as u32);
+
+            for &count in histogram.counts() {
+                assert!(count <= limit + share, "{counts:?} clipped to {limit}: {:?}", histogram.counts());
+            }
+        }
+    }
+
+    #[test]
+    fn clipping_spreads_the_remainder() {
+        let mut histogram = with_counts(&[7, 0, 0, 0, 0, 0, 0, 0]);
+        histogram.clip(2);
+
+        // Five values are cut off, one each for five bins spread over the whole range.
+        assert_eq!(histogram.counts(), [2, 1, 0, 1, 1, 0, 1, 1]);
+
+        let mut histogram = with_counts(&[0, 0, 0, 0, 0, 0, 0, 3]);
+        histogram.clip(1);
+        assert_eq!(histogram.counts(), [0, 0, 0, 1, 0, 0, 0, 2]);
+    }
+
+    #[test]
+    fn equalization_spreads_two_values_to_the_ends() {
+        let histogram = Histogram::from_values(256, [0.2; 10].into_iter().chain([0.7; 30]));
+        let equalization = histogram.equalization();
+
+        assert_eq!(equalization[histogram.bin(0.2)], 0.0);
+        assert_eq!(equalization[histogram.bin(0.7)], 1.0);
+        assert!(equalization.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn equalizing_a_single_value_keeps_it() {
+        let histogram = Histogram::from_values(4, [0.6; 10]);
+        assert_eq!(histogram.equalization(), [0.125, 0.375, 0.625, 0.875]);
+    }
+}
@@ -0,0 +1,155 @@
+use std::marker::PhantomData;
+
+use glam::{IVec2, UVec2};
+use rayon::{iter::{IndexedParallelIterator, ParallelIterator}, slice::ParallelSliceMut};
+
+use super::{pixel::Pixel, Image};
+
+/// A summed-area table of an image, which sums or averages any rectangle of it in constant time.
+///
+/// Tables built with [`Self::with_variance`] also hold the sums of squares, so they can give the
+/// variance of a rectangle too. Sums are kept in `f64`, so large tables don't lose precision.
+pub struct SummedAreaTable<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>> {
+    /// The sums of the pixels above and to the left of each corner, with a row and column of
+    /// zeroes in front.
+    sums: Vec<[f64; CHANNELS]>,
+    squares: Option<Vec<[f64; CHANNELS]>>,
+    resolution: UVec2,
+    pixel: PhantomData<P>,
+}
+
+impl<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>> SummedAreaTable<CHANNELS, P> {
+    /// Builds the table of `image`, for sums and averages.
+    pub fn new(image: &Image<CHANNELS, f32, P>) -> SummedAreaTable<CHANNELS, P> {
+        SummedAreaTable {
+            sums: build_table(image, |v| v),
+            squares: None,
+            resolution: image.resolution(),
+            pixel: PhantomData,
+        }
+    }
+
+    /// Builds the table of `image`, along with the sums of squares needed by
+    /// [`Self::variance`].
+    pub fn with_variance(image: &Image<CHANNELS, f32, P>) -> SummedAreaTable<CHANNELS, P> {
+        SummedAreaTable {
+            squares: Some(build_table(image, |v| v * v)),
+            ..Self::new(image)
+        }
+    }
+
+    pub fn resolution(&self) -> UVec2 {
+        self.resolution
+    }
+
+    /// The number of pixels of the image in the rectangle from `min` up to, but not including,
+    /// `max`. Parts of the rectangle outside the image are ignored.
+    pub fn area(&self, min: IVec2, max: IVec2) -> u32 {
+        let (min, max) = self.clip(min, max);
+        let size = max.saturating_sub(min);
+        size.x * size.y
+    }
+
+    /// The sum of the pixels in the rectangle from `min` up to, but not including, `max`. Parts
+    /// of the rectangle outside the image are ignored.
+    pub fn sum(&self, min: IVec2, max: IVec2) -> P {
+        P::from_channels(self.query(&self.sums, min, max).map(|v| v as f32))
+    }
+
+    /// The average of the pixels in the rectangle from `min` up to, but not including, `max`.
+    /// Parts of the rectangle outside the image are ignored, and an empty rectangle is black.
+    pub fn mean(&self, min: IVec2, max: IVec2) -> P {
+        let area = self.area(min, max);
+
+        if area == 0 {
+            return P::BLACK;
+        }
+
+        let sum = self.query(&self.sums, min, max);
+        P::from_channels(sum.map(|v| (v / area as f64) as f32))
+    }
+
+    /// The variance of each channel of the pixels in the rectangle from `min` up to, but not
+    /// including, `max`. Parts of the rectangle outside the image are ignored, and an empty
+    /// rectangle is black.
+    ///
+    /// # Panics
+    ///
+    /// If the table wasn't built with [`Self::with_variance`].
+    pub fn variance(&self, min: IVec2, max: IVec2) -> P {
+        let squares = self.squares.as_ref().expect("table was built without variance");
+        let area = self.area(min, max) as f64;
+
+        if area == 0.0 {
+            return P::BLACK;
+        }
+
+        let sum = self.query(&self.sums, min, max);
+        let squares = self.query(squares, min, max);
+
+        P::from_channels(std::array::from_fn(|c| {
+            let mean = sum[c] / area;
+            (squares[c] / area - mean * mean).max(0.0) as f32
+        }))
+    }
+
+    fn clip(&self, min: IVec2, max: IVec2) -> (UVec2, UVec2) {
+        let resolution = self.resolution.as_ivec2();
+        (min.clamp(IVec2::ZERO, resolution).as_uvec2(), max.clamp(IVec2::ZERO, resolution).as_uvec2())
+    }
+
+    fn query(&self, table: &[[f64; CHANNELS]], min: IVec2, max: IVec2) -> [f64; CHANNELS] {
+        let (min, max) = self.clip(min, max);
+
+        if min.x >= max.x || min.y >= max.y {
+            return [0.0; CHANNELS];
+        }
+
+        let stride = self.resolution.x as usize + 1;
+        let corner = |p: UVec2| table[p.y as usize * stride + p.x as usize];
+
+        let (a, b, c, d) = (corner(min), corner(UVec2::new(max.x, min.y)), corner(UVec2::new(min.x, max.y)), corner(max));
+
+        std::array::from_fn(|i| d[i] - b[i] - c[i] + a[i])
+    }
+}
+
+impl<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>> Image<CHANNELS, f32, P> {
+    /// Builds the [`SummedAreaTable`] of this image.
+    pub fn summed_area_table(&self) -> SummedAreaTable<CHANNELS, P> {
+        SummedAreaTable::new(self)
+    }
+}
+
+/// Sums `f` of every channel of `image` above and to the left of each pixel corner.
+fn build_table<const CHANNELS: usize, P, Sum>(image: &Image<CHANNELS, f32, P>, f: Sum) -> Vec<[f64; CHANNELS]>
+where
+    P: Pixel<CHANNELS, Format = f32>,
+    Sum: Fn(f64) -> f64 + Sync,
+{
+    let resolution = image.resolution();
+    let stride = resolution.x as usize + 1;
+
+    let mut table = vec![[0.0; CHANNELS]; stride * (resolution.y as usize + 1)];
+
+    // Rows are summed on their own first, then each row adds the running total above it.
+    table.par_chunks_mut(stride).skip(1).enumerate().for_each(|(y, row)| {
+        let mut total = [0.0; CHANNELS];
+
+        for (x, sum) in row.iter_mut().skip(1).enumerate() {
+            let channels = image.load(UVec2::new(x as u32, y as u32)).channels();
+            total.iter_mut().zip(channels).for_each(|(t, v)| *t += f(v as f64));
+            *sum = total;
+        }
+    });
+
+    for y in 1..resolution.y as usize {
+        let (above, below) = table.split_at_mut((y + 1) * stride);
+
+        for (sum, previous) in below[..stride].iter_mut().zip(&above[y * stride..]) {
+            sum.iter_mut().zip(previous).for_each(|(s, p)| *s += p);
+        }
+    }
+
+    table
+}
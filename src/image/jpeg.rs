@@ -0,0 +1,288 @@
+use std::{collections::BTreeMap, io::Write};
+
+use glam::UVec2;
+
+use super::{format::PixelFormat, metadata::ImageMetadata, pixel::{luma::Luma, rgb::Rgb, Pixel}, Image, ImageError, WriteOptions};
+
+/// The comment marker, whose segments hold the text entries of the metadata.
+const COM: u8 = 0xFE;
+
+impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Image<CHANNELS, F, P> {
+    /// Reads a JPEG, along with the text entries stored in its comment segments.
+    pub(super) fn read_jpeg(data: &[u8]) -> Result<(Image<CHANNELS, F, P>, ImageMetadata), ImageError> {
+        let mut decoder = jpeg_decoder::Decoder::new(data);
+        let im_data = decoder.decode()?;
+        let info = decoder.info().unwrap();
+
+        // Lossless JPEGs can have any precision from 2 to 16 bits. Anything but 8 bits is decoded
+        // to native-endian 16-bit samples that go up to the precision's maximum.
+        let precision = segments(data)
+            .find(|&(marker, _)| is_frame_header(marker))
+            .and_then(|(_, segment)| segment.first().copied())
+            .unwrap_or(8);
+        let max = ((1u32 << precision) - 1) as f32;
+        let sample = |bytes: &[u8]| F::from_scaled_float(u16::from_ne_bytes([bytes[0], bytes[1]]) as f32 / max);
+
+        let pixels = match info.pixel_format {
+            jpeg_decoder::PixelFormat::L8 | jpeg_decoder::PixelFormat::L16 if precision != 8 => {
+                im_data
+                    .chunks_exact(2)
+                    .map(|bytes| P::from_pixel(Luma::<F>::from_channels([sample(bytes)])))
+                    .collect()
+            },
+            jpeg_decoder::PixelFormat::L8 | jpeg_decoder::PixelFormat::L16 => {
+                im_data
+                    .into_iter()
+                    .map(|v| P::from_pixel(Luma::<F>::from_channels([F::from_scaled_float(v.to_scaled_float())])))
+                    .collect()
+            },
+            jpeg_decoder::PixelFormat::RGB24 if precision != 8 => {
+                im_data
+                    .chunks_exact(6)
+                    .map(|bytes| P::from_pixel(Rgb::<F>::from_channels([sample(&bytes[0..2]), sample(&bytes[2..4]), sample(&bytes[4..6])])))
+                    .collect()
+            },
+            jpeg_decoder::PixelFormat::RGB24 => {
+                im_data
+                    .chunks_exact(3)
+                    .map(|bytes| P::from_pixel(Rgb::<F>::from_channels(
+                        [bytes[0], bytes[1], bytes[2]].map(|v| F::from_scaled_float(v.to_scaled_float()))
+                    )))
+                    .collect()
+            },
+            jpeg_decoder::PixelFormat::CMYK32 => {
+                im_data
+                    .chunks_exact(4)
+                    .map(|bytes| {
+                        let k = 1.0 - bytes[3].to_scaled_float();
+
+                        P::from_pixel(Rgb::<F>::from_channels(
+                            [bytes[0], bytes[1], bytes[2]].map(|v| F::from_scaled_float((1.0 - v.to_scaled_float()) * k))
+                        ))
+                    })
+                    .collect()
+            },
+        };
+
+        // Comments written by `write_jpeg` hold a key and value separated by a nul byte.
+        let text = segments(data)
+            .filter(|&(marker, _)| marker == COM)
+            .filter_map(|(_, segment)| {
+                let (key, value) = std::str::from_utf8(segment).ok()?.split_once('\0')?;
+                Some((key.to_string(), value.to_string()))
+            })
+            .collect();
+
+        let image = Self::new(
+            UVec2::new(info.width as u32, info.height as u32),
+            pixels,
+        );
+
+        Ok((image, ImageMetadata { text, ..ImageMetadata::default() }))
+    }
+
+    pub(super) fn write_jpeg<W: Write>(&self, mut writer: W, options: &WriteOptions) -> Result<(), ImageError> {
+        if self.resolution.x > u16::MAX as u32 || self.resolution.y > u16::MAX as u32 {
+            return Err(ImageError::BadResolution(self.resolution, String::from("jpeg")));
+        }
+
+        // JPEG has no alpha channel, so alpha is folded into the color channels the same way
+        // `FromPixel` does when dropping it.
+        let (data, color_type): (Vec<u8>, _) = match CHANNELS {
+            1 | 2 => (
+                self.pixels.iter()
+                    .map(|p| p.convert::<Luma<F>>())
+                    .map(|p| u8::from_scaled_float(p.v.to_scaled_float()))
+                    .collect(),
+                jpeg_encoder::ColorType::Luma,
+            ),
+            3 | 4 => (
+                self.pixels.iter()
+                    .flat_map(|p| p.convert::<Rgb<F>>().channels())
+                    .map(|v| u8::from_scaled_float(v.to_scaled_float()))
+                    .collect(),
+                jpeg_encoder::ColorType::Rgb,
+            ),
+            _ => return Err(ImageError::BadChannelCount(CHANNELS, String::from("jpeg"))),
+        };
+
+        let comments = comment_segments(&options.metadata.text)?;
+
+        let mut encoded = Vec::new();
+        let encoder = jpeg_encoder::Encoder::new(&mut encoded, options.quality.clamp(1, 100));
+        encoder.encode(&data, self.resolution.x as u16, self.resolution.y as u16, color_type)?;
+
+        // The comments go straight after the start of image marker.
+        writer.write_all(&encoded[..2])?;
+        writer.write_all(&comments)?;
+        writer.write_all(&encoded[2..])?;
+
+        Ok(())
+    }
+}
+
+/// Comment segments holding each text entry as its key and value separated by a nul byte.
+fn comment_segments(text: &BTreeMap<String, String>) -> Result<Vec<u8>, ImageError> {
+    let mut segments = Vec::new();
+
+    for (key, value) in text {
+        // The segment length counts its own two bytes.
+        let length = u16::try_from(key.len() + value.len() + 3)
+            .map_err(|_| ImageError::TextTooLong(key.clone(), String::from("jpeg")))?;
+
+        segments.extend([0xFF, COM]);
+        segments.extend(length.to_be_bytes());
+        segments.extend(key.as_bytes());
+        segments.push(0);
+        segments.extend(value.as_bytes());
+    }
+
+    Ok(segments)
+}
+
+/// Whether `marker` starts a frame header, rather than DHT, JPG or DAC which share its range.
+fn is_frame_header(marker: u8) -> bool {
+    matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC)
+}
+
+/// The markers and contents of the segments in `data` before the image data.
+fn segments(data: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    // Skip the start of image marker.
+    let mut i = 2;
+
+    std::iter::from_fn(move || {
+        while i + 1 < data.len() {
+            if data[i] != 0xFF {
+                return None;
+            }
+
+            let marker = data[i + 1];
+
+            match marker {
+                // Fill bytes.
+                0xFF => i += 1,
+                // Markers without a segment.
+                0x01 | 0xD0..=0xD7 => i += 2,
+                // Start of scan, after which comes the image data.
+                0xDA => return None,
+                _ => {
+                    let length = u16::from_be_bytes([*data.get(i + 2)?, *data.get(i + 3)?]) as usize;
+                    let segment = data.get(i + 4..i + 2 + length.max(2))?;
+                    i += 2 + length;
+
+                    return Some((marker, segment));
+                },
+            }
+        }
+
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use glam::UVec2;
+
+    use crate::image::{pixel::{luma::Luma, rgb::Rgb}, Image, ImageFormat, WriteOptions};
+
+    /// Encodes a single component lossless JPEG, predicting each sample from the one to its left.
+    fn lossless_jpeg(resolution: UVec2, precision: u8, samples: &[u16]) -> Vec<u8> {
+        let mut data = vec![0xFF, 0xD8];
+
+        // A Huffman table giving each difference category `s` up to 12 bits the 4-bit code `s`.
+        data.extend([0xFF, 0xC4, 0x00, 0x20, 0x00]);
+        data.extend([0, 0, 0, 13, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend(0..13);
+
+        data.extend([0xFF, 0xC3, 0x00, 0x0B, precision]);
+        data.extend((resolution.y as u16).to_be_bytes());
+        data.extend((resolution.x as u16).to_be_bytes());
+        data.extend([0x01, 0x01, 0x11, 0x00]);
+
+        data.extend([0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x00, 0x01, 0x00, 0x00]);
+
+        let mut bits: Vec<bool> = Vec::new();
+        let mut push = |value: u32, len: u32| bits.extend((0..len).rev().map(|i| value >> i & 1 == 1));
+        let width = resolution.x as usize;
+
+        for (i, &sample) in samples.iter().enumerate() {
+            let prediction = match (i % width, i / width) {
+                (0, 0) => 1 << (precision - 1),
+                (0, _) => samples[i - width] as i32,
+                _ => samples[i - 1] as i32,
+            };
+
+            let difference = sample as i32 - prediction;
+            let category = 32 - difference.unsigned_abs().leading_zeros();
+            let extra = if difference < 0 { difference - 1 } else { difference };
+
+            push(category, 4);
+            push(extra as u32 & ((1 << category) - 1), category);
+        }
+
+        bits.resize(bits.len().next_multiple_of(8), true);
+
+        for byte in bits.chunks(8).map(|bits| bits.iter().fold(0u8, |byte, &bit| byte << 1 | bit as u8)) {
+            data.push(byte);
+
+            if byte == 0xFF {
+                data.push(0x00);
+            }
+        }
+
+        data.extend([0xFF, 0xD9]);
+        data
+    }
+
+    #[test]
+    fn scales_samples_by_their_precision() {
+        let resolution = UVec2::new(4, 2);
+
+        for (precision, samples) in [(12, [0, 4095, 2048, 1000, 3000, 17, 4000, 123]), (6, [0, 63, 32, 10, 50, 1, 40, 7])] {
+            let data = lossless_jpeg(resolution, precision, &samples);
+            let (image, _) = Image::<1, f32, Luma<f32>>::read_from(data.as_slice()).unwrap();
+
+            assert_eq!(image.resolution(), resolution);
+
+            for (pixel, sample) in image.iter_pixels().zip(samples) {
+                let expected = sample as f32 / ((1 << precision) - 1) as f32;
+                assert!((pixel.v - expected).abs() < 1e-6, "{precision} bits: {} != {expected}", pixel.v);
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_8_bit_samples() {
+        let pixels = (0..64u8).map(|v| Rgb { r: v * 4, g: v * 4, b: v * 4 }).collect();
+        let image = Image::<3, u8, Rgb<u8>>::new(UVec2::new(8, 8), pixels);
+
+        let mut data = Vec::new();
+        image.write_to(&mut data, ImageFormat::Jpeg, &WriteOptions { quality: 100, ..WriteOptions::default() }).unwrap();
+
+        let (read, _) = Image::<3, u8, Rgb<u8>>::read_from(data.as_slice()).unwrap();
+
+        for (a, b) in read.iter_pixels().zip(image.iter_pixels()) {
+            assert!(a.r.abs_diff(b.r) <= 2, "{} != {}", a.r, b.r);
+        }
+    }
+
+    #[test]
+    fn text_round_trips_through_comments() {
+        let image = Image::<1, u8, Luma<u8>>::new(UVec2::new(4, 4), vec![Luma { v: 128 }; 16]);
+
+        let mut options = WriteOptions::default();
+        options.metadata.text = BTreeMap::from([
+            (String::from("nprs:graph"), String::from("blur = Blur { sigma: 2.0 }\nblur")),
+            (String::from("nprs:args"), String::new()),
+        ]);
+
+        let mut data = Vec::new();
+        image.write_to(&mut data, ImageFormat::Jpeg, &options).unwrap();
+
+        let (_, metadata) = Image::<1, u8, Luma<u8>>::read_from(data.as_slice()).unwrap();
+
+        assert_eq!(metadata.text, options.metadata.text);
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+
+/// Information stored alongside the pixels of an image file.
+///
+/// Returned by [`Image::read_with_metadata`](super::Image::read_with_metadata) and written through
+/// [`WriteOptions::metadata`](super::WriteOptions::metadata). Formats that can't store a given
+/// field ignore it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImageMetadata {
+    /// The exponent the samples were encoded with, such that `sample = linear ^ gamma`
+    /// (the PNG `gAMA` chunk). sRGB data is roughly `1.0 / 2.2`.
+    pub gamma: Option<f32>,
+    /// Marks the samples as sRGB encoded, with the given rendering intent (the PNG `sRGB` chunk).
+    pub srgb: Option<RenderingIntent>,
+    /// An embedded ICC profile (the PNG `iCCP` chunk).
+    pub icc_profile: Option<Vec<u8>>,
+    /// Free-form text entries, keyed by name (PNG text chunks, JPEG comments and
+    /// OpenEXR string attributes).
+    pub text: BTreeMap<String, String>,
+}
+
+impl ImageMetadata {
+    /// Metadata marking the samples as linear light.
+    pub const LINEAR: ImageMetadata = ImageMetadata {
+        gamma: Some(1.0),
+        srgb: None,
+        icc_profile: None,
+        text: BTreeMap::new(),
+    };
+}
+
+/// The rendering intent of sRGB encoded data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderingIntent {
+    Perceptual,
+    RelativeColorimetric,
+    Saturation,
+    AbsoluteColorimetric,
+}
+
+impl From<png::SrgbRenderingIntent> for RenderingIntent {
+    fn from(value: png::SrgbRenderingIntent) -> Self {
+        match value {
+            png::SrgbRenderingIntent::Perceptual => RenderingIntent::Perceptual,
+            png::SrgbRenderingIntent::RelativeColorimetric => RenderingIntent::RelativeColorimetric,
+            png::SrgbRenderingIntent::Saturation => RenderingIntent::Saturation,
+            png::SrgbRenderingIntent::AbsoluteColorimetric => RenderingIntent::AbsoluteColorimetric,
+        }
+    }
+}
+
+impl From<RenderingIntent> for png::SrgbRenderingIntent {
+    fn from(value: RenderingIntent) -> Self {
+        match value {
+            RenderingIntent::Perceptual => png::SrgbRenderingIntent::Perceptual,
+            RenderingIntent::RelativeColorimetric => png::SrgbRenderingIntent::RelativeColorimetric,
+            RenderingIntent::Saturation => png::SrgbRenderingIntent::Saturation,
+            RenderingIntent::AbsoluteColorimetric => png::SrgbRenderingIntent::AbsoluteColorimetric,
+        }
+    }
+}
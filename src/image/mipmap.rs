@@ -0,0 +1,38 @@
+use glam::UVec2;
+
+use super::{format::PixelFormat, pixel::Pixel, sampler::WrapMode2D, weighted_sum, Image};
+
+impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Image<CHANNELS, F, P> {
+    /// The mip chain of this image, where each level is half the size of the one before it, down
+    /// to a single pixel. The image itself is level zero and isn't included.
+    ///
+    /// The chain is built the first time it is needed, and rebuilt after the image is modified.
+    pub fn mip_levels(&self) -> &[Image<CHANNELS, F, P>] {
+        self.mips.get_or_init(|| {
+            let mut levels: Vec<Image<CHANNELS, F, P>> = Vec::new();
+
+            loop {
+                let level = levels.last().unwrap_or(self);
+
+                if level.resolution.cmple(UVec2::ONE).all() {
+                    break levels;
+                }
+
+                let next = level.downsample();
+                levels.push(next);
+            }
+        })
+    }
+
+    /// Halves the resolution of this image, averaging each 2x2 block of pixels.
+    fn downsample(&self) -> Image<CHANNELS, F, P> {
+        let resolution = (self.resolution / 2).max(UVec2::ONE);
+
+        Image::new_fill(resolution, P::BLACK).map_with_positions(|_, pos| {
+            let taps = [UVec2::ZERO, UVec2::X, UVec2::Y, UVec2::ONE]
+                .map(|offset| (self.load_wrapped((pos * 2 + offset).as_ivec2(), WrapMode2D::CLAMP), 0.25));
+
+            weighted_sum(taps)
+        })
+    }
+}
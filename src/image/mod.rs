@@ -1,10 +1,15 @@
-use std::{fs::File, path::Path};
+use std::{fs::File, io::{BufWriter, Cursor, Read, Write}, path::Path, sync::OnceLock};
 
+use animation::Animation;
+use color_management::ColorEncoding;
 use format::PixelFormat;
+use metadata::ImageMetadata;
+use netpbm::NetpbmKind;
 use glam::{IVec2, UVec2, Vec2};
 use pixel::{luma::Luma, luma_alpha::LumaAlpha, rgb::Rgb, rgba::Rgba, Pixel};
 use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator};
 use thiserror::Error;
+use y4m::{Y4mHeader, Y4mReader, Y4mWriter};
 use sampler::{Filter, Sampler, WrapMode2D};
 
 use crate::parser::{interpreter::ParsedValue, FromParsedValue, ParseValueError};
@@ -12,6 +17,24 @@ use crate::parser::{interpreter::ParsedValue, FromParsedValue, ParseValueError};
 pub mod pixel;
 pub mod format;
 pub mod sampler;
+pub mod metadata;
+pub mod color_management;
+pub mod animation;
+pub mod y4m;
+pub mod resize;
+pub mod pyramid;
+pub mod integral;
+pub mod histogram;
+pub mod distance;
+pub mod compare;
+pub mod buffer;
+mod png;
+mod jpeg;
+mod openexr;
+mod netpbm;
+mod gif;
+mod convolution;
+mod mipmap;
 
 #[derive(Clone)]
 pub struct Image<const CHANNELS: usize, F, P>
@@ -21,6 +44,9 @@ where
 {
     pixels: Vec<P>,
     resolution: UVec2,
+    /// The mip chain below this image, built on first use and dropped whenever the image is
+    /// modified.
+    mips: OnceLock<Vec<Image<CHANNELS, F, P>>>,
 }
 
 impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Image<CHANNELS, F, P> {
@@ -28,6 +54,7 @@ impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Imag
         Self {
             pixels,
             resolution,
+            mips: OnceLock::new(),
         }
     }
 
@@ -35,6 +62,7 @@ impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Imag
         Self {
             pixels: vec![pixel; (resolution.x * resolution.y) as usize],
             resolution,
+            mips: OnceLock::new(),
         }
     }
 
@@ -47,6 +75,22 @@ impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Imag
     }
 
     pub fn sample_absolute(&self, pos: Vec2, sampler: Sampler) -> P {
+        self.sample_absolute_with_footprint(pos, 1.0, sampler)
+    }
+
+    /// Sample the image at `uv`, where the sample covers `footprint` pixels of this image.
+    ///
+    /// See [`Self::sample_absolute_with_footprint`].
+    pub fn sample_with_footprint(&self, uv: Vec2, footprint: f32, sampler: Sampler) -> P {
+        self.sample_absolute_with_footprint(uv * self.resolution.as_vec2(), footprint, sampler)
+    }
+
+    /// Sample the image at `pos`, in pixels, where the sample covers `footprint` pixels of this
+    /// image.
+    ///
+    /// [`Filter::Trilinear`] uses the footprint to choose between mip levels, the other filters
+    /// ignore it.
+    pub fn sample_absolute_with_footprint(&self, pos: Vec2, footprint: f32, sampler: Sampler) -> P {
         match sampler.filter {
             Filter::NearestNeighbor => {
                 let p = pos.round().as_ivec2();
@@ -67,6 +111,42 @@ impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Imag
                     + v01 * F::from_scaled_float((1.0 - d.x) * d.y)
                     + v11 * F::from_scaled_float(d.x * d.y)
             },
+            Filter::Bicubic => {
+                let p = pos - 0.5;
+                let pi = p.floor().as_ivec2();
+                let d = p - p.floor();
+
+                let (wx, wy) = (catmull_rom(d.x), catmull_rom(d.y));
+
+                // The weights can be negative, so the sum is taken in floats.
+                let taps = (0..16).map(|i| {
+                    let offset = IVec2::new(i % 4 - 1, i / 4 - 1);
+                    let v = self.load_wrapped(pi + offset, sampler.wrap_mode);
+                    (v, wx[(i % 4) as usize] * wy[(i / 4) as usize])
+                });
+
+                weighted_sum(taps)
+            },
+            Filter::Trilinear => {
+                let levels = self.mip_levels();
+                let lod = footprint.max(1.0).log2().min(levels.len() as f32);
+                let level = lod.floor() as usize;
+                let t = lod - lod.floor();
+
+                let linear = Sampler { filter: Filter::Linear, ..sampler };
+
+                let sample_level = |level: usize| {
+                    let image = if level == 0 { self } else { &levels[level - 1] };
+                    let scale = image.resolution.as_vec2() / self.resolution.as_vec2();
+                    image.sample_absolute(pos * scale, linear)
+                };
+
+                if t == 0.0 {
+                    sample_level(level)
+                } else {
+                    weighted_sum([(sample_level(level), 1.0 - t), (sample_level(level + 1), t)])
+                }
+            },
         }
     }
 
@@ -74,7 +154,8 @@ impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Imag
         if let Some(p) = wrap_mode.remap(p, self.resolution.as_ivec2()) {
             self.pixels[(p.y * self.resolution.x + p.x) as usize]
         } else {
-            P::BLACK
+            let fill = wrap_mode.fill(p, self.resolution.as_ivec2());
+            P::from_pixel(Rgba::from_channels(fill.channels().map(F::from_scaled_float)))
         }
     }
 
@@ -89,11 +170,13 @@ impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Imag
     }
 
     pub fn get_mut(&mut self, p: UVec2) -> &mut P {
+        self.mips.take();
         assert!(p.x < self.resolution.x && p.y < self.resolution.y);
         &mut self.pixels[(p.y * self.resolution.x + p.x) as usize]
     }
 
     pub fn get_mut_wrapped(&mut self, p: IVec2, wrap_mode: WrapMode2D) -> Option<&mut P> {
+        self.mips.take();
         if let Some(p) = wrap_mode.remap(p, self.resolution.as_ivec2()) {
             Some(&mut self.pixels[(p.y * self.resolution.x + p.x) as usize])
         } else {
@@ -102,11 +185,13 @@ impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Imag
     }
 
     pub fn store(&mut self, p: UVec2, c: P) {
+        self.mips.take();
         assert!(p.x < self.resolution.x && p.y < self.resolution.y);
         self.pixels[(p.y * self.resolution.x + p.x) as usize] = c;
     }
 
     pub fn store_wrapped(&mut self, p: IVec2, c: P) {
+        self.mips.take();
         if p.x < self.resolution.x as i32 && p.y < self.resolution.y as i32 && p.x >= 0 && p.y >= 0 {
             self.pixels[(p.y as u32 * self.resolution.x + p.x as u32) as usize] = c;
         }
@@ -144,6 +229,8 @@ impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Imag
     where
         Convert: Fn(&mut P) + Send + Sync
     {
+        self.mips.take();
+
         self.pixels.par_iter_mut().for_each(f);
     }
 
@@ -152,6 +239,8 @@ impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Imag
     where
         Convert: Fn(&mut P, UVec2) + Sync
     {
+        self.mips.take();
+
         self.pixels
             .par_iter_mut()
             .enumerate()
@@ -164,6 +253,8 @@ impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Imag
     where
         Convert: Fn(&mut P, Vec2) + Sync
     {
+        self.mips.take();
+
         self.pixels
             .par_iter_mut()
             .enumerate()
@@ -222,141 +313,176 @@ impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Imag
     }
 
     pub fn read<S: AsRef<Path>>(path: S) -> Result<Image<CHANNELS, F, P>, ImageError> {
-        let path = path.as_ref();
+        Self::read_with_metadata(path).map(|(image, _)| image)
+    }
 
-        match path.extension() {
-            Some(ext) => if ext.eq("png") {
-                Self::read_png(path)
-            } else {
-                Err(ImageError::InvalidExtension(ext.to_str().unwrap().to_string()))
+    /// Read an image from `path`, along with the [`ImageMetadata`] stored in the file.
+    ///
+    /// The format is detected from the contents of the file rather than its extension.
+    pub fn read_with_metadata<S: AsRef<Path>>(path: S) -> Result<(Image<CHANNELS, F, P>, ImageMetadata), ImageError> {
+        Self::read_from(File::open(path)?)
+    }
+
+    /// Read an image from `reader`, along with its [`ImageMetadata`], detecting the format from
+    /// the magic bytes at the start of the stream.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<(Image<CHANNELS, F, P>, ImageMetadata), ImageError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        match ImageFormat::detect(&data) {
+            Some(ImageFormat::Png) => Self::read_png(data.as_slice()),
+            Some(ImageFormat::Jpeg) => Self::read_jpeg(&data),
+            Some(ImageFormat::Exr) => Self::read_exr(Cursor::new(data)),
+            Some(ImageFormat::Gif) => {
+                let frame = Animation::read_gif(&data)?.frames.into_iter().next().ok_or(ImageError::NoFrames)?;
+                Ok((frame.image, ImageMetadata::default()))
+            },
+            // PFM always stores linear light.
+            Some(ImageFormat::Pfm) => Ok((Self::read_netpbm(&data)?, ImageMetadata::LINEAR)),
+            Some(ImageFormat::Pgm | ImageFormat::Ppm | ImageFormat::Pam) => Ok((Self::read_netpbm(&data)?, ImageMetadata::default())),
+            Some(ImageFormat::Y4m) => {
+                let image = Y4mReader::new(data.as_slice())?.read_frame()?.ok_or(ImageError::NoFrames)?;
+                Ok((image, ImageMetadata::default()))
             },
-            None => Err(ImageError::NoExtension(path.to_str().unwrap().to_string()))
+            None => Err(ImageError::UnknownFormat),
         }
     }
 
-    fn read_png(path: &Path) -> Result<Image<CHANNELS, F, P>, ImageError> {
-        let mut decoder = png::Decoder::new(File::open(path)?);
-
-        if decoder.read_header_info().ok().map(|h| h.color_type) == Some(png::ColorType::Indexed) {
-            decoder.set_transformations(png::Transformations::EXPAND);
-        } else {
-            decoder.set_transformations(png::Transformations::IDENTITY);
-        }
+    pub fn write<S: AsRef<Path>>(&self, path: S) -> Result<(), ImageError> {
+        self.write_with_options(path, &WriteOptions::default())
+    }
 
-        let mut reader = decoder.read_info()?;
+    /// Write this image to `path`, with format-specific settings taken from `options`.
+    ///
+    /// The format is chosen from the extension of `path`.
+    pub fn write_with_options<S: AsRef<Path>>(&self, path: S, options: &WriteOptions) -> Result<(), ImageError> {
+        let path = path.as_ref();
+        let format = ImageFormat::from_path(path)?;
 
-        let mut im_data = vec![0; reader.output_buffer_size()];
-        let info = reader.next_frame(&mut im_data)?;
+        self.write_to(BufWriter::new(File::create(path)?), format, options)
+    }
 
+    /// Write this image to `writer` as `format`, with format-specific settings taken from
+    /// `options`.
+    pub fn write_to<W: Write>(&self, mut writer: W, format: ImageFormat, options: &WriteOptions) -> Result<(), ImageError> {
+        match format {
+            ImageFormat::Png => self.write_png(&mut writer, &options.metadata)?,
+            ImageFormat::Jpeg => self.write_jpeg(&mut writer, options)?,
+            ImageFormat::Exr => {
+                // OpenEXR seeks back to write its offset tables, which streams like stdout can't do.
+                let mut buffer = Cursor::new(Vec::new());
+                self.write_exr(&mut buffer, &options.metadata)?;
+                writer.write_all(buffer.get_ref())?;
+            },
+            ImageFormat::Gif => Animation::from(self.clone()).write_gif(&mut writer)?,
+            ImageFormat::Pgm => self.write_netpbm(&mut writer, NetpbmKind::Pgm)?,
+            ImageFormat::Ppm => self.write_netpbm(&mut writer, NetpbmKind::Ppm)?,
+            ImageFormat::Pam => self.write_netpbm(&mut writer, NetpbmKind::Pam)?,
+            ImageFormat::Pfm => self.write_netpbm(&mut writer, NetpbmKind::Pfm)?,
+            ImageFormat::Y4m => Y4mWriter::new(&mut writer, Y4mHeader::new(self.resolution))?.write_frame(self)?,
+        }
 
-        let chunk_size = match info.bit_depth {
-            png::BitDepth::Eight => 1,
-            png::BitDepth::Sixteen => 2,
-            _ => todo!(),
-        };
+        Ok(writer.flush()?)
+    }
+}
 
-        let formatted_im_data: Vec<F> = im_data.chunks_exact(chunk_size).map(|bytes| F::from_bytes(bytes)).collect();
+/// A file format that an [`Image`] can be read from and written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Exr,
+    Gif,
+    Pgm,
+    Ppm,
+    Pam,
+    Pfm,
+    Y4m,
+}
 
-        let pixels = match info.color_type {
-            png::ColorType::Grayscale => {
-                formatted_im_data
-                    .into_iter()
-                    .map(|channels| P::from_pixel(Luma::<F>::from_channels([channels])))
-                    .collect()
-            },
-            png::ColorType::GrayscaleAlpha => {
-                formatted_im_data
-                    .chunks_exact(2)
-                    .flat_map(<[F; 2]>::try_from)
-                    .map(|channels| P::from_pixel(LumaAlpha::<F>::from_channels(channels)))
-                    .collect()
-            },
-            png::ColorType::Rgb => {
-                formatted_im_data
-                    .chunks_exact(3)
-                    .flat_map(<[F; 3]>::try_from)
-                    .map(|channels| P::from_pixel(Rgb::<F>::from_channels(channels)))
-                    .collect()
-            },
-            png::ColorType::Rgba => {
-                formatted_im_data
-                    .chunks_exact(4)
-                    .flat_map(<[F; 4]>::try_from)
-                    .map(|channels| P::from_pixel(Rgba::<F>::from_channels(channels)))
-                    .collect()
-            },
-            png::ColorType::Indexed => unreachable!(),
-        };
-        
-        Ok(Self::new(
-            UVec2::new(info.width, info.height),
-            pixels,
-        ))
+impl ImageFormat {
+    /// The format with the given file extension, ignoring case.
+    pub fn from_extension(extension: &str) -> Option<ImageFormat> {
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(ImageFormat::Png),
+            "jpg" | "jpeg" => Some(ImageFormat::Jpeg),
+            "exr" => Some(ImageFormat::Exr),
+            "gif" => Some(ImageFormat::Gif),
+            "pgm" => Some(ImageFormat::Pgm),
+            "ppm" => Some(ImageFormat::Ppm),
+            "pam" => Some(ImageFormat::Pam),
+            "pfm" => Some(ImageFormat::Pfm),
+            "y4m" => Some(ImageFormat::Y4m),
+            _ => None,
+        }
     }
 
-    pub fn write<S: AsRef<Path>>(&self, path: S) -> Result<(), ImageError> {
+    /// The format given by the extension of `path`.
+    pub fn from_path<S: AsRef<Path>>(path: S) -> Result<ImageFormat, ImageError> {
         let path = path.as_ref();
 
         match path.extension() {
-            Some(ext) => if ext.eq("png") {
-                self.write_png(path)
-            } else {
-                Err(ImageError::InvalidExtension(ext.to_str().unwrap().to_string()))
-            },
-            None => Err(ImageError::NoExtension(path.to_str().unwrap().to_string()))
+            Some(ext) => ext.to_str()
+                .and_then(ImageFormat::from_extension)
+                .ok_or(ImageError::InvalidExtension(ext.to_string_lossy().to_string())),
+            None => Err(ImageError::NoExtension(path.to_string_lossy().to_string())),
         }
     }
 
-    fn write_png(&self, path: &Path) -> Result<(), ImageError> {
-        let file = File::create(path)?;
-        let buf_writer = &mut std::io::BufWriter::new(file);
-        let mut encoder = png::Encoder::new(buf_writer, self.resolution.x, self.resolution.y);
+    /// Whether the format can store more than one frame.
+    pub fn supports_animation(self) -> bool {
+        matches!(self, ImageFormat::Png | ImageFormat::Gif | ImageFormat::Y4m)
+    }
 
-        match CHANNELS {
-            1 => encoder.set_color(png::ColorType::Grayscale),
-            2 => encoder.set_color(png::ColorType::GrayscaleAlpha),
-            3 => encoder.set_color(png::ColorType::Rgb),
-            4 => encoder.set_color(png::ColorType::Rgba),
-            _ => return Err(ImageError::BadChannelCount(CHANNELS, String::from("png")))
-        }
+    /// Whether the format stores the text entries of [`ImageMetadata`].
+    pub fn stores_text(self) -> bool {
+        matches!(self, ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Exr)
+    }
 
-        match F::bytes() {
-            1 => encoder.set_depth(png::BitDepth::Eight),
-            2 => encoder.set_depth(png::BitDepth::Sixteen),
-            _ => return Err(ImageError::BadBitDepth(F::bytes(), String::from("png"))),
+    /// Detects the format of an image file from its first few bytes.
+    pub fn detect(data: &[u8]) -> Option<ImageFormat> {
+        match data {
+            [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', ..] => Some(ImageFormat::Png),
+            [0xff, 0xd8, 0xff, ..] => Some(ImageFormat::Jpeg),
+            [0x76, 0x2f, 0x31, 0x01, ..] => Some(ImageFormat::Exr),
+            [b'G', b'I', b'F', b'8', ..] => Some(ImageFormat::Gif),
+            _ if data.starts_with(y4m::MAGIC) => Some(ImageFormat::Y4m),
+            [b'P', b'5', ..] => Some(ImageFormat::Pgm),
+            [b'P', b'6', ..] => Some(ImageFormat::Ppm),
+            [b'P', b'7', ..] => Some(ImageFormat::Pam),
+            [b'P', b'f' | b'F', ..] => Some(ImageFormat::Pfm),
+            _ => None,
         }
-
-        let data: Vec<_> = self.pixels.iter()
-            .flat_map(|p| p.channels())
-            .flat_map(|v| v.to_bytes())
-            .collect();
-
-        let mut writer = encoder.write_header()?;
-        Ok(writer.write_image_data(&data)?)
     }
 }
 
-impl<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>> Image<CHANNELS, f32, P> {
-    // TODO: FFT-based convolution algorithm
-    pub fn convolve(&self, kernel: &[f32], kernel_size: UVec2) -> Image<CHANNELS, f32, P> {
-        assert!(kernel.len() as u32 == kernel_size.x * kernel_size.y);
-
-        let kernel_size = kernel_size.as_ivec2();
+impl std::str::FromStr for ImageFormat {
+    type Err = ImageError;
 
-        self.map_with_positions(|_pixel, pos| {
-            let mut c = P::BLACK;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ImageFormat::from_extension(s).ok_or(ImageError::InvalidExtension(s.to_string()))
+    }
+}
 
-            for i in -(kernel_size.x / 2)..=(kernel_size.x / 2) {
-                for j in -(kernel_size.y / 2)..=(kernel_size.y / 2) {
-                    let p = IVec2::new(pos.x as i32 + i, pos.y as i32 + j);
-                    let v = self.load_wrapped(p, WrapMode2D::CLAMP);
-                    let w = kernel[((j + kernel_size.y / 2) * kernel_size.x + (i + kernel_size.x / 2)) as usize];
-                    c = c + (v * w);
-                }
-            }
+/// Settings used when encoding an image with [`Image::write_with_options`].
+///
+/// Settings that don't apply to the chosen format are ignored.
+#[derive(Clone, Debug)]
+pub struct WriteOptions {
+    /// The JPEG quality, from `1` (smallest file) to `100` (best quality).
+    ///
+    /// Defaults to `90`
+    pub quality: u8,
+    /// The metadata to store alongside the pixels.
+    pub metadata: ImageMetadata,
+}
 
-            c
-        })
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            quality: 90,
+            metadata: ImageMetadata::default(),
+        }
     }
 }
 
@@ -368,29 +494,107 @@ pub enum ImageError {
     /// No extension for image file.
     #[error("expected some extension for image file `{0}`.")]
     NoExtension(String),
+    /// Image data in an unrecognized format.
+    #[error("unrecognized image format.")]
+    UnknownFormat,
+    /// An animation without any frames.
+    #[error("image has no frames.")]
+    NoFrames,
+    /// Several frames written to a format that only stores one.
+    #[error("{0:?} images can't store more than one frame.")]
+    NotAnimated(ImageFormat),
     /// Unsupported channel count for a given extension.
     #[error("{0} color channels not supported by {1}.")]
     BadChannelCount(usize, String),
     /// Unsupported 
     #[error("bit depth {0} not supported by {1}.")]
     BadBitDepth(u8, String),
+    /// Unsupported resolution for a given extension.
+    #[error("resolution {0} not supported by {1}.")]
+    BadResolution(UVec2, String),
+    /// A text metadata entry too long for a given format.
+    #[error("text entry `{0}` is too long for {1}.")]
+    TextTooLong(String, String),
+    /// A malformed netpbm file.
+    #[error("malformed netpbm file: {0}.")]
+    MalformedNetpbm(String),
+    /// A malformed YUV4MPEG2 stream.
+    #[error("malformed y4m stream: {0}.")]
+    MalformedY4m(String),
     /// An IO Error.
     #[error(transparent)]
     Io(#[from] std::io::Error),
     /// A PNG Decoding Error.
     #[error(transparent)]
-    PngDecoding(#[from] png::DecodingError),
+    PngDecoding(#[from] ::png::DecodingError),
     /// A PNG Encoding Error.
     #[error(transparent)]
-    PngEncoding(#[from] png::EncodingError)
+    PngEncoding(#[from] ::png::EncodingError),
+    /// A JPEG Decoding Error.
+    #[error(transparent)]
+    JpegDecoding(#[from] jpeg_decoder::Error),
+    /// A JPEG Encoding Error.
+    #[error(transparent)]
+    JpegEncoding(#[from] jpeg_encoder::EncodingError),
+    /// A GIF Decoding Error.
+    #[error(transparent)]
+    GifDecoding(#[from] ::gif::DecodingError),
+    /// A GIF Encoding Error.
+    #[error(transparent)]
+    GifEncoding(#[from] ::gif::EncodingError),
+    /// An OpenEXR Error.
+    #[error(transparent)]
+    Exr(#[from] exr::error::Error),
 }
 
-impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> FromParsedValue for Image<CHANNELS, F, P> {
+/// Textures are converted from the transfer function of their file to
+/// [`ColorEncoding::Display`], the encoding passes work in unless they declare another.
+impl<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>> FromParsedValue for Image<CHANNELS, f32, P> {
     fn from_parsed_value(value: ParsedValue) -> Result<Self, ParseValueError> {
         if let ParsedValue::Path(path) = value {
-            Ok(Self::read(path)?)
+            let (mut image, metadata) = Self::read_with_metadata(path)?;
+            let (from, to) = (metadata.transfer_function(), ColorEncoding::Display.transfer_function());
+
+            if from != to {
+                image.decode_transfer(from);
+                image.encode_transfer(to);
+            }
+
+            Ok(image)
         } else {
             Err(ParseValueError::WrongType(String::from("path"), value.type_name()))
         }
     }
 }
+
+/// Sums the weighted pixels of `taps` in floats, so that formats without negative values can
+/// still take negative weights.
+fn weighted_sum<const CHANNELS: usize, F, P, I>(taps: I) -> P
+where
+    F: PixelFormat,
+    P: Pixel<CHANNELS, Format = F>,
+    I: IntoIterator<Item = (P, f32)>,
+{
+    let mut sum = [0.0; CHANNELS];
+
+    for (pixel, w) in taps {
+        for (c, v) in sum.iter_mut().zip(pixel.channels()) {
+            *c += v.to_scaled_float() * w;
+        }
+    }
+
+    P::from_channels(sum.map(F::from_scaled_float))
+}
+
+/// The Catmull-Rom weights of the four pixels around a sample `t` of the way between the middle
+/// two.
+fn catmull_rom(t: f32) -> [f32; 4] {
+    let (t2, t3) = (t * t, t * t * t);
+
+    [
+        0.5 * (-t3 + 2.0 * t2 - t),
+        0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+        0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+        0.5 * (t3 - t2),
+    ]
+}
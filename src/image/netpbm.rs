@@ -0,0 +1,321 @@
+use std::io::Write;
+
+use glam::UVec2;
+
+use super::{format::PixelFormat, pixel::{luma::Luma, luma_alpha::LumaAlpha, rgb::Rgb, rgba::Rgba, FromPixel, Pixel}, Image, ImageError};
+
+/// The members of the netpbm family that can be written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum NetpbmKind {
+    /// Binary graymap (`P5`).
+    Pgm,
+    /// Binary pixmap (`P6`).
+    Ppm,
+    /// Portable arbitrary map (`P7`), with one to four channels.
+    Pam,
+    /// Portable float map (`Pf` or `PF`).
+    Pfm,
+}
+
+impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Image<CHANNELS, F, P> {
+    /// Reads any binary netpbm file, detecting its kind from the magic number rather than the
+    /// extension.
+    pub(super) fn read_netpbm(data: &[u8]) -> Result<Image<CHANNELS, F, P>, ImageError> {
+        let mut header = HeaderReader { data, pos: 0 };
+
+        let magic = header.token()?;
+
+        match magic {
+            "P5" | "P6" => {
+                let width = header.number()?;
+                let height = header.number()?;
+                let max_value = header.number()?;
+                let depth = if magic == "P5" { 1 } else { 3 };
+                header.single_whitespace()?;
+
+                Self::from_netpbm_samples(&data[header.pos..], UVec2::new(width, height), depth, max_value)
+            },
+            "P7" => {
+                let (mut width, mut height, mut depth, mut max_value) = (None, None, None, None);
+
+                loop {
+                    match header.token()? {
+                        "WIDTH" => width = Some(header.number()?),
+                        "HEIGHT" => height = Some(header.number()?),
+                        "DEPTH" => depth = Some(header.number()?),
+                        "MAXVAL" => max_value = Some(header.number()?),
+                        "TUPLTYPE" => header.skip_line(),
+                        "ENDHDR" => break,
+                        token => return Err(ImageError::MalformedNetpbm(format!("unknown PAM header field `{}`", token))),
+                    }
+                }
+
+                header.single_whitespace()?;
+
+                let (Some(width), Some(height), Some(depth), Some(max_value)) = (width, height, depth, max_value) else {
+                    return Err(ImageError::MalformedNetpbm(String::from("PAM header is missing a required field")));
+                };
+
+                Self::from_netpbm_samples(&data[header.pos..], UVec2::new(width, height), depth, max_value)
+            },
+            "Pf" | "PF" => {
+                let width = header.number()?;
+                let height = header.number()?;
+                let scale: f32 = header.token()?.parse()
+                    .map_err(|_| ImageError::MalformedNetpbm(String::from("invalid PFM scale")))?;
+                header.single_whitespace()?;
+
+                let depth = if magic == "Pf" { 1 } else { 3 };
+                Self::from_pfm_samples(&data[header.pos..], UVec2::new(width, height), depth, scale < 0.0)
+            },
+            _ => Err(ImageError::MalformedNetpbm(format!("unsupported magic number `{}`", magic))),
+        }
+    }
+
+    fn from_netpbm_samples(data: &[u8], resolution: UVec2, depth: u32, max_value: u32) -> Result<Image<CHANNELS, F, P>, ImageError> {
+        if max_value == 0 || max_value > u16::MAX as u32 {
+            return Err(ImageError::MalformedNetpbm(format!("invalid maximum value {}", max_value)));
+        }
+
+        let sample_bytes = if max_value < 256 { 1 } else { 2 };
+        let data_len = Self::data_len(resolution, depth, sample_bytes)?;
+
+        if data.len() < data_len {
+            return Err(ImageError::MalformedNetpbm(String::from("not enough image data")));
+        }
+
+        let samples: Vec<F> = data[..data_len]
+            .chunks_exact(sample_bytes)
+            .map(|bytes| {
+                let v = if sample_bytes == 1 { bytes[0] as u32 } else { u16::from_be_bytes([bytes[0], bytes[1]]) as u32 };
+                F::from_scaled_float(v as f32 / max_value as f32)
+            })
+            .collect();
+
+        Ok(Self::new(resolution, Self::pixels_from_samples(&samples, depth)?))
+    }
+
+    fn from_pfm_samples(data: &[u8], resolution: UVec2, depth: u32, little_endian: bool) -> Result<Image<CHANNELS, F, P>, ImageError> {
+        let data_len = Self::data_len(resolution, depth, 4)?;
+
+        if data.len() < data_len {
+            return Err(ImageError::MalformedNetpbm(String::from("not enough image data")));
+        }
+
+        // PFM scanlines are stored from the bottom of the image to the top.
+        let samples: Vec<F> = data[..data_len]
+            .chunks_exact(data_len / resolution.y as usize)
+            .rev()
+            .flat_map(|row| row.chunks_exact(4))
+            .map(|bytes| {
+                let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+                F::from_scaled_float(if little_endian { f32::from_le_bytes(bytes) } else { f32::from_be_bytes(bytes) })
+            })
+            .collect();
+
+        Ok(Self::new(resolution, Self::pixels_from_samples(&samples, depth)?))
+    }
+
+    /// The number of bytes of image data, or an error if the image is empty or that overflows.
+    fn data_len(resolution: UVec2, depth: u32, sample_bytes: usize) -> Result<usize, ImageError> {
+        if resolution.x == 0 || resolution.y == 0 {
+            return Err(ImageError::MalformedNetpbm(format!("invalid size {}x{}", resolution.x, resolution.y)));
+        }
+
+        (resolution.x as usize)
+            .checked_mul(resolution.y as usize)
+            .and_then(|v| v.checked_mul(depth as usize))
+            .and_then(|v| v.checked_mul(sample_bytes))
+            .ok_or_else(|| ImageError::MalformedNetpbm(format!("size {}x{} is too large", resolution.x, resolution.y)))
+    }
+
+    fn pixels_from_samples(samples: &[F], depth: u32) -> Result<Vec<P>, ImageError> {
+        Ok(match depth {
+            1 => samples.iter().map(|&v| P::from_pixel(Luma::<F>::from_channels([v]))).collect(),
+            2 => samples.chunks_exact(2).map(|c| P::from_pixel(LumaAlpha::<F>::from_channels([c[0], c[1]]))).collect(),
+            3 => samples.chunks_exact(3).map(|c| P::from_pixel(Rgb::<F>::from_channels([c[0], c[1], c[2]]))).collect(),
+            4 => samples.chunks_exact(4).map(|c| P::from_pixel(Rgba::<F>::from_channels([c[0], c[1], c[2], c[3]]))).collect(),
+            _ => return Err(ImageError::MalformedNetpbm(format!("unsupported depth {}", depth))),
+        })
+    }
+
+    /// Writes a netpbm file of the given `kind`.
+    ///
+    /// PGM and PPM images are converted to [`Luma`] and [`Rgb`] respectively, PAM keeps every
+    /// channel, and PFM is written as grayscale for one and two channel images and as RGB
+    /// otherwise. Integer formats use 16-bit samples when `F` is wider than one byte.
+    pub(super) fn write_netpbm<W: Write>(&self, mut writer: W, kind: NetpbmKind) -> Result<(), ImageError> {
+        let (width, height) = (self.resolution.x, self.resolution.y);
+        let max_value = if F::bytes() == 1 { u8::MAX as u32 } else { u16::MAX as u32 };
+
+        let samples: Vec<F> = match kind {
+            NetpbmKind::Pgm => {
+                writeln!(writer, "P5\n{} {}\n{}", width, height, max_value)?;
+                self.pixels.iter().flat_map(|p| p.convert::<Luma<F>>().channels()).collect()
+            },
+            NetpbmKind::Ppm => {
+                writeln!(writer, "P6\n{} {}\n{}", width, height, max_value)?;
+                self.pixels.iter().flat_map(|p| p.convert::<Rgb<F>>().channels()).collect()
+            },
+            NetpbmKind::Pam => {
+                let tuple_type = match CHANNELS {
+                    1 => "GRAYSCALE",
+                    2 => "GRAYSCALE_ALPHA",
+                    3 => "RGB",
+                    4 => "RGB_ALPHA",
+                    _ => return Err(ImageError::BadChannelCount(CHANNELS, String::from("pam"))),
+                };
+
+                writeln!(
+                    writer,
+                    "P7\nWIDTH {}\nHEIGHT {}\nDEPTH {}\nMAXVAL {}\nTUPLTYPE {}\nENDHDR",
+                    width, height, CHANNELS, max_value, tuple_type,
+                )?;
+                self.pixels.iter().flat_map(|p| p.channels()).collect()
+            },
+            NetpbmKind::Pfm => {
+                let (magic, depth) = if CHANNELS <= 2 { ("Pf", 1) } else { ("PF", 3) };
+                writeln!(writer, "{}\n{} {}\n-1.0", magic, width, height)?;
+
+                let row_len = (width * depth) as usize;
+                let samples: Vec<F> = if depth == 1 {
+                    self.pixels.iter().flat_map(|p| p.convert::<Luma<F>>().channels()).collect()
+                } else {
+                    self.pixels.iter().flat_map(|p| p.convert::<Rgb<F>>().channels()).collect()
+                };
+
+                for row in samples.chunks_exact(row_len).rev() {
+                    for v in row {
+                        writer.write_all(&v.to_scaled_float().to_le_bytes())?;
+                    }
+                }
+
+                return Ok(writer.flush()?);
+            },
+        };
+
+        for v in samples {
+            let v = (v.to_scaled_float().clamp(0.0, 1.0) * max_value as f32).round() as u32;
+
+            if max_value > u8::MAX as u32 {
+                writer.write_all(&(v as u16).to_be_bytes())?;
+            } else {
+                writer.write_all(&[v as u8])?;
+            }
+        }
+
+        Ok(writer.flush()?)
+    }
+}
+
+struct HeaderReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> HeaderReader<'a> {
+    /// Reads the next whitespace-delimited token, skipping `#` comments.
+    fn token(&mut self) -> Result<&'a str, ImageError> {
+        loop {
+            match self.data.get(self.pos) {
+                Some(b'#') => self.skip_line(),
+                Some(c) if c.is_ascii_whitespace() => self.pos += 1,
+                Some(_) => break,
+                None => return Err(ImageError::MalformedNetpbm(String::from("unexpected end of header"))),
+            }
+        }
+
+        let start = self.pos;
+
+        while self.data.get(self.pos).is_some_and(|c| !c.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+
+        std::str::from_utf8(&self.data[start..self.pos])
+            .map_err(|_| ImageError::MalformedNetpbm(String::from("header is not valid ASCII")))
+    }
+
+    fn number(&mut self) -> Result<u32, ImageError> {
+        let token = self.token()?;
+        token.parse().map_err(|_| ImageError::MalformedNetpbm(format!("expected a number, found `{}`", token)))
+    }
+
+    fn skip_line(&mut self) {
+        while self.data.get(self.pos).is_some_and(|&c| c != b'\n') {
+            self.pos += 1;
+        }
+    }
+
+    /// Consumes the single whitespace character separating the header from the raster.
+    fn single_whitespace(&mut self) -> Result<(), ImageError> {
+        match self.data.get(self.pos) {
+            Some(c) if c.is_ascii_whitespace() => {
+                self.pos += 1;
+                Ok(())
+            },
+            _ => Err(ImageError::MalformedNetpbm(String::from("expected whitespace after header"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::UVec2;
+
+    use crate::image::{pixel::{luma::Luma, rgb::Rgb, rgba::Rgba, Pixel}, Image, ImageFormat, WriteOptions};
+
+    fn round_trip<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>>(image: &Image<CHANNELS, f32, P>, format: ImageFormat) -> Image<CHANNELS, f32, P> {
+        let mut data = Vec::new();
+        image.write_to(&mut data, format, &WriteOptions::default()).unwrap();
+
+        assert_eq!(ImageFormat::detect(&data), Some(format));
+
+        Image::read_from(data.as_slice()).unwrap().0
+    }
+
+    fn channels<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>>(image: &Image<CHANNELS, f32, P>) -> Vec<[f32; CHANNELS]> {
+        image.iter_pixels().map(|pixel| pixel.channels()).collect()
+    }
+
+    #[test]
+    fn pgm_keeps_16_bit_samples() {
+        let resolution = UVec2::new(7, 5);
+        let image = Image::<1, f32, Luma<f32>>::new(resolution, (0..35).map(|i| Luma { v: (i * 1871) as f32 / 65535.0 }).collect());
+
+        assert_eq!(channels(&round_trip(&image, ImageFormat::Pgm)), channels(&image));
+    }
+
+    #[test]
+    fn pam_keeps_every_channel() {
+        let resolution = UVec2::new(3, 4);
+        let pixels = (0..12).map(|i| Rgba::new(i as f32 / 11.0, 1.0 - i as f32 / 11.0, 0.5, (i % 3) as f32 / 2.0)).collect();
+        let image = Image::<4, f32, Rgba<f32>>::new(resolution, pixels);
+
+        let read = round_trip(&image, ImageFormat::Pam);
+
+        for (read, expected) in channels(&read).iter().zip(channels(&image)) {
+            for (a, b) in read.iter().zip(expected) {
+                assert!((a - b).abs() <= 0.5 / 65535.0, "{read:?} != {expected:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn pfm_keeps_values_outside_the_unit_range() {
+        // Not square, so that flipping the rows or swapping the axes would show.
+        let resolution = UVec2::new(5, 3);
+        let pixels = (0..15).map(|i| Rgb { r: i as f32 * 1.5, g: -(i as f32) / 7.0, b: 1e-3 * i as f32 }).collect();
+        let image = Image::<3, f32, Rgb<f32>>::new(resolution, pixels);
+
+        let read = round_trip(&image, ImageFormat::Pfm);
+
+        assert_eq!(read.resolution(), resolution);
+        assert_eq!(channels(&read), channels(&image));
+    }
+
+    #[test]
+    fn rejects_sizes_that_overflow() {
+        let data = b"P6\n4294967295 4294967295\n255\n";
+        assert!(Image::<3, f32, Rgb<f32>>::read_from(data.as_slice()).is_err());
+    }
+}
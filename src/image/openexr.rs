@@ -0,0 +1,140 @@
+use std::io::{Read, Seek, Write};
+
+use exr::prelude::{AttributeValue, IntoSample, ReadChannels, ReadLayers, SpecificChannels, Text, Vec2, WritableImage};
+use glam::UVec2;
+use half::f16;
+
+use super::{format::PixelFormat, metadata::ImageMetadata, pixel::{rgba::Rgba, FromPixel, Pixel}, Image, ImageError};
+
+impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Image<CHANNELS, F, P> {
+    /// Reads the first RGB or RGBA layer of an OpenEXR file, along with its string attributes.
+    /// Values outside of `[0, 1]` are preserved as long as `F` can represent them. OpenEXR stores
+    /// premultiplied alpha, so colors are divided by alpha wherever it isn't zero.
+    pub(super) fn read_exr<R: Read + Seek + Send>(reader: R) -> Result<(Image<CHANNELS, F, P>, ImageMetadata), ImageError> {
+        let image = exr::prelude::read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .rgba_channels(
+                |resolution, _channels| {
+                    (resolution.width(), vec![Rgba::<F>::BLACK; resolution.area()])
+                },
+                |(width, pixels): &mut (usize, Vec<Rgba<F>>), pos, (r, g, b, a): (f32, f32, f32, f32)| {
+                    let (r, g, b) = if a > 0.0 { (r / a, g / a, b / a) } else { (r, g, b) };
+
+                    pixels[pos.y() * *width + pos.x()] = Rgba::new(
+                        F::from_scaled_float(r),
+                        F::from_scaled_float(g),
+                        F::from_scaled_float(b),
+                        F::from_scaled_float(a),
+                    );
+                },
+            )
+            .first_valid_layer()
+            .all_attributes()
+            .from_buffered(reader)?;
+
+        // OpenEXR always stores linear light.
+        let mut metadata = ImageMetadata::LINEAR;
+
+        for (name, value) in image.attributes.other.iter().chain(image.layer_data.attributes.other.iter()) {
+            if let AttributeValue::Text(text) = value {
+                metadata.text.insert(
+                    String::from_utf8_lossy(name.bytes()).into_owned(),
+                    String::from_utf8_lossy(text.bytes()).into_owned(),
+                );
+            }
+        }
+
+        let size = image.layer_data.size;
+        let (_, pixels) = image.layer_data.channel_data.pixels;
+
+        Ok((
+            Self::new(
+                UVec2::new(size.width() as u32, size.height() as u32),
+                pixels.into_iter().map(P::from_pixel).collect(),
+            ),
+            metadata,
+        ))
+    }
+
+    /// Writes an OpenEXR file with full float samples if `F` is 4 bytes wide, and half float
+    /// samples otherwise. One and three channel images are written as RGB, two and four channel
+    /// images as RGBA. Text entries in `metadata` are stored as string attributes.
+    pub(super) fn write_exr<W: Write + Seek>(&self, writer: W, metadata: &ImageMetadata) -> Result<(), ImageError> {
+        if F::bytes() == 4 {
+            self.write_exr_samples(writer, metadata, |v| v)
+        } else {
+            self.write_exr_samples(writer, metadata, f16::from_f32)
+        }
+    }
+
+    fn write_exr_samples<W, S, ToSample>(&self, writer: W, metadata: &ImageMetadata, to_sample: ToSample) -> Result<(), ImageError>
+    where
+        W: Write + Seek,
+        S: IntoSample + Copy,
+        ToSample: Fn(f32) -> S + Sync,
+    {
+        let width = self.resolution.x as usize;
+        let height = self.resolution.y as usize;
+        let channels = |pos: Vec2<usize>| self.pixels[pos.y() * width + pos.x()].channels().map(|v| to_sample(v.to_scaled_float()));
+
+        // Text is stored as raw bytes, so UTF-8 survives even though OpenEXR only specifies Latin-1.
+        let attributes = || metadata.text.iter()
+            .map(|(name, value)| (Text::from_slice_unchecked(name.as_bytes()), AttributeValue::Text(Text::from_slice_unchecked(value.as_bytes()))));
+
+        match CHANNELS {
+            1 | 3 => {
+                let mut image = exr::prelude::Image::from_channels((width, height), SpecificChannels::rgb(|pos| {
+                    let c = channels(pos);
+                    if CHANNELS == 1 { (c[0], c[0], c[0]) } else { (c[0], c[1], c[2]) }
+                }));
+                image.attributes.other.extend(attributes());
+                image.write().to_buffered(writer)?;
+            },
+            2 | 4 => {
+                let mut image = exr::prelude::Image::from_channels((width, height), SpecificChannels::rgba(|pos| {
+                    let c = channels(pos);
+                    if CHANNELS == 2 { (c[0], c[0], c[0], c[1]) } else { (c[0], c[1], c[2], c[3]) }
+                }));
+                image.attributes.other.extend(attributes());
+                image.write().to_buffered(writer)?;
+            },
+            _ => return Err(ImageError::BadChannelCount(CHANNELS, String::from("exr"))),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::UVec2;
+
+    use crate::image::{pixel::rgba::Rgba, Image, ImageFormat, WriteOptions};
+
+    #[test]
+    fn premultiplied_alpha_is_undone_on_read() {
+        let resolution = UVec2::new(5, 3);
+        let pixels = (0..15u32)
+            .map(|i| Rgba::new(i as f32 / 4.0, 0.5, 1.0 - i as f32 / 15.0, [0.0, 0.25, 0.5, 1.0][i as usize % 4]))
+            .collect();
+        let image = Image::<4, f32, Rgba<f32>>::new(resolution, pixels);
+
+        // The CLI premultiplies OpenEXR output before writing it.
+        let premultiplied = image.map(|p| Rgba::new(p.r * p.a, p.g * p.a, p.b * p.a, p.a));
+
+        let mut data = Vec::new();
+        premultiplied.write_to(&mut data, ImageFormat::Exr, &WriteOptions::default()).unwrap();
+
+        let (read, _) = Image::<4, f32, Rgba<f32>>::read_from(data.as_slice()).unwrap();
+
+        for ((read, original), premultiplied) in read.iter_pixels().zip(image.iter_pixels()).zip(premultiplied.iter_pixels()) {
+            // Fully transparent pixels have lost their color.
+            let expected = if original.a > 0.0 { original } else { premultiplied };
+
+            for (a, b) in [(read.r, expected.r), (read.g, expected.g), (read.b, expected.b), (read.a, expected.a)] {
+                assert!((a - b).abs() < 1e-6, "{read:?} != {expected:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,432 @@
+//! Conversions between color spaces.
+//!
+//! Colors are passed around as [`Vec3`]s holding the three components of their space, in the
+//! order of its name. Hues are fractions of a turn in `[0, 1)`.
+
+use std::f32::consts::TAU;
+
+use glam::{Mat3, Vec3};
+use nprs_derive::FromParsedValue;
+
+use crate::image::color_management::{ColorEncoding, TransferFunction};
+
+use super::{rgb::Rgb, rgba::Rgba};
+
+/// A space the three color channels of a pixel can be expressed in.
+#[derive(FromParsedValue, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// sRGB encoded, display-referred RGB.
+    Srgb,
+    /// Linear light RGB with sRGB primaries.
+    LinearRgb,
+    /// Lightness in `[0, 1]` and two opponent axes, designed so that distances match perceived
+    /// differences.
+    OkLab,
+    /// The cylindrical form of [`ColorSpace::OkLab`]: lightness, chroma and hue.
+    OkLch,
+    /// CIE 1976 L\*a\*b\* relative to the D65 white point, with lightness in `[0, 100]`.
+    Lab,
+    /// The cylindrical form of [`ColorSpace::Lab`]: lightness, chroma and hue.
+    Lch,
+    /// Hue, saturation and value of sRGB encoded colors.
+    Hsv,
+    /// Hue, saturation and lightness of sRGB encoded colors.
+    Hsl,
+    /// BT.601 full range luma and chroma of sRGB encoded colors, with chroma centered on `0.5`.
+    YCbCr,
+}
+
+impl ColorSpace {
+    /// Converts the components `c` of a color in this space to `to`.
+    pub fn convert(self, to: ColorSpace, c: Vec3) -> Vec3 {
+        if self == to {
+            return c;
+        }
+
+        // Spaces built on sRGB encoded values skip the trip through linear light.
+        match (self.space_to_srgb(c), to.is_srgb_based()) {
+            (Some(srgb), true) => to.srgb_to_space(srgb),
+            _ => to.from_linear(self.to_linear(c)),
+        }
+    }
+
+    /// Converts the components `c` of a color in this space to linear RGB.
+    pub fn to_linear(self, c: Vec3) -> Vec3 {
+        match self {
+            ColorSpace::LinearRgb => c,
+            ColorSpace::OkLab => oklab_to_linear(c),
+            ColorSpace::OkLch => oklab_to_linear(lch_to_lab(c)),
+            ColorSpace::Lab => lab_to_linear(c),
+            ColorSpace::Lch => lab_to_linear(lch_to_lab(c)),
+            _ => srgb_to_linear(self.space_to_srgb(c).unwrap()),
+        }
+    }
+
+    /// Converts the linear RGB color `c` to this space.
+    pub fn from_linear(self, c: Vec3) -> Vec3 {
+        match self {
+            ColorSpace::LinearRgb => c,
+            ColorSpace::OkLab => linear_to_oklab(c),
+            ColorSpace::OkLch => lab_to_lch(linear_to_oklab(c)),
+            ColorSpace::Lab => linear_to_lab(c),
+            ColorSpace::Lch => lab_to_lch(linear_to_lab(c)),
+            _ => self.srgb_to_space(linear_to_srgb(c)),
+        }
+    }
+
+    /// The encoding graph images are in when their pixels hold colors of this space, if it is
+    /// one of the RGB spaces.
+    pub fn encoding(self) -> Option<ColorEncoding> {
+        match self {
+            ColorSpace::Srgb => Some(ColorEncoding::Display),
+            ColorSpace::LinearRgb => Some(ColorEncoding::Linear),
+            _ => None,
+        }
+    }
+
+    fn is_srgb_based(self) -> bool {
+        matches!(self, ColorSpace::Srgb | ColorSpace::Hsv | ColorSpace::Hsl | ColorSpace::YCbCr)
+    }
+
+    fn space_to_srgb(self, c: Vec3) -> Option<Vec3> {
+        match self {
+            ColorSpace::Srgb => Some(c),
+            ColorSpace::Hsv => Some(hsv_to_srgb(c)),
+            ColorSpace::Hsl => Some(hsl_to_srgb(c)),
+            ColorSpace::YCbCr => Some(ycbcr_to_srgb(c)),
+            _ => None,
+        }
+    }
+
+    /// Only called on spaces that are [`Self::is_srgb_based`].
+    fn srgb_to_space(self, c: Vec3) -> Vec3 {
+        match self {
+            ColorSpace::Hsv => srgb_to_hsv(c),
+            ColorSpace::Hsl => srgb_to_hsl(c),
+            ColorSpace::YCbCr => srgb_to_ycbcr(c),
+            _ => c,
+        }
+    }
+}
+
+impl Rgb<f32> {
+    /// Converts this color from `from` to `to`, where the channels hold the components of the
+    /// color in order.
+    pub fn convert_space(self, from: ColorSpace, to: ColorSpace) -> Rgb<f32> {
+        let c = from.convert(to, self.into());
+        Rgb::new(c.x, c.y, c.z)
+    }
+}
+
+impl Rgba<f32> {
+    /// Converts the color channels of this pixel from `from` to `to`. Alpha is left untouched.
+    ///
+    /// See [`Rgb::convert_space`].
+    pub fn convert_space(self, from: ColorSpace, to: ColorSpace) -> Rgba<f32> {
+        let c = self.rgb().convert_space(from, to);
+        Rgba::new(c.r, c.g, c.b, self.a)
+    }
+}
+
+pub fn srgb_to_linear(c: Vec3) -> Vec3 {
+    c.to_array().map(|v| TransferFunction::Srgb.decode(v)).into()
+}
+
+pub fn linear_to_srgb(c: Vec3) -> Vec3 {
+    c.to_array().map(|v| TransferFunction::Srgb.encode(v)).into()
+}
+
+const LINEAR_TO_LMS: Mat3 = Mat3::from_cols_array(&[
+    0.412_221_47, 0.211_903_5, 0.088_302_46,
+    0.536_332_55, 0.680_699_5, 0.281_718_85,
+    0.051_445_995, 0.107_396_96, 0.629_978_7,
+]);
+
+const LMS_TO_OKLAB: Mat3 = Mat3::from_cols_array(&[
+    0.210_454_26, 1.977_998_5, 0.025_904_037,
+    0.793_617_8, -2.428_592_2, 0.782_771_77,
+    -0.004_072_047, 0.450_593_7, -0.808_675_77,
+]);
+
+const OKLAB_TO_LMS: Mat3 = Mat3::from_cols_array(&[
+    1.0, 1.0, 1.0,
+    0.396_337_78, -0.105_561_346, -0.089_484_18,
+    0.215_803_76, -0.063_854_17, -1.291_485_5,
+]);
+
+const LMS_TO_LINEAR: Mat3 = Mat3::from_cols_array(&[
+    4.076_741_7, -1.268_438, -0.004_196_086,
+    -3.307_711_6, 2.609_757_4, -0.703_418_6,
+    0.230_969_94, -0.341_319_38, 1.707_614_7,
+]);
+
+pub fn linear_to_oklab(c: Vec3) -> Vec3 {
+    let lms = LINEAR_TO_LMS * c;
+    LMS_TO_OKLAB * Vec3::from_array(lms.to_array().map(f32::cbrt))
+}
+
+pub fn oklab_to_linear(c: Vec3) -> Vec3 {
+    let lms = OKLAB_TO_LMS * c;
+    LMS_TO_LINEAR * (lms * lms * lms)
+}
+
+const LINEAR_TO_XYZ: Mat3 = Mat3::from_cols_array(&[
+    0.412_456_4, 0.212_672_9, 0.019_333_9,
+    0.357_576_1, 0.715_152_2, 0.119_192,
+    0.180_437_5, 0.072_175, 0.950_304_1,
+]);
+
+const XYZ_TO_LINEAR: Mat3 = Mat3::from_cols_array(&[
+    3.240_454_2, -0.969_266, 0.055_643_4,
+    -1.537_138_5, 1.876_010_8, -0.204_025_9,
+    -0.498_531_4, 0.041_556, 1.057_225_2,
+]);
+
+/// The D65 white point, which linear RGB white maps to.
+const D65: Vec3 = Vec3::new(0.950_47, 1.0, 1.088_83);
+
+pub fn linear_to_lab(c: Vec3) -> Vec3 {
+    const DELTA: f32 = 6.0 / 29.0;
+
+    let f = |t: f32| if t > DELTA * DELTA * DELTA {
+        t.cbrt()
+    } else {
+        t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+    };
+
+    let xyz = LINEAR_TO_XYZ * c / D65;
+    let (fx, fy, fz) = (f(xyz.x), f(xyz.y), f(xyz.z));
+
+    Vec3::new(116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+}
+
+pub fn lab_to_linear(c: Vec3) -> Vec3 {
+    const DELTA: f32 = 6.0 / 29.0;
+
+    let f_inv = |t: f32| if t > DELTA {
+        t * t * t
+    } else {
+        3.0 * DELTA * DELTA * (t - 4.0 / 29.0)
+    };
+
+    let fy = (c.x + 16.0) / 116.0;
+    let xyz = Vec3::new(f_inv(fy + c.y / 500.0), f_inv(fy), f_inv(fy - c.z / 200.0));
+
+    XYZ_TO_LINEAR * (xyz * D65)
+}
+
+/// Converts OkLab or CIELAB to its cylindrical form.
+pub fn lab_to_lch(c: Vec3) -> Vec3 {
+    let hue = (c.z.atan2(c.y) / TAU).rem_euclid(1.0);
+    Vec3::new(c.x, c.y.hypot(c.z), hue)
+}
+
+/// Converts OkLCh or CIELCh to its rectangular form.
+pub fn lch_to_lab(c: Vec3) -> Vec3 {
+    let (sin, cos) = (c.z * TAU).sin_cos();
+    Vec3::new(c.x, c.y * cos, c.y * sin)
+}
+
+/// The hue of `c` and the range of its channels.
+fn hue_chroma(c: Vec3) -> (f32, f32, f32) {
+    let (max, min) = (c.max_element(), c.min_element());
+    let chroma = max - min;
+
+    let hue = if chroma == 0.0 {
+        0.0
+    } else if max == c.x {
+        ((c.y - c.z) / chroma).rem_euclid(6.0)
+    } else if max == c.y {
+        (c.z - c.x) / chroma + 2.0
+    } else {
+        (c.x - c.y) / chroma + 4.0
+    };
+
+    (hue / 6.0, max, min)
+}
+
+pub fn srgb_to_hsv(c: Vec3) -> Vec3 {
+    let (hue, max, min) = hue_chroma(c);
+    let saturation = if max == 0.0 { 0.0 } else { (max - min) / max };
+
+    Vec3::new(hue, saturation, max)
+}
+
+pub fn hsv_to_srgb(c: Vec3) -> Vec3 {
+    let (h, s, v) = (c.x, c.y, c.z);
+
+    let f = |n: f32| {
+        let k = (n + h * 6.0).rem_euclid(6.0);
+        v - v * s * k.min(4.0 - k).clamp(0.0, 1.0)
+    };
+
+    Vec3::new(f(5.0), f(3.0), f(1.0))
+}
+
+pub fn srgb_to_hsl(c: Vec3) -> Vec3 {
+    let (hue, max, min) = hue_chroma(c);
+    let lightness = (max + min) / 2.0;
+
+    let saturation = if max == min {
+        0.0
+    } else {
+        (max - min) / (1.0 - (2.0 * lightness - 1.0).abs())
+    };
+
+    Vec3::new(hue, saturation, lightness)
+}
+
+pub fn hsl_to_srgb(c: Vec3) -> Vec3 {
+    let (h, s, l) = (c.x, c.y, c.z);
+    let a = s * l.min(1.0 - l);
+
+    let f = |n: f32| {
+        let k = (n + h * 12.0).rem_euclid(12.0);
+        l - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+
+    Vec3::new(f(0.0), f(8.0), f(4.0))
+}
+
+pub fn srgb_to_ycbcr(c: Vec3) -> Vec3 {
+    let y = 0.299 * c.x + 0.587 * c.y + 0.114 * c.z;
+    Vec3::new(y, (c.z - y) / 1.772 + 0.5, (c.x - y) / 1.402 + 0.5)
+}
+
+pub fn ycbcr_to_srgb(c: Vec3) -> Vec3 {
+    let r = c.x + 1.402 * (c.z - 0.5);
+    let b = c.x + 1.772 * (c.y - 0.5);
+    let g = (c.x - 0.299 * r - 0.114 * b) / 0.587;
+
+    Vec3::new(r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::{hsl_to_srgb, hsv_to_srgb, lch_to_lab, ColorSpace};
+
+    const SPACES: [ColorSpace; 9] = [
+        ColorSpace::Srgb,
+        ColorSpace::LinearRgb,
+        ColorSpace::OkLab,
+        ColorSpace::OkLch,
+        ColorSpace::Lab,
+        ColorSpace::Lch,
+        ColorSpace::Hsv,
+        ColorSpace::Hsl,
+        ColorSpace::YCbCr,
+    ];
+
+    /// Every sRGB color with channels in steps of `0.25`, which includes five greys.
+    fn grid() -> impl Iterator<Item = Vec3> {
+        let steps = [0.0, 0.25, 0.5, 0.75, 1.0];
+        steps.into_iter().flat_map(move |r| steps.into_iter().flat_map(move |g| steps.into_iter().map(move |b| Vec3::new(r, g, b))))
+    }
+
+    fn assert_close(a: Vec3, b: Vec3, tolerance: f32, context: impl std::fmt::Display) {
+        assert!((a - b).abs().max_element() < tolerance, "{context}: {a} != {b}");
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        for space in SPACES {
+            for c in grid() {
+                let converted = ColorSpace::Srgb.convert(space, c);
+                assert!(converted.is_finite(), "{space:?} of {c}: {converted}");
+
+                let back = space.convert(ColorSpace::Srgb, converted);
+                assert_close(back, c, 1e-4, format_args!("{space:?} of {c}"));
+
+                for other in SPACES {
+                    let back = other.convert(space, space.convert(other, converted));
+                    let back = space.convert(ColorSpace::Srgb, back);
+                    assert_close(back, c, 1e-4, format_args!("{space:?} through {other:?} of {c}"));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hues_are_in_range() {
+        for space in [ColorSpace::OkLch, ColorSpace::Lch, ColorSpace::Hsv, ColorSpace::Hsl] {
+            for c in grid() {
+                let converted = ColorSpace::Srgb.convert(space, c);
+                let hue = if matches!(space, ColorSpace::Hsv | ColorSpace::Hsl) { converted.x } else { converted.z };
+
+                assert!((0.0..1.0).contains(&hue), "{space:?} of {c}: {converted}");
+            }
+        }
+    }
+
+    #[test]
+    fn greys_have_no_saturation() {
+        for v in [0.0, 0.25, 0.5, 1.0] {
+            let grey = Vec3::splat(v);
+
+            assert_eq!(ColorSpace::Srgb.convert(ColorSpace::Hsv, grey), Vec3::new(0.0, 0.0, v));
+            assert_eq!(ColorSpace::Srgb.convert(ColorSpace::Hsl, grey), Vec3::new(0.0, 0.0, v));
+            assert!(ColorSpace::Srgb.convert(ColorSpace::OkLch, grey).y < 1e-4);
+            assert!(ColorSpace::Srgb.convert(ColorSpace::Lch, grey).y < 1e-2);
+
+            // Any hue is the same grey.
+            for hue in [0.0, 0.3, 0.9] {
+                assert_close(hsv_to_srgb(Vec3::new(hue, 0.0, v)), grey, 1e-6, hue);
+                assert_close(hsl_to_srgb(Vec3::new(hue, 0.0, v)), grey, 1e-6, hue);
+            }
+        }
+    }
+
+    #[test]
+    fn hues_wrap_around() {
+        for hue in [0.0, 0.1, 0.45, 0.8] {
+            for turns in [-2.0, -1.0, 1.0, 3.0] {
+                let c = Vec3::new(hue, 0.8, 0.6);
+                let wrapped = Vec3::new(hue + turns, 0.8, 0.6);
+
+                assert_close(hsv_to_srgb(wrapped), hsv_to_srgb(c), 1e-5, hue);
+                assert_close(hsl_to_srgb(wrapped), hsl_to_srgb(c), 1e-5, hue);
+
+                let lch = Vec3::new(0.7, 0.1, hue);
+                assert_close(lch_to_lab(Vec3::new(0.7, 0.1, hue + turns)), lch_to_lab(lch), 1e-5, hue);
+            }
+        }
+
+        // Just below a full turn is just below red.
+        let almost_red = ColorSpace::Hsv.convert(ColorSpace::Srgb, Vec3::new(0.999, 1.0, 1.0));
+        assert_close(almost_red, Vec3::new(1.0, 0.0, 0.006), 1e-5, "almost red");
+    }
+
+    #[test]
+    fn oklab_reference_values() {
+        // From the reference implementation of OkLab.
+        let references = [
+            (Vec3::new(1.0, 1.0, 1.0), Vec3::new(1.0, 0.0, 0.0)),
+            (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.627_955, 0.224_863, 0.125_846)),
+            (Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.866_440, -0.233_888, 0.179_498)),
+            (Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.452_014, -0.032_457, -0.311_528)),
+        ];
+
+        for (linear, oklab) in references {
+            assert_close(ColorSpace::LinearRgb.convert(ColorSpace::OkLab, linear), oklab, 1e-3, linear);
+        }
+    }
+
+    #[test]
+    fn lab_reference_values() {
+        // sRGB colors and their CIELAB values relative to D65.
+        let references = [
+            (Vec3::new(1.0, 1.0, 1.0), Vec3::new(100.0, 0.0, 0.0)),
+            (Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0)),
+            (Vec3::new(1.0, 0.0, 0.0), Vec3::new(53.24, 80.09, 67.20)),
+            (Vec3::new(0.0, 1.0, 0.0), Vec3::new(87.73, -86.18, 83.18)),
+            (Vec3::new(0.0, 0.0, 1.0), Vec3::new(32.30, 79.19, -107.86)),
+            (Vec3::splat(0.5), Vec3::new(53.39, 0.0, 0.0)),
+        ];
+
+        for (srgb, lab) in references {
+            assert_close(ColorSpace::Srgb.convert(ColorSpace::Lab, srgb), lab, 0.05, srgb);
+        }
+    }
+}
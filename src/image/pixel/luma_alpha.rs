@@ -59,7 +59,10 @@ impl<F: PixelFormat> FromPixel<Rgb<F>> for LumaAlpha<F> {
 
 impl<F: PixelFormat> FromPixel<Rgba<F>> for LumaAlpha<F> {
     fn from_pixel(pixel: Rgba<F>) -> Self {
-        Self::from_pixel(Luma::<F>::from_pixel(pixel))
+        LumaAlpha {
+            v: Luma::<F>::from_pixel(Rgb { r: pixel.r, g: pixel.g, b: pixel.b }).v,
+            a: pixel.a,
+        }
     }
 }
 
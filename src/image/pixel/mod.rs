@@ -12,6 +12,7 @@ pub mod luma;
 pub mod luma_alpha;
 pub mod rgb;
 pub mod rgba;
+pub mod color;
 mod ops;
 
 pub trait Pixel<const CHANNELS: usize>:
@@ -38,6 +39,26 @@ pub trait Pixel<const CHANNELS: usize>:
     fn channels(&self) -> [Self::Format; CHANNELS];
 
     fn invert(self) -> Self;
+
+    /// Convert this pixel into a pixel of another layout with the same format, through the
+    /// corresponding [`FromPixel`] implementation.
+    fn convert<T>(self) -> T
+    where
+        T: FromPixel<Luma<Self::Format>>
+            + FromPixel<LumaAlpha<Self::Format>>
+            + FromPixel<Rgb<Self::Format>>
+            + FromPixel<Rgba<Self::Format>>,
+    {
+        let c = self.channels();
+
+        match CHANNELS {
+            1 => T::from_pixel(Luma::from_channels([c[0]])),
+            2 => T::from_pixel(LumaAlpha::from_channels([c[0], c[1]])),
+            3 => T::from_pixel(Rgb::from_channels([c[0], c[1], c[2]])),
+            4 => T::from_pixel(Rgba::from_channels([c[0], c[1], c[2], c[3]])),
+            _ => unreachable!(),
+        }
+    }
 }
 
 pub trait FromPixel<T> {
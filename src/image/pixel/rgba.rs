@@ -94,7 +94,12 @@ impl<F: PixelFormat> FromPixel<Luma<F>> for Rgba<F> {
 
 impl<F: PixelFormat> FromPixel<LumaAlpha<F>> for Rgba<F> {
     fn from_pixel(pixel: LumaAlpha<F>) -> Self {
-        Self::from_pixel(Rgb::<F>::from_pixel(pixel))
+        Self {
+            r: pixel.v,
+            g: pixel.v,
+            b: pixel.v,
+            a: pixel.a,
+        }
     }
 }
 
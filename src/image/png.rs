@@ -0,0 +1,266 @@
+use std::{io::{Read, Write}, time::Duration};
+
+use glam::UVec2;
+
+use super::{animation::{Animation, Blend, Compositor, Disposal}, format::PixelFormat, metadata::ImageMetadata, pixel::{luma::Luma, luma_alpha::LumaAlpha, rgb::Rgb, rgba::Rgba, Pixel}, Image, ImageError};
+
+impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Image<CHANNELS, F, P> {
+    /// Reads a PNG of any bit depth and color type, along with its color and text chunks. Only
+    /// the first frame of an APNG is read.
+    ///
+    /// Palette images are expanded to RGB(A), `tRNS` transparency to an alpha channel, and 1, 2
+    /// and 4-bit grayscale to 8 bits. 16-bit samples are read as big-endian integers.
+    pub(super) fn read_png<R: Read>(reader: R) -> Result<(Image<CHANNELS, F, P>, ImageMetadata), ImageError> {
+        let mut decoder = png::Decoder::new(reader);
+        decoder.set_transformations(png::Transformations::EXPAND);
+
+        let mut reader = decoder.read_info()?;
+
+        let mut im_data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut im_data)?;
+        im_data.truncate(info.buffer_size());
+
+        // Text chunks may also follow the image data.
+        reader.finish()?;
+
+        Ok((
+            Self::from_png_data(&im_data, &info),
+            png_metadata(reader.info())?,
+        ))
+    }
+
+    /// Converts a frame decoded with the `EXPAND` transformation into an image.
+    pub(super) fn from_png_data(data: &[u8], info: &png::OutputInfo) -> Image<CHANNELS, F, P> {
+        let chunk_size = match info.bit_depth {
+            png::BitDepth::Eight => 1,
+            png::BitDepth::Sixteen => 2,
+            // `EXPAND` widens every smaller bit depth to 8 bits.
+            _ => unreachable!(),
+        };
+
+        let formatted_im_data: Vec<F> = data[..info.buffer_size()].chunks_exact(chunk_size).map(|bytes| F::from_bytes(bytes)).collect();
+
+        let pixels = match info.color_type {
+            png::ColorType::Grayscale => {
+                formatted_im_data
+                    .into_iter()
+                    .map(|channels| P::from_pixel(Luma::<F>::from_channels([channels])))
+                    .collect()
+            },
+            png::ColorType::GrayscaleAlpha => {
+                formatted_im_data
+                    .chunks_exact(2)
+                    .flat_map(<[F; 2]>::try_from)
+                    .map(|channels| P::from_pixel(LumaAlpha::<F>::from_channels(channels)))
+                    .collect()
+            },
+            png::ColorType::Rgb => {
+                formatted_im_data
+                    .chunks_exact(3)
+                    .flat_map(<[F; 3]>::try_from)
+                    .map(|channels| P::from_pixel(Rgb::<F>::from_channels(channels)))
+                    .collect()
+            },
+            png::ColorType::Rgba => {
+                formatted_im_data
+                    .chunks_exact(4)
+                    .flat_map(<[F; 4]>::try_from)
+                    .map(|channels| P::from_pixel(Rgba::<F>::from_channels(channels)))
+                    .collect()
+            },
+            // `EXPAND` turns palette indices into RGB(A) samples.
+            png::ColorType::Indexed => unreachable!(),
+        };
+
+        Self::new(UVec2::new(info.width, info.height), pixels)
+    }
+
+    /// Writes an 8-bit PNG if `F` is one byte wide and a 16-bit PNG otherwise, along with any
+    /// color chunks in `metadata`. Text entries are written as compressed `iTXt` chunks.
+    pub(super) fn write_png<W: Write>(&self, writer: W, metadata: &ImageMetadata) -> Result<(), ImageError> {
+        let mut writer = self.png_encoder(writer, metadata)?.write_header()?;
+        Ok(writer.write_image_data(&self.png_data())?)
+    }
+
+    /// An encoder for PNGs with the resolution, layout and bit depth of this image.
+    pub(super) fn png_encoder<W: Write>(&self, writer: W, metadata: &ImageMetadata) -> Result<png::Encoder<'static, W>, ImageError> {
+        let mut info = png::Info::with_size(self.resolution.x, self.resolution.y);
+
+        info.color_type = match CHANNELS {
+            1 => png::ColorType::Grayscale,
+            2 => png::ColorType::GrayscaleAlpha,
+            3 => png::ColorType::Rgb,
+            4 => png::ColorType::Rgba,
+            _ => return Err(ImageError::BadChannelCount(CHANNELS, String::from("png")))
+        };
+
+        info.bit_depth = if F::bytes() == 1 {
+            png::BitDepth::Eight
+        } else {
+            png::BitDepth::Sixteen
+        };
+
+        info.icc_profile = metadata.icc_profile.clone().map(Into::into);
+        info.source_gamma = metadata.gamma.map(png::ScaledFloat::new);
+
+        for (keyword, text) in metadata.text.iter() {
+            let mut chunk = png::text_metadata::ITXtChunk::new(keyword, text);
+            chunk.compress_text()?;
+            info.utf8_text.push(chunk);
+        }
+
+        let mut encoder = png::Encoder::with_info(writer, info)?;
+
+        // An sRGB chunk takes precedence over the gamma and ICC profile.
+        if let Some(intent) = metadata.srgb {
+            encoder.set_source_srgb(intent.into());
+        }
+
+        Ok(encoder)
+    }
+
+    /// The samples of this image as PNG image data, in the bit depth chosen by
+    /// [`Self::png_encoder`].
+    pub(super) fn png_data(&self) -> Vec<u8> {
+        if F::bytes() == 1 {
+            self.pixels.iter()
+                .flat_map(|p| p.channels())
+                .flat_map(|v| v.to_bytes())
+                .collect()
+        } else {
+            self.pixels.iter()
+                .flat_map(|p| p.channels())
+                .flat_map(|v| ((v.to_scaled_float().clamp(0.0, 1.0) * u16::MAX as f32).round() as u16).to_be_bytes())
+                .collect()
+        }
+    }
+}
+
+impl<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>> Animation<CHANNELS, F, P> {
+    /// Reads every frame of an APNG, or the only frame of a still PNG.
+    pub(super) fn read_apng(data: &[u8]) -> Result<(Animation<CHANNELS, F, P>, ImageMetadata), ImageError> {
+        let mut decoder = png::Decoder::new(data);
+        decoder.set_transformations(png::Transformations::EXPAND);
+
+        let mut reader = decoder.read_info()?;
+
+        let Some(control) = reader.info().animation_control else {
+            return Image::read_png(data).map(|(image, metadata)| (Animation::from(image), metadata));
+        };
+
+        let mut im_data = vec![0; reader.output_buffer_size()];
+
+        // Without a frame control chunk ahead of it, the default image isn't part of the animation.
+        if reader.info().frame_control.is_none() {
+            reader.next_frame(&mut im_data)?;
+        }
+
+        let (width, height) = reader.info().size();
+        let mut compositor = Compositor::new(UVec2::new(width, height));
+
+        for _ in 0..control.num_frames {
+            let info = reader.next_frame(&mut im_data)?;
+            let frame = reader.info().frame_control.unwrap_or_default();
+
+            // A zero denominator means hundredths of a second.
+            let denominator = if frame.delay_den == 0 { 100 } else { frame.delay_den };
+
+            compositor.push(
+                &Image::<4, f32, Rgba<f32>>::from_png_data(&im_data, &info),
+                UVec2::new(frame.x_offset, frame.y_offset),
+                match frame.blend_op {
+                    png::BlendOp::Source => Blend::Source,
+                    png::BlendOp::Over => Blend::Over,
+                },
+                match frame.dispose_op {
+                    png::DisposeOp::None => Disposal::Keep,
+                    png::DisposeOp::Background => Disposal::Background,
+                    png::DisposeOp::Previous => Disposal::Previous,
+                },
+                Duration::from_secs_f64(frame.delay_num as f64 / denominator as f64),
+            );
+        }
+
+        reader.finish()?;
+
+        Ok((compositor.finish(control.num_plays), png_metadata(reader.info())?))
+    }
+
+    /// Writes every frame as an APNG, each covering the whole canvas.
+    pub(super) fn write_apng<W: Write>(&self, writer: W, metadata: &ImageMetadata) -> Result<(), ImageError> {
+        let mut encoder = self.frames[0].image.png_encoder(writer, metadata)?;
+        encoder.set_animated(self.frames.len() as u32, self.loop_count)?;
+
+        let mut writer = encoder.write_header()?;
+
+        for frame in self.frames.iter() {
+            if frame.image.resolution != self.frames[0].image.resolution {
+                return Err(ImageError::BadResolution(frame.image.resolution, String::from("apng frames of different sizes")));
+            }
+
+            writer.set_frame_delay(frame.delay.as_millis().min(u16::MAX as u128) as u16, 1000)?;
+            writer.write_image_data(&frame.image.png_data())?;
+        }
+
+        Ok(writer.finish()?)
+    }
+}
+
+/// The color and text chunks of a PNG.
+fn png_metadata(info: &png::Info) -> Result<ImageMetadata, png::DecodingError> {
+    let latin1_text = info.uncompressed_latin1_text.iter()
+        .map(|chunk| Ok((chunk.keyword.clone(), chunk.text.clone())));
+    let compressed_text = info.compressed_latin1_text.iter()
+        .map(|chunk| Ok((chunk.keyword.clone(), chunk.get_text()?)));
+    let utf8_text = info.utf8_text.iter()
+        .map(|chunk| Ok((chunk.keyword.clone(), chunk.get_text()?)));
+
+    Ok(ImageMetadata {
+        gamma: info.gama_chunk.map(|gamma| gamma.into_value()),
+        srgb: info.srgb.map(Into::into),
+        icc_profile: info.icc_profile.as_ref().map(|icc| icc.to_vec()),
+        text: latin1_text.chain(compressed_text).chain(utf8_text).collect::<Result<_, png::DecodingError>>()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::UVec2;
+
+    use crate::image::{metadata::ImageMetadata, pixel::{rgba::Rgba, Pixel}, Image, ImageFormat, WriteOptions};
+
+    #[test]
+    fn keeps_16_bit_samples_and_text() {
+        let resolution = UVec2::new(6, 4);
+        let pixels = (0..24u32)
+            .map(|i| Rgba::from_channels([i * 2731, 65535 - i * 997, i * i * 113, 40000 + i].map(|v| v as f32 / 65535.0)))
+            .collect();
+        let image = Image::<4, f32, Rgba<f32>>::new(resolution, pixels);
+
+        let mut metadata = ImageMetadata::LINEAR;
+        metadata.text.insert(String::from("nprs:args"), String::from("sigma=2.0\nphi=20.0"));
+
+        let mut data = Vec::new();
+        image.write_to(&mut data, ImageFormat::Png, &WriteOptions { metadata: metadata.clone(), ..WriteOptions::default() }).unwrap();
+
+        let (read, read_metadata) = Image::<4, f32, Rgba<f32>>::read_from(data.as_slice()).unwrap();
+
+        assert_eq!(read.resolution(), resolution);
+        assert!(read.iter_pixels().eq(image.iter_pixels()));
+        assert_eq!(read_metadata.gamma, metadata.gamma);
+        assert_eq!(read_metadata.text, metadata.text);
+    }
+
+    #[test]
+    fn keeps_8_bit_samples() {
+        let pixels = (0..=255u8).map(|v| Rgba::new(v, 255 - v, v / 2, 255)).collect();
+        let image = Image::<4, u8, Rgba<u8>>::new(UVec2::new(16, 16), pixels);
+
+        let mut data = Vec::new();
+        image.write_to(&mut data, ImageFormat::Png, &WriteOptions::default()).unwrap();
+
+        let (read, _) = Image::<4, u8, Rgba<u8>>::read_from(data.as_slice()).unwrap();
+
+        assert!(read.iter_pixels().eq(image.iter_pixels()));
+    }
+}
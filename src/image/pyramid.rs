@@ -0,0 +1,200 @@
+use glam::UVec2;
+
+use super::{pixel::Pixel, resize::ResizeFilter, Image};
+
+/// A stack of progressively smoothed and halved copies of an image, starting with the image
+/// itself.
+#[derive(Clone)]
+pub struct GaussianPyramid<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>> {
+    levels: Vec<Image<CHANNELS, f32, P>>,
+}
+
+impl<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>> GaussianPyramid<CHANNELS, P> {
+    /// Builds a pyramid of up to `levels` levels from `image`, stopping early once a level is a
+    /// single pixel.
+    pub fn new(image: &Image<CHANNELS, f32, P>, levels: usize) -> GaussianPyramid<CHANNELS, P> {
+        let mut pyramid = vec![image.clone()];
+
+        while pyramid.len() < levels.min(max_levels(image.resolution())) {
+            let next = reduce(pyramid.last().unwrap());
+            pyramid.push(next);
+        }
+
+        GaussianPyramid { levels: pyramid }
+    }
+
+    /// The level at `index`, where level `0` is the original image and each level after it is
+    /// half the size of the one before.
+    pub fn level(&self, index: usize) -> &Image<CHANNELS, f32, P> {
+        &self.levels[index]
+    }
+
+    pub fn levels(&self) -> &[Image<CHANNELS, f32, P>] {
+        &self.levels
+    }
+
+    pub fn levels_mut(&mut self) -> &mut [Image<CHANNELS, f32, P>] {
+        &mut self.levels
+    }
+
+    pub fn into_levels(self) -> Vec<Image<CHANNELS, f32, P>> {
+        self.levels
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+}
+
+/// A stack of band-pass images, each holding the detail lost between two levels of a
+/// [`GaussianPyramid`], ending with the smallest level of the Gaussian pyramid itself.
+///
+/// Collapsing the pyramid gives back the original image. Scaling the bands before collapsing it
+/// boosts or suppresses detail at each scale.
+#[derive(Clone)]
+pub struct LaplacianPyramid<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>> {
+    levels: Vec<Image<CHANNELS, f32, P>>,
+}
+
+impl<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>> LaplacianPyramid<CHANNELS, P> {
+    /// Builds a pyramid of up to `levels` levels from `image`.
+    ///
+    /// See [`GaussianPyramid::new`].
+    pub fn new(image: &Image<CHANNELS, f32, P>, levels: usize) -> LaplacianPyramid<CHANNELS, P> {
+        Self::from(GaussianPyramid::new(image, levels))
+    }
+
+    /// The level at `index`. Every level but the last is a band-pass image, which is signed, and
+    /// the last is the low-pass residual.
+    pub fn level(&self, index: usize) -> &Image<CHANNELS, f32, P> {
+        &self.levels[index]
+    }
+
+    pub fn levels(&self) -> &[Image<CHANNELS, f32, P>] {
+        &self.levels
+    }
+
+    pub fn levels_mut(&mut self) -> &mut [Image<CHANNELS, f32, P>] {
+        &mut self.levels
+    }
+
+    pub fn into_levels(self) -> Vec<Image<CHANNELS, f32, P>> {
+        self.levels
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+
+    /// Rebuilds the full resolution image, by expanding each level and adding the detail of the
+    /// level above it.
+    pub fn collapse(&self) -> Image<CHANNELS, f32, P> {
+        let mut levels = self.levels.iter().rev();
+        let mut image = levels.next().expect("pyramid has no levels").clone();
+
+        for detail in levels {
+            image = expand(&image, detail.resolution());
+            image.for_each_with_positions(|pixel, pos| *pixel = *pixel + detail.load(pos));
+        }
+
+        image
+    }
+}
+
+impl<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>> From<GaussianPyramid<CHANNELS, P>> for LaplacianPyramid<CHANNELS, P> {
+    fn from(gaussian: GaussianPyramid<CHANNELS, P>) -> Self {
+        let mut levels = gaussian.into_levels();
+
+        // Each level keeps what the smaller level above it can't reproduce.
+        for i in 0..levels.len().saturating_sub(1) {
+            let expanded = expand(&levels[i + 1], levels[i].resolution());
+            levels[i].for_each_with_positions(|pixel, pos| *pixel = *pixel - expanded.load(pos));
+        }
+
+        LaplacianPyramid { levels }
+    }
+}
+
+/// The number of levels until an image of `resolution` is halved down to a single pixel.
+pub fn max_levels(resolution: UVec2) -> usize {
+    resolution.max_element().max(1).next_power_of_two().trailing_zeros() as usize + 1
+}
+
+/// Smooths `image` and halves its resolution, rounding up.
+///
+/// Halving with a tent filter weighs each row and column of source pixels by the binomial
+/// `[1, 3, 3, 1] / 8`, a close approximation of a gaussian.
+fn reduce<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>>(image: &Image<CHANNELS, f32, P>) -> Image<CHANNELS, f32, P> {
+    image.resize((image.resolution() + 1) / 2, ResizeFilter::Bilinear)
+}
+
+/// Upsamples a level of a pyramid to the `resolution` of the level below it.
+fn expand<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>>(image: &Image<CHANNELS, f32, P>, resolution: UVec2) -> Image<CHANNELS, f32, P> {
+    image.resize(resolution, ResizeFilter::Bilinear)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::UVec2;
+
+    use crate::image::{pixel::{rgba::Rgba, Pixel}, Image};
+
+    use super::{max_levels, GaussianPyramid, LaplacianPyramid};
+
+    fn pattern(resolution: UVec2) -> Image<4, f32, Rgba<f32>> {
+        let pixels = (0..resolution.y)
+            .flat_map(|y| (0..resolution.x).map(move |x| Rgba::new((x * 7 % 5) as f32 / 4.0, (y * 3 % 4) as f32 / 3.0, ((x + y) % 2) as f32, 1.0)))
+            .collect();
+
+        Image::new(resolution, pixels)
+    }
+
+    #[test]
+    fn levels_halve_rounding_up() {
+        assert_eq!(max_levels(UVec2::new(5, 3)), 4);
+        assert_eq!(max_levels(UVec2::new(8, 8)), 4);
+        assert_eq!(max_levels(UVec2::new(1, 1)), 1);
+
+        let pyramid = GaussianPyramid::new(&pattern(UVec2::new(5, 3)), 10);
+        let resolutions: Vec<UVec2> = pyramid.levels().iter().map(|level| level.resolution()).collect();
+
+        assert_eq!(resolutions, [UVec2::new(5, 3), UVec2::new(3, 2), UVec2::new(2, 1), UVec2::new(1, 1)]);
+        assert_eq!(GaussianPyramid::new(&pattern(UVec2::new(5, 3)), 2).len(), 2);
+        assert_eq!(LaplacianPyramid::new(&pattern(UVec2::new(5, 3)), 10).len(), 4);
+    }
+
+    #[test]
+    fn collapsing_gives_back_the_image() {
+        for resolution in [UVec2::new(5, 3), UVec2::new(16, 16), UVec2::new(31, 9), UVec2::ONE] {
+            let image = pattern(resolution);
+
+            for levels in [1, 2, 10] {
+                let collapsed = LaplacianPyramid::new(&image, levels).collapse();
+                assert_eq!(collapsed.resolution(), resolution);
+
+                for (a, b) in collapsed.iter_pixels().zip(image.iter_pixels()) {
+                    for (a, b) in a.channels().into_iter().zip(b.channels()) {
+                        assert!((a - b).abs() < 1e-5, "{resolution} with {levels} levels: {a} != {b}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_last_level_is_the_smallest_gaussian_level() {
+        let image = pattern(UVec2::new(12, 7));
+        let gaussian = GaussianPyramid::new(&image, 3);
+        let laplacian = LaplacianPyramid::from(gaussian.clone());
+
+        assert!(laplacian.level(2).iter_pixels().eq(gaussian.level(2).iter_pixels()));
+    }
+}
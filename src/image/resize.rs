@@ -0,0 +1,220 @@
+use std::f32::consts::PI;
+
+use glam::UVec2;
+use nprs_derive::FromParsedValue;
+use rayon::{iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator}, slice::ParallelSliceMut};
+
+use super::{pixel::Pixel, Image};
+
+/// The filter used to resample an image with [`Image::resize`].
+#[derive(FromParsedValue, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeFilter {
+    /// Takes the source pixel nearest to each output pixel.
+    Nearest,
+    /// Interpolates linearly between source pixels, widened to a tent over each output pixel when
+    /// downscaling.
+    Bilinear,
+    /// Catmull-Rom cubic interpolation, sharper than [`ResizeFilter::Bilinear`].
+    Bicubic,
+    /// A windowed sinc with three lobes, the sharpest of the filters. It can ring around hard
+    /// edges.
+    Lanczos3,
+    /// Averages the source pixels covered by each output pixel, weighted by how much of them is
+    /// covered. Best suited to downscaling.
+    Area,
+}
+
+impl ResizeFilter {
+    /// The radius of the filter, before it is stretched for downscaling.
+    fn support(self) -> f32 {
+        match self {
+            ResizeFilter::Nearest | ResizeFilter::Area => 0.5,
+            ResizeFilter::Bilinear => 1.0,
+            ResizeFilter::Bicubic => 2.0,
+            ResizeFilter::Lanczos3 => 3.0,
+        }
+    }
+
+    /// The weight of the filter `x` pixels from its center.
+    fn weight(self, x: f32) -> f32 {
+        let x = x.abs();
+
+        match self {
+            ResizeFilter::Nearest | ResizeFilter::Area => if x < 0.5 { 1.0 } else { 0.0 },
+            ResizeFilter::Bilinear => (1.0 - x).max(0.0),
+            ResizeFilter::Bicubic => {
+                if x < 1.0 {
+                    1.5 * x * x * x - 2.5 * x * x + 1.0
+                } else if x < 2.0 {
+                    -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0
+                } else {
+                    0.0
+                }
+            },
+            ResizeFilter::Lanczos3 => if x < 3.0 { sinc(x) * sinc(x / 3.0) } else { 0.0 },
+        }
+    }
+
+    /// The source pixels, and their weights, that make up each output pixel along an axis resized
+    /// from `from` to `to` pixels. Pixels past the edges are clamped.
+    fn taps(self, from: u32, to: u32) -> Vec<Vec<(usize, f32)>> {
+        let scale = from as f32 / to as f32;
+
+        (0..to)
+            .map(|i| {
+                let center = (i as f32 + 0.5) * scale;
+
+                if self == ResizeFilter::Nearest {
+                    return vec![((center as usize).min(from as usize - 1), 1.0)];
+                }
+
+                let mut taps: Vec<(usize, f32)> = Vec::new();
+
+                let mut push = |j: i64, w: f32| {
+                    let j = j.clamp(0, from as i64 - 1) as usize;
+
+                    match taps.iter_mut().find(|(k, _)| *k == j) {
+                        Some((_, v)) => *v += w,
+                        None => taps.push((j, w)),
+                    }
+                };
+
+                if self == ResizeFilter::Area {
+                    // Each source pixel is weighted by its overlap with the output pixel.
+                    let (start, end) = (center - scale / 2.0, center + scale / 2.0);
+
+                    for j in start.floor() as i64..end.ceil() as i64 {
+                        let w = end.min(j as f32 + 1.0) - start.max(j as f32);
+                        push(j, w);
+                    }
+                } else {
+                    // Downscaling stretches the filter over the output pixel, so that it also
+                    // filters out detail too small to show.
+                    let filter_scale = scale.max(1.0);
+                    let support = self.support() * filter_scale;
+
+                    for j in (center - support).floor() as i64..=(center + support).ceil() as i64 {
+                        let w = self.weight((j as f32 + 0.5 - center) / filter_scale);
+
+                        if w != 0.0 {
+                            push(j, w);
+                        }
+                    }
+                }
+
+                let sum: f32 = taps.iter().map(|(_, w)| w).sum();
+                taps.iter_mut().for_each(|(_, w)| *w /= sum);
+                taps
+            })
+            .collect()
+    }
+}
+
+impl<const CHANNELS: usize, P: Pixel<CHANNELS, Format = f32>> Image<CHANNELS, f32, P> {
+    /// Resamples this image to `resolution` with `filter`.
+    ///
+    /// The image is resized one axis at a time, so the cost grows with the width of the filter
+    /// rather than its area.
+    pub fn resize(&self, resolution: UVec2, filter: ResizeFilter) -> Image<CHANNELS, f32, P> {
+        if resolution.x == 0 || resolution.y == 0 || self.resolution.x == 0 || self.resolution.y == 0 {
+            return Image::new_fill(resolution, P::BLACK);
+        }
+
+        if resolution == self.resolution {
+            return self.clone();
+        }
+
+        let (source_width, width) = (self.resolution.x as usize, resolution.x as usize);
+        let taps_x = filter.taps(self.resolution.x, resolution.x);
+        let taps_y = filter.taps(self.resolution.y, resolution.y);
+
+        // Rows are resized first, keeping the source height.
+        let mut rows = vec![P::BLACK; width * self.resolution.y as usize];
+
+        rows.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+            let source = &self.pixels[y * source_width..(y + 1) * source_width];
+
+            for (c, taps) in row.iter_mut().zip(taps_x.iter()) {
+                *c = taps.iter().fold(P::BLACK, |c, &(i, w)| c + source[i] * w);
+            }
+        });
+
+        // Columns are accumulated a whole row at a time, which reads memory in order.
+        let mut pixels = vec![P::BLACK; (resolution.x * resolution.y) as usize];
+
+        pixels.par_chunks_mut(width).zip(taps_y.par_iter()).for_each(|(row, taps)| {
+            for &(j, w) in taps {
+                let source = &rows[j * width..(j + 1) * width];
+                row.iter_mut().zip(source).for_each(|(c, &v)| *c = *c + v * w);
+            }
+        });
+
+        Image::new(resolution, pixels)
+    }
+}
+
+/// The normalized sinc function.
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::UVec2;
+
+    use crate::image::{pixel::{rgba::Rgba, Pixel}, Image};
+
+    use super::ResizeFilter;
+
+    const FILTERS: [ResizeFilter; 5] = [ResizeFilter::Nearest, ResizeFilter::Bilinear, ResizeFilter::Bicubic, ResizeFilter::Lanczos3, ResizeFilter::Area];
+
+    fn gradient(resolution: UVec2) -> Image<4, f32, Rgba<f32>> {
+        let pixels = (0..resolution.y)
+            .flat_map(|y| (0..resolution.x).map(move |x| Rgba::new(x as f32 / 10.0, y as f32 / 10.0, ((x * y) % 3) as f32, 1.0)))
+            .collect();
+
+        Image::new(resolution, pixels)
+    }
+
+    #[test]
+    fn same_size_is_identity() {
+        let image = gradient(UVec2::new(11, 7));
+
+        for filter in FILTERS {
+            let resized = image.resize(image.resolution(), filter);
+            assert!(resized.iter_pixels().eq(image.iter_pixels()), "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn constant_images_stay_constant() {
+        let color = Rgba::new(0.25, 0.5, 0.75, 1.0);
+        let image = Image::<4, f32, Rgba<f32>>::new_fill(UVec2::new(9, 6), color);
+
+        for filter in FILTERS {
+            for resolution in [UVec2::new(23, 4), UVec2::new(3, 17), UVec2::ONE] {
+                let resized = image.resize(resolution, filter);
+                assert_eq!(resized.resolution(), resolution);
+
+                for pixel in resized.iter_pixels() {
+                    let error = pixel.channels().iter().zip(color.channels()).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+                    assert!(error < 1e-5, "{filter:?} to {resolution}: {pixel:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_upscaling_repeats_pixels() {
+        let image = gradient(UVec2::new(5, 4));
+        let resized = image.resize(UVec2::new(15, 8), ResizeFilter::Nearest);
+
+        for (pixel, pos) in resized.iter_pixels_with_positions() {
+            assert_eq!(*pixel, image.load(pos / UVec2::new(3, 2)));
+        }
+    }
+}
@@ -1,4 +1,7 @@
 use glam::{IVec2, UVec2};
+use nprs_derive::FromParsedValue;
+
+use super::pixel::{rgba::Rgba, Color, Pixel};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Sampler {
@@ -15,49 +18,144 @@ impl Sampler {
     pub const NEAREST_BLACK: Sampler = Sampler { wrap_mode: WrapMode2D::BLACK, filter: Filter::NearestNeighbor };
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+/// How pixels outside the image are sampled along one axis.
+#[derive(FromParsedValue, Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum WrapMode {
+    /// Pixels outside the image are black.
     Black,
+    /// Pixels outside the image take the value of the nearest edge pixel.
     Clamp,
+    /// The image tiles.
     Repeat,
+    /// The image tiles, with every other tile flipped, so edges meet their own reflection.
+    MirroredRepeat,
+    /// The image is reflected once across its edges, then clamped.
+    MirrorOnce,
+    /// Pixels outside the image are the [border color](WrapMode2D::border) of the
+    /// [`WrapMode2D`].
+    Border,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+impl WrapMode {
+    /// Remaps `p` into `0..len`, or returns `None` for pixels that take a fixed color.
+    fn remap(self, p: i32, len: i32) -> Option<i32> {
+        if p >= 0 && p < len {
+            return Some(p);
+        }
+
+        match self {
+            WrapMode::Black | WrapMode::Border => None,
+            WrapMode::Clamp => Some(p.clamp(0, len - 1)),
+            WrapMode::Repeat => Some(modulo(p, len)),
+            WrapMode::MirroredRepeat => {
+                let p = modulo(p, 2 * len);
+                Some(if p < len { p } else { 2 * len - 1 - p })
+            },
+            WrapMode::MirrorOnce => Some(if p < 0 { -1 - p } else { p }.clamp(0, len - 1)),
+        }
+    }
+}
+
+/// How pixels outside the image are sampled along each axis.
+#[derive(FromParsedValue, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[nprs(from = WrapMode2DBuilder)]
 pub struct WrapMode2D {
-    x: WrapMode,
-    y: WrapMode,
+    pub(super) x: WrapMode,
+    pub(super) y: WrapMode,
+    /// The bits of the border color, see [`Self::border`]. Kept as bits so wrap modes can be
+    /// compared and hashed.
+    border: [u32; 4],
 }
 
 impl WrapMode2D {
-    pub const BLACK: WrapMode2D = WrapMode2D { x: WrapMode::Black, y: WrapMode::Black };
-    pub const CLAMP: WrapMode2D = WrapMode2D { x: WrapMode::Clamp, y: WrapMode::Clamp };
-    pub const REPEAT: WrapMode2D = WrapMode2D { x: WrapMode::Repeat, y: WrapMode::Repeat };
+    pub const BLACK: WrapMode2D = WrapMode2D::uniform(WrapMode::Black);
+    pub const CLAMP: WrapMode2D = WrapMode2D::uniform(WrapMode::Clamp);
+    pub const REPEAT: WrapMode2D = WrapMode2D::uniform(WrapMode::Repeat);
+    pub const MIRRORED_REPEAT: WrapMode2D = WrapMode2D::uniform(WrapMode::MirroredRepeat);
+    pub const MIRROR_ONCE: WrapMode2D = WrapMode2D::uniform(WrapMode::MirrorOnce);
 
     pub fn new(x: WrapMode, y: WrapMode) -> WrapMode2D {
-        WrapMode2D { x, y }
+        WrapMode2D { x, y, border: [0; 4] }
+    }
+
+    /// Uses `wrap_mode` for both axes.
+    pub const fn uniform(wrap_mode: WrapMode) -> WrapMode2D {
+        WrapMode2D { x: wrap_mode, y: wrap_mode, border: [0; 4] }
+    }
+
+    /// Uses [`WrapMode::Border`] for both axes, filling pixels outside the image with `border`.
+    pub fn bordered(border: Rgba<f32>) -> WrapMode2D {
+        WrapMode2D { border: border.channels().map(f32::to_bits), ..WrapMode2D::uniform(WrapMode::Border) }
+    }
+
+    /// The color of pixels outside the image on axes with [`WrapMode::Border`].
+    pub fn border(self) -> Rgba<f32> {
+        Rgba::from_channels(self.border.map(f32::from_bits))
+    }
+
+    /// Remaps `p` into an image of `resolution`, or returns `None` when it lies outside the image
+    /// on an axis that fills it with a fixed color, see [`Self::fill`].
+    pub fn remap(self, p: IVec2, resolution: IVec2) -> Option<UVec2> {
+        let x = self.x.remap(p.x, resolution.x)?;
+        let y = self.y.remap(p.y, resolution.y)?;
+
+        Some(UVec2::new(x as u32, y as u32))
     }
 
-    pub fn remap(self, mut p: IVec2, resolution: IVec2) -> Option<UVec2> {
-        for (c, wrap) in [self.x, self.y].into_iter().enumerate() {
-            if p[c] >= 0 && p[c] < resolution[c] {
-                continue;
-            }
-
-            match wrap {
-                WrapMode::Black => return None,
-                WrapMode::Clamp => p[c] = p[c].clamp(0, resolution[c] - 1),
-                WrapMode::Repeat => p[c] = modulo(p[c], resolution[c]),
-            }
+    /// The color of `p`, for a point that [`Self::remap`] doesn't map into the image. That is the
+    /// border color if it lies outside on an axis with [`WrapMode::Border`], and black otherwise.
+    pub fn fill(self, p: IVec2, resolution: IVec2) -> Rgba<f32> {
+        let outside = |wrap, p: i32, len| wrap == WrapMode::Border && (p < 0 || p >= len);
+
+        if outside(self.x, p.x, resolution.x) || outside(self.y, p.y, resolution.y) {
+            self.border()
+        } else {
+            Rgba { r: 0.0, g: 0.0, b: 0.0, a: 0.0 }
         }
+    }
+}
 
-        Some(p.as_uvec2())
+/// The forms a [`WrapMode2D`] can take in `.nprs` files. A single mode applies to both axes.
+#[derive(FromParsedValue)]
+pub enum WrapMode2DBuilder {
+    Black,
+    Clamp,
+    Repeat,
+    MirroredRepeat,
+    MirrorOnce,
+    Border(Color),
+    WrapMode2D {
+        x: WrapMode,
+        y: WrapMode,
+        #[nprs(default = Color::Rgba(0.0, 0.0, 0.0, 0.0))]
+        border: Color,
+    },
+}
+
+impl From<WrapMode2DBuilder> for WrapMode2D {
+    fn from(builder: WrapMode2DBuilder) -> Self {
+        match builder {
+            WrapMode2DBuilder::Black => WrapMode2D::BLACK,
+            WrapMode2DBuilder::Clamp => WrapMode2D::CLAMP,
+            WrapMode2DBuilder::Repeat => WrapMode2D::REPEAT,
+            WrapMode2DBuilder::MirroredRepeat => WrapMode2D::MIRRORED_REPEAT,
+            WrapMode2DBuilder::MirrorOnce => WrapMode2D::MIRROR_ONCE,
+            WrapMode2DBuilder::Border(color) => WrapMode2D::bordered(color.into()),
+            WrapMode2DBuilder::WrapMode2D { x, y, border } => WrapMode2D { x, y, ..WrapMode2D::bordered(border.into()) },
+        }
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(FromParsedValue, Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Filter {
     NearestNeighbor,
     Linear,
+    /// Catmull-Rom interpolation over the nearest 4x4 pixels, sharper than [`Filter::Linear`] when
+    /// magnifying.
+    Bicubic,
+    /// Linear interpolation within and between the two [mip levels](super::Image::mip_levels)
+    /// closest to the size of the sample, which avoids aliasing when minifying.
+    Trilinear,
 }
 
 fn modulo(a: i32, b: i32) -> i32 {
@@ -69,3 +167,28 @@ fn modulo(a: i32, b: i32) -> i32 {
         c
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn borders_round_trip_and_hash() {
+        let border = Rgba::new(0.2, -0.5, 1.5, 0.75);
+        let wrap_mode = WrapMode2D::bordered(border);
+
+        assert_eq!(wrap_mode.border(), border);
+        assert_eq!(wrap_mode.fill(IVec2::new(-1, 0), IVec2::splat(4)), border);
+
+        let samplers: HashSet<_> = [
+            Sampler { wrap_mode, filter: Filter::Linear },
+            Sampler { wrap_mode: WrapMode2D::bordered(border), filter: Filter::Linear },
+            Sampler { wrap_mode: WrapMode2D::bordered(Rgba::new(0.2, -0.5, 1.5, 1.0)), filter: Filter::Linear },
+            Sampler::LINEAR_CLAMP,
+        ].into_iter().collect();
+
+        assert_eq!(samplers.len(), 3);
+    }
+}
@@ -0,0 +1,437 @@
+use std::io::{BufRead, Write};
+
+use glam::UVec2;
+
+use super::{format::PixelFormat, pixel::{rgb::Rgb, Pixel}, Image, ImageError};
+
+/// The magic string every YUV4MPEG2 stream starts with.
+pub const MAGIC: &[u8] = b"YUV4MPEG2";
+
+/// How the chroma planes of a YUV4MPEG2 stream are subsampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subsampling {
+    /// Chroma at half the width and height of the image.
+    Yuv420,
+    /// Chroma at half the width of the image.
+    Yuv422,
+    /// Chroma at the full resolution of the image.
+    Yuv444,
+    /// Luma only.
+    Mono,
+}
+
+impl Subsampling {
+    /// The resolution of each chroma plane for an image of `resolution`.
+    fn chroma_resolution(self, resolution: UVec2) -> UVec2 {
+        match self {
+            Subsampling::Yuv420 => (resolution + 1) / 2,
+            Subsampling::Yuv422 => UVec2::new(resolution.x.div_ceil(2), resolution.y),
+            Subsampling::Yuv444 => resolution,
+            Subsampling::Mono => UVec2::ZERO,
+        }
+    }
+}
+
+/// The stream header of a YUV4MPEG2 file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Y4mHeader {
+    pub resolution: UVec2,
+    /// Frames per second, as a numerator and denominator.
+    pub frame_rate: (u32, u32),
+    pub subsampling: Subsampling,
+    /// Bits per sample, from `8` to `16`.
+    pub bit_depth: u8,
+    /// Whether samples use the full range rather than BT.601 studio swing.
+    pub full_range: bool,
+    /// Header fields that aren't interpreted, like interlacing and the pixel aspect ratio. They are
+    /// written back unchanged.
+    pub other_fields: Vec<String>,
+}
+
+impl Y4mHeader {
+    /// A header for 8-bit 4:2:0 frames of `resolution` at 25 frames per second.
+    pub fn new(resolution: UVec2) -> Y4mHeader {
+        Y4mHeader {
+            resolution,
+            frame_rate: (25, 1),
+            subsampling: Subsampling::Yuv420,
+            bit_depth: 8,
+            full_range: false,
+            other_fields: Vec::new(),
+        }
+    }
+
+    fn parse(line: &str) -> Result<Y4mHeader, ImageError> {
+        let mut fields = line.split(' ');
+
+        if fields.next() != Some("YUV4MPEG2") {
+            return Err(ImageError::MalformedY4m(String::from("missing stream header")));
+        }
+
+        let mut header = Y4mHeader::new(UVec2::ZERO);
+
+        let number = |v: &str| v.parse::<u32>().map_err(|_| ImageError::MalformedY4m(format!("invalid number `{}`", v)));
+
+        for field in fields.filter(|field| !field.is_empty()) {
+            let (tag, value) = field.split_at_checked(1)
+                .ok_or_else(|| ImageError::MalformedY4m(format!("invalid field `{}`", field)))?;
+
+            match tag {
+                "W" => header.resolution.x = number(value)?,
+                "H" => header.resolution.y = number(value)?,
+                "F" => {
+                    let (num, den) = value.split_once(':')
+                        .ok_or(ImageError::MalformedY4m(format!("invalid frame rate `{}`", value)))?;
+                    header.frame_rate = (number(num)?, number(den)?);
+                },
+                "C" => (header.subsampling, header.bit_depth) = Self::parse_colorspace(value)?,
+                "X" if value == "COLORRANGE=FULL" => header.full_range = true,
+                "X" if value == "COLORRANGE=LIMITED" => header.full_range = false,
+                _ => header.other_fields.push(field.to_string()),
+            }
+        }
+
+        if header.resolution.x == 0 || header.resolution.y == 0 {
+            return Err(ImageError::MalformedY4m(String::from("missing frame size")));
+        }
+
+        header.frame_size()?;
+
+        Ok(header)
+    }
+
+    fn parse_colorspace(value: &str) -> Result<(Subsampling, u8), ImageError> {
+        let (subsampling, depth) = match value {
+            "420jpeg" | "420paldv" | "420mpeg2" | "420" => return Ok((Subsampling::Yuv420, 8)),
+            "mono" => return Ok((Subsampling::Mono, 8)),
+            _ if value.starts_with("mono") => (Subsampling::Mono, &value[4..]),
+            _ if value.starts_with("420p") => (Subsampling::Yuv420, &value[4..]),
+            _ if value.starts_with("422") => (Subsampling::Yuv422, value[3..].trim_start_matches('p')),
+            _ if value.starts_with("444") => (Subsampling::Yuv444, value[3..].trim_start_matches('p')),
+            _ => return Err(ImageError::MalformedY4m(format!("unsupported colorspace `{}`", value))),
+        };
+
+        match depth {
+            "" => Ok((subsampling, 8)),
+            depth => match depth.parse::<u8>() {
+                Ok(depth @ 8..=16) => Ok((subsampling, depth)),
+                _ => Err(ImageError::MalformedY4m(format!("unsupported colorspace `{}`", value))),
+            },
+        }
+    }
+
+    fn colorspace(&self) -> String {
+        match (self.subsampling, self.bit_depth) {
+            (Subsampling::Yuv420, 8) => String::from("420jpeg"),
+            (Subsampling::Yuv422, 8) => String::from("422"),
+            (Subsampling::Yuv444, 8) => String::from("444"),
+            (Subsampling::Mono, 8) => String::from("mono"),
+            (Subsampling::Yuv420, depth) => format!("420p{}", depth),
+            (Subsampling::Yuv422, depth) => format!("422p{}", depth),
+            (Subsampling::Yuv444, depth) => format!("444p{}", depth),
+            (Subsampling::Mono, depth) => format!("mono{}", depth),
+        }
+    }
+
+    fn sample_bytes(&self) -> usize {
+        if self.bit_depth > 8 { 2 } else { 1 }
+    }
+
+    /// The size of a frame, in bytes, or an error if that overflows.
+    fn frame_size(&self) -> Result<usize, ImageError> {
+        let chroma = self.subsampling.chroma_resolution(self.resolution);
+        let luma_samples = (self.resolution.x as usize).checked_mul(self.resolution.y as usize);
+        let chroma_samples = (chroma.x as usize).checked_mul(chroma.y as usize).and_then(|v| v.checked_mul(2));
+
+        luma_samples
+            .zip(chroma_samples)
+            .and_then(|(luma, chroma)| luma.checked_add(chroma))
+            .and_then(|samples| samples.checked_mul(self.sample_bytes()))
+            .ok_or_else(|| ImageError::MalformedY4m(format!("frame size {}x{} is too large", self.resolution.x, self.resolution.y)))
+    }
+
+    /// The scale and offset that map a luma or chroma sample to the range `[0, 1]` or `[-0.5, 0.5]`.
+    fn sample_range(&self, chroma: bool) -> (f32, f32) {
+        let max = ((1u32 << self.bit_depth) - 1) as f32;
+        let steps = (1u32 << (self.bit_depth - 8)) as f32;
+
+        match (self.full_range, chroma) {
+            (true, false) => (max, 0.0),
+            (true, true) => (max, (1u32 << (self.bit_depth - 1)) as f32),
+            (false, false) => (219.0 * steps, 16.0 * steps),
+            (false, true) => (224.0 * steps, 128.0 * steps),
+        }
+    }
+}
+
+/// Reads the frames of a YUV4MPEG2 stream one at a time.
+///
+/// Frames are converted to R'G'B' with the BT.601 matrix, upsampling chroma by replication.
+pub struct Y4mReader<R: BufRead> {
+    reader: R,
+    header: Y4mHeader,
+}
+
+impl<R: BufRead> Y4mReader<R> {
+    /// Reads the stream header from `reader`.
+    pub fn new(mut reader: R) -> Result<Y4mReader<R>, ImageError> {
+        let header = Y4mHeader::parse(&read_line(&mut reader)?)?;
+
+        Ok(Y4mReader { reader, header })
+    }
+
+    pub fn header(&self) -> &Y4mHeader {
+        &self.header
+    }
+
+    /// Reads the next frame, or `None` at the end of the stream.
+    pub fn read_frame<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>>(&mut self) -> Result<Option<Image<CHANNELS, F, P>>, ImageError> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+
+        if !read_line(&mut self.reader)?.starts_with("FRAME") {
+            return Err(ImageError::MalformedY4m(String::from("missing frame header")));
+        }
+
+        let header = &self.header;
+
+        let mut data = vec![0; header.frame_size()?];
+        self.reader.read_exact(&mut data)?;
+
+        let samples: Vec<f32> = if header.sample_bytes() == 1 {
+            data.iter().map(|&v| v as f32).collect()
+        } else {
+            data.chunks_exact(2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as f32).collect()
+        };
+
+        let resolution = header.resolution;
+        let chroma_resolution = header.subsampling.chroma_resolution(resolution);
+        let (luma, chroma) = samples.split_at(resolution.x as usize * resolution.y as usize);
+        let (cb, cr) = chroma.split_at(chroma_resolution.x as usize * chroma_resolution.y as usize);
+
+        let (luma_scale, luma_offset) = header.sample_range(false);
+        let (chroma_scale, chroma_offset) = header.sample_range(true);
+
+        // Each chroma sample covers a block of luma samples.
+        let block = if chroma_resolution == UVec2::ZERO { UVec2::ONE } else { (resolution + chroma_resolution - 1) / chroma_resolution };
+
+        let pixels = (0..resolution.y)
+            .flat_map(|y| (0..resolution.x).map(move |x| UVec2::new(x, y)))
+            .map(|pos| {
+                let y = (luma[(pos.y * resolution.x + pos.x) as usize] - luma_offset) / luma_scale;
+
+                let (cb, cr) = if header.subsampling == Subsampling::Mono {
+                    (0.0, 0.0)
+                } else {
+                    let c = pos / block;
+                    let i = (c.y * chroma_resolution.x + c.x) as usize;
+                    ((cb[i] - chroma_offset) / chroma_scale, (cr[i] - chroma_offset) / chroma_scale)
+                };
+
+                let rgb = Rgb::new(
+                    y + 1.402 * cr,
+                    y - 0.344_136 * cb - 0.714_136 * cr,
+                    y + 1.772 * cb,
+                );
+
+                P::from_pixel(Rgb::<F>::from_channels(rgb.channels().map(|v| F::from_scaled_float(v.clamp(0.0, 1.0)))))
+            })
+            .collect();
+
+        Ok(Some(Image::new(resolution, pixels)))
+    }
+}
+
+/// Writes frames to a YUV4MPEG2 stream one at a time.
+///
+/// Frames are converted from R'G'B' with the BT.601 matrix, averaging chroma over each subsampled
+/// block. Alpha is dropped.
+pub struct Y4mWriter<W: Write> {
+    writer: W,
+    header: Y4mHeader,
+}
+
+impl<W: Write> Y4mWriter<W> {
+    /// Writes the stream header to `writer`.
+    pub fn new(mut writer: W, header: Y4mHeader) -> Result<Y4mWriter<W>, ImageError> {
+        let mut line = format!(
+            "YUV4MPEG2 W{} H{} F{}:{} C{}",
+            header.resolution.x,
+            header.resolution.y,
+            header.frame_rate.0,
+            header.frame_rate.1,
+            header.colorspace(),
+        );
+
+        if header.full_range {
+            line.push_str(" XCOLORRANGE=FULL");
+        }
+
+        for field in header.other_fields.iter() {
+            line.push(' ');
+            line.push_str(field);
+        }
+
+        writeln!(writer, "{}", line)?;
+
+        Ok(Y4mWriter { writer, header })
+    }
+
+    pub fn header(&self) -> &Y4mHeader {
+        &self.header
+    }
+
+    /// Writes `image` as the next frame. It must have the resolution given in the header.
+    pub fn write_frame<const CHANNELS: usize, F: PixelFormat, P: Pixel<CHANNELS, Format = F>>(&mut self, image: &Image<CHANNELS, F, P>) -> Result<(), ImageError> {
+        let header = &self.header;
+        let resolution = header.resolution;
+
+        if image.resolution != resolution {
+            return Err(ImageError::BadResolution(image.resolution, format!("a {}x{} y4m stream", resolution.x, resolution.y)));
+        }
+
+        let ycbcr: Vec<[f32; 3]> = image.pixels.iter()
+            .map(|pixel| {
+                let [r, g, b] = pixel.convert::<Rgb<F>>().channels().map(|v| v.to_scaled_float().clamp(0.0, 1.0));
+                let y = 0.299 * r + 0.587 * g + 0.114 * b;
+                [y, (b - y) / 1.772, (r - y) / 1.402]
+            })
+            .collect();
+
+        let (luma_scale, luma_offset) = header.sample_range(false);
+        let (chroma_scale, chroma_offset) = header.sample_range(true);
+        let max = ((1u32 << header.bit_depth) - 1) as f32;
+
+        let mut samples: Vec<f32> = ycbcr.iter().map(|c| c[0] * luma_scale + luma_offset).collect();
+
+        if header.subsampling != Subsampling::Mono {
+            let chroma_resolution = header.subsampling.chroma_resolution(resolution);
+            let block = (resolution + chroma_resolution - 1) / chroma_resolution;
+
+            for channel in [1, 2] {
+                for cy in 0..chroma_resolution.y {
+                    for cx in 0..chroma_resolution.x {
+                        let start = UVec2::new(cx, cy) * block;
+                        let end = (start + block).min(resolution);
+
+                        let (sum, count) = (start.y..end.y)
+                            .flat_map(|y| (start.x..end.x).map(move |x| (y * resolution.x + x) as usize))
+                            .fold((0.0, 0), |(sum, count), i| (sum + ycbcr[i][channel], count + 1));
+
+                        samples.push(sum / count as f32 * chroma_scale + chroma_offset);
+                    }
+                }
+            }
+        }
+
+        let data: Vec<u8> = if header.sample_bytes() == 1 {
+            samples.iter().map(|v| v.round().clamp(0.0, max) as u8).collect()
+        } else {
+            samples.iter().flat_map(|v| (v.round().clamp(0.0, max) as u16).to_le_bytes()).collect()
+        };
+
+        self.writer.write_all(b"FRAME\n")?;
+        self.writer.write_all(&data)?;
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), ImageError> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> Result<String, ImageError> {
+    let mut line = Vec::new();
+    reader.read_until(b'\n', &mut line)?;
+
+    if line.pop() != Some(b'\n') {
+        return Err(ImageError::MalformedY4m(String::from("unexpected end of stream")));
+    }
+
+    String::from_utf8(line).map_err(|_| ImageError::MalformedY4m(String::from("header isn't valid text")))
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::UVec2;
+
+    use crate::image::{pixel::{rgb::Rgb, Pixel}, Image};
+
+    use super::{Subsampling, Y4mHeader, Y4mReader, Y4mWriter};
+
+    fn gradient(resolution: UVec2, frame: u32) -> Image<3, f32, Rgb<f32>> {
+        let pixels = (0..resolution.y)
+            .flat_map(|y| (0..resolution.x).map(move |x| (x, y)))
+            .map(|(x, y)| Rgb::new(x as f32 / resolution.x as f32, y as f32 / resolution.y as f32, frame as f32 / 4.0))
+            .collect();
+
+        Image::new(resolution, pixels)
+    }
+
+    fn assert_close(a: &Image<3, f32, Rgb<f32>>, b: &Image<3, f32, Rgb<f32>>, tolerance: f32) {
+        assert_eq!(a.resolution(), b.resolution());
+
+        for (a, b) in a.iter_pixels().zip(b.iter_pixels()) {
+            let error = a.channels().iter().zip(b.channels()).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+            assert!(error <= tolerance, "{a:?} != {b:?}");
+        }
+    }
+
+    #[test]
+    fn round_trips_frames_and_header() {
+        let resolution = UVec2::new(9, 5);
+        let header = Y4mHeader {
+            frame_rate: (30000, 1001),
+            subsampling: Subsampling::Yuv444,
+            bit_depth: 16,
+            full_range: true,
+            other_fields: vec![String::from("Ip"), String::from("A1:1")],
+            ..Y4mHeader::new(resolution)
+        };
+
+        let mut data = Vec::new();
+        let mut writer = Y4mWriter::new(&mut data, header.clone()).unwrap();
+
+        for frame in 0..3 {
+            writer.write_frame(&gradient(resolution, frame)).unwrap();
+        }
+
+        writer.flush().unwrap();
+        drop(writer);
+
+        let mut reader = Y4mReader::new(data.as_slice()).unwrap();
+        assert_eq!(reader.header(), &header);
+
+        for frame in 0..3 {
+            let read: Image<3, f32, Rgb<f32>> = reader.read_frame().unwrap().unwrap();
+            assert_close(&read, &gradient(resolution, frame), 1e-3);
+        }
+
+        assert!(reader.read_frame::<3, f32, Rgb<f32>>().unwrap().is_none());
+    }
+
+    #[test]
+    fn subsampled_chroma_covers_odd_resolutions() {
+        // A flat color survives any chroma subsampling, even with partial blocks at the edges.
+        let resolution = UVec2::new(7, 3);
+        let image = Image::<3, f32, Rgb<f32>>::new_fill(resolution, Rgb::new(0.8, 0.3, 0.1));
+
+        for subsampling in [Subsampling::Yuv420, Subsampling::Yuv422] {
+            let mut data = Vec::new();
+            let header = Y4mHeader { subsampling, ..Y4mHeader::new(resolution) };
+            Y4mWriter::new(&mut data, header).unwrap().write_frame(&image).unwrap();
+
+            let read: Image<3, f32, Rgb<f32>> = Y4mReader::new(data.as_slice()).unwrap().read_frame().unwrap().unwrap();
+            assert_close(&read, &image, 0.02);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        for header in ["YUV4MPEG2 W4 H4 C444 \u{e9}", "YUV4MPEG2 W4294967295 H4294967295 C444", "YUV4MPEG2 H4 C444"] {
+            assert!(Y4mHeader::parse(header).is_err(), "{header}");
+        }
+    }
+}
@@ -2,19 +2,24 @@
 
 extern crate self as nprs;
 
-use std::path::PathBuf;
+use std::{collections::BTreeMap, fs::File, io::{BufWriter, Write}, num::NonZeroUsize, path::{Path, PathBuf}};
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+use glam::UVec2;
 use half::f16;
-use image::{pixel::{rgb::Rgb, rgba::Rgba}, ImageError};
+use image::{animation::{Animation, Frame}, color_management::{ColorEncoding, TransferFunction}, format::PixelFormat, metadata::RenderingIntent, pixel::{rgb::Rgb, rgba::Rgba, Pixel}, ImageError, ImageFormat, WriteOptions};
 use parser::{cli::PassArg, RenderGraphReadError};
+use pass::luminance::LuminanceMethod;
 use render_graph::RenderGraphVerifyError;
+use sequence::{FramePattern, FrameSink, FrameSource};
 use thiserror::Error;
 
 pub mod pass;
 pub mod image;
 pub mod render_graph;
 pub mod parser;
+mod sequence;
+mod golden;
 
 pub mod pixel {
     pub use nprs::image::pixel::{
@@ -54,24 +59,190 @@ pub mod __private {
     pub type String = alloc::string::String;
 }
 
+/// Metadata keys that record how an output image was produced, so that it can be re-run.
+const RUN_VERSION_KEY: &str = "nprs:version";
+const RUN_GRAPH_KEY: &str = "nprs:graph";
+const RUN_ARGS_KEY: &str = "nprs:args";
+const RUN_INPUT_KEY: &str = "nprs:input";
+const RUN_ALPHA_KEY: &str = "nprs:alpha";
+const RUN_DEPTH_KEY: &str = "nprs:depth";
+
 #[derive(Parser)]
-#[command(author, version, about)]
-struct Args {
+#[command(author, version, about, args_conflicts_with_subcommands = true, arg_required_else_help = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    run: Option<RunArgs>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Re-run the render graph recorded in the metadata of an image written by nprs. Only PNG, JPEG
+    /// and OpenEXR outputs store this metadata.
+    Rerun {
+        /// The previous output to read the render graph, arguments and input path from.
+        image: PathBuf,
+
+        /// The file to write the processed image to, or `-` for stdout.
+        outfile: PathBuf,
+
+        /// Read this input image instead of the recorded one.
+        #[arg(long)]
+        input: Option<PathBuf>,
+
+        /// The format to write, required when writing to stdout. Defaults to the format given by
+        /// the extension of OUTFILE.
+        #[arg(long)]
+        output_format: Option<ImageFormat>,
+
+        /// The most passes to render at once, on independent branches of the render graph. Each
+        /// one holds its output in memory, so more use more memory.
+        #[arg(long, default_value_t = NonZeroUsize::MIN)]
+        parallelism: NonZeroUsize,
+    },
+    /// Compare two images, printing the error and similarity of each channel. Fails if they differ
+    /// by more than a threshold, so that it can be used for regression checks.
+    Compare {
+        /// The reference image.
+        expected: PathBuf,
+
+        /// The image to check against the reference.
+        actual: PathBuf,
+
+        /// The metric checked against the threshold.
+        #[arg(long, value_enum, default_value_t = CompareMetric::Psnr)]
+        metric: CompareMetric,
+
+        /// The worst value of the metric that passes: the highest MSE, or the lowest PSNR or SSIM.
+        /// Without a threshold the images have to be identical.
+        #[arg(long)]
+        threshold: Option<f32>,
+
+        /// Write a heatmap of the differences between the images to this file.
+        #[arg(long)]
+        diff: Option<PathBuf>,
+
+        /// The difference shown as white in the heatmap.
+        #[arg(long, default_value_t = 0.1)]
+        diff_scale: f32,
+    },
+    /// Run the golden image tests listed in a manifest, rendering each effect over its input and
+    /// comparing the result against a reference image.
+    Test {
+        /// The manifest listing the tests.
+        manifest: PathBuf,
+
+        /// Only run the tests whose names contain this.
+        filter: Option<String>,
+
+        /// Where failed tests write their output and a heatmap of its differences. Defaults to a
+        /// `diff` directory next to the manifest.
+        #[arg(long)]
+        diff_dir: Option<PathBuf>,
+
+        /// Overwrite the references with the rendered images instead of comparing against them.
+        #[arg(long)]
+        bless: bool,
+
+        /// The most passes to render at once, on independent branches of the render graph. Each
+        /// one holds its output in memory, so more use more memory.
+        #[arg(long, default_value_t = NonZeroUsize::MIN)]
+        parallelism: NonZeroUsize,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum CompareMetric {
+    /// The mean squared error, averaged over all channels.
+    Mse,
+    /// The peak signal-to-noise ratio of the mean squared error, in decibels.
+    Psnr,
+    /// The structural similarity of the luminance of the images.
+    Ssim,
+}
+
+#[derive(clap::Args)]
+struct RunArgs {
     /// The path to read as the render graph descriptor.
     render_graph: PathBuf,
 
-    /// The file to read as the input image.
+    /// The file to read as the input image, or `-` for stdin.
     input: PathBuf,
 
-    /// The file to write the processed image to.
+    /// The file to write the processed image to, or `-` for stdout.
     outfile: PathBuf,
 
+    /// The format to write, required when writing to stdout. Defaults to the format given by the
+    /// extension of OUTFILE.
+    #[arg(long)]
+    output_format: Option<ImageFormat>,
+
+    /// How to write the alpha channel. Defaults to `premultiplied` for OpenEXR and `none`
+    /// otherwise.
+    #[arg(long, value_enum)]
+    alpha: Option<OutputAlpha>,
+
+    /// The bit depth of the written image, which must be one the format supports. Defaults to the
+    /// deepest the format supports: `float` for OpenEXR and PFM, `8` for JPEG and GIF and `16`
+    /// otherwise.
+    #[arg(long, value_enum)]
+    depth: Option<OutputDepth>,
+
+    /// The most passes to render at once, on independent branches of the render graph. Each one
+    /// holds its output in memory, so more use more memory.
+    #[arg(long, default_value_t = NonZeroUsize::MIN)]
+    parallelism: NonZeroUsize,
+
     /// Additional arguments, formatted NAME=VALUE, that will be supplied to the render graph.
     /// NAME should match the identifier used in the given .nprs file and VALUE should be a valid
     /// expression in the nprs language.
     args: Vec<PassArg>,
 }
 
+/// How to write the output image, where `None` picks a default based on the format.
+#[derive(Clone, Copy)]
+struct OutputArgs {
+    output_format: Option<ImageFormat>,
+    alpha: Option<OutputAlpha>,
+    depth: Option<OutputDepth>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputAlpha {
+    /// Composite onto black and write RGB only.
+    None,
+    /// Write RGBA with color independent of alpha.
+    Straight,
+    /// Write RGBA with color multiplied by alpha.
+    Premultiplied,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputDepth {
+    /// 8-bit integer samples.
+    #[value(name = "8")]
+    Eight,
+    /// 16-bit samples, stored as integers or half floats depending on the format.
+    #[value(name = "16")]
+    Sixteen,
+    /// 32-bit float samples, for formats that support them.
+    Float,
+}
+
+impl OutputDepth {
+    /// The depths `format` can be written with, from shallowest to deepest.
+    fn supported(format: ImageFormat) -> &'static [OutputDepth] {
+        match format {
+            ImageFormat::Jpeg | ImageFormat::Gif => &[OutputDepth::Eight],
+            ImageFormat::Png | ImageFormat::Pgm | ImageFormat::Ppm | ImageFormat::Pam | ImageFormat::Y4m => &[OutputDepth::Eight, OutputDepth::Sixteen],
+            ImageFormat::Exr => &[OutputDepth::Sixteen, OutputDepth::Float],
+            ImageFormat::Pfm => &[OutputDepth::Float],
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum NprsError {
     /// An image error.
@@ -83,22 +254,358 @@ pub enum NprsError {
     /// A render graph verification error.
     #[error(transparent)]
     RenderGraphVerify(#[from] RenderGraphVerifyError),
+    /// An image without the metadata needed to re-run it.
+    #[error("image `{0}` has no recorded render graph")]
+    MissingRunMetadata(String),
+    /// Recorded metadata that couldn't be read back.
+    #[error("invalid recorded {0} `{1}`")]
+    InvalidRunMetadata(&'static str, String),
+    /// Writing to stdout without an explicit format.
+    #[error("writing to stdout requires --output-format")]
+    MissingOutputFormat,
+    /// A frame pattern that doesn't match any files.
+    #[error("no frames match `{0}`")]
+    MissingFrames(String),
+    /// Compared images of different sizes.
+    #[error("compared images have different resolutions ({0} and {1})")]
+    MismatchedResolutions(UVec2, UVec2),
+    /// Compared images that differ by more than the threshold.
+    #[error("images differ: {0}")]
+    ImagesDiffer(String),
+    /// A golden test manifest that couldn't be read, with the location of the problem.
+    #[error("invalid manifest at {0}: {1}")]
+    InvalidManifest(String, String),
+    /// A bit depth the output format can't store.
+    #[error("{0:?} images can't be written with --depth {1}")]
+    UnsupportedDepth(ImageFormat, String),
+    /// Golden tests that didn't match their references.
+    #[error("{0} of {1} golden tests failed")]
+    TestsFailed(usize, usize),
 }
 
 pub fn run_cli() -> Result<(), NprsError> {
-    let args = Args::parse();
+    let cli = Cli::parse();
+
+    match (cli.command, cli.run) {
+        (Some(Command::Rerun { image, outfile, input, output_format, parallelism }), _) => {
+            rerun(&image, outfile, input, output_format, parallelism)
+        },
+        (Some(Command::Compare { expected, actual, metric, threshold, diff, diff_scale }), _) => {
+            compare(&expected, &actual, metric, threshold, diff.map(|diff| (diff, diff_scale)))
+        },
+        (Some(Command::Test { manifest, filter, diff_dir, bless, parallelism }), _) => {
+            let diff_dir = diff_dir.unwrap_or_else(|| manifest.parent().unwrap_or(Path::new("")).join("diff"));
+            golden::Manifest::read(&manifest)?.run(filter.as_deref(), &diff_dir, bless, parallelism)
+        },
+        (None, Some(args)) => {
+            let graph_source = std::fs::read_to_string(&args.render_graph).map_err(RenderGraphReadError::from)?;
+            let output = OutputArgs { output_format: args.output_format, alpha: args.alpha, depth: args.depth };
+            run(&graph_source, args.input, args.outfile, output, args.parallelism, args.args)
+        },
+        // `arg_required_else_help` prints the help instead.
+        (None, None) => unreachable!(),
+    }
+}
+
+fn rerun(
+    image: &Path,
+    outfile: PathBuf,
+    input: Option<PathBuf>,
+    output_format: Option<ImageFormat>,
+    parallelism: NonZeroUsize,
+) -> Result<(), NprsError> {
+    let (_, metadata) = Image::<4, f32, Rgba<f32>>::read_with_metadata(image)?;
+    let text = metadata.text;
+
+    let (Some(graph_source), Some(recorded_input)) = (text.get(RUN_GRAPH_KEY), text.get(RUN_INPUT_KEY)) else {
+        return Err(NprsError::MissingRunMetadata(image.to_string_lossy().to_string()));
+    };
+
+    let args = text.get(RUN_ARGS_KEY).map(String::as_str).unwrap_or_default()
+        .lines()
+        .map(|arg| arg.parse().map_err(|_| NprsError::InvalidRunMetadata("argument", arg.to_string())))
+        .collect::<Result<Vec<PassArg>, _>>()?;
+
+    let alpha = text.get(RUN_ALPHA_KEY)
+        .map(|alpha| OutputAlpha::from_str(alpha, false).map_err(|_| NprsError::InvalidRunMetadata("alpha mode", alpha.to_string())))
+        .transpose()?;
+
+    let depth = text.get(RUN_DEPTH_KEY)
+        .map(|depth| OutputDepth::from_str(depth, false).map_err(|_| NprsError::InvalidRunMetadata("bit depth", depth.to_string())))
+        .transpose()?;
+
+    // Rerunning into another format falls back to its default depth when it can't store the
+    // recorded one.
+    let format = output_format.or_else(|| ImageFormat::from_path(&outfile).ok());
+    let depth = depth.filter(|depth| format.is_none_or(|format| OutputDepth::supported(format).contains(depth)));
+
+    let output = OutputArgs { output_format, alpha, depth };
+
+    run(graph_source, input.unwrap_or(PathBuf::from(recorded_input)), outfile, output, parallelism, args)
+}
+
+/// Compares `actual` against `expected`, optionally writing a heatmap of their differences with the
+/// given scale.
+fn compare(
+    expected: &Path,
+    actual: &Path,
+    metric: CompareMetric,
+    threshold: Option<f32>,
+    diff: Option<(PathBuf, f32)>,
+) -> Result<(), NprsError> {
+    let expected = Image::<4, f32, Rgba<f32>>::read(expected)?;
+    let actual = Image::<4, f32, Rgba<f32>>::read(actual)?;
+
+    if expected.resolution() != actual.resolution() {
+        return Err(NprsError::MismatchedResolutions(expected.resolution(), actual.resolution()));
+    }
+
+    let (mse, psnr, ssim) = (expected.mse(&actual), expected.psnr(&actual), expected.ssim(&actual));
+
+    println!("channel  {:>12}  {:>10}  {:>8}", "mse", "psnr", "ssim");
+
+    for (c, name) in ["r", "g", "b", "a"].into_iter().enumerate() {
+        println!("{:<7}  {:>12.8}  {:>7.2} dB  {:>8.5}", name, mse[c], psnr[c], ssim[c]);
+    }
+
+    let mean_mse = mse.iter().sum::<f32>() / mse.len() as f32;
+    let mean_psnr = image::compare::psnr(mean_mse);
+    let luminance_ssim = expected.luminance_ssim(&actual, LuminanceMethod::Standard);
+
+    println!("{:<7}  {:>12.8}  {:>7.2} dB  {:>8.5}", "overall", mean_mse, mean_psnr, luminance_ssim);
+
+    if let Some((path, scale)) = diff {
+        expected.diff_heatmap(&actual, scale).write(path)?;
+    }
+
+    check_threshold(metric, threshold, mean_mse, luminance_ssim).map(|_| ())
+}
+
+/// Checks the mean MSE or the luminance SSIM of two images against the threshold of `metric`,
+/// requiring identical images without one. Returns the value of the metric.
+fn check_threshold(metric: CompareMetric, threshold: Option<f32>, mean_mse: f32, luminance_ssim: f32) -> Result<String, NprsError> {
+    let (name, value, unit) = match metric {
+        CompareMetric::Mse => ("mse", mean_mse, ""),
+        CompareMetric::Psnr => ("psnr", image::compare::psnr(mean_mse), " dB"),
+        CompareMetric::Ssim => ("ssim", luminance_ssim, ""),
+    };
+
+    // Non-finite pixels make every metric meaningless, so they never pass. An infinite PSNR just
+    // means the images are identical.
+    if !mean_mse.is_finite() || value.is_nan() {
+        return Err(NprsError::ImagesDiffer(format!("{name} is {value}{unit}")));
+    }
+
+    match threshold {
+        None if mean_mse > 0.0 => Err(NprsError::ImagesDiffer(format!("{name} is {value}{unit}"))),
+        Some(threshold) if metric == CompareMetric::Mse && value > threshold => {
+            Err(NprsError::ImagesDiffer(format!("{name} of {value} is above {threshold}")))
+        },
+        Some(threshold) if metric != CompareMetric::Mse && value < threshold => {
+            Err(NprsError::ImagesDiffer(format!("{name} of {value}{unit} is below {threshold}{unit}")))
+        },
+        _ => Ok(format!("{name} {value}{unit}")),
+    }
+}
+
+fn run(
+    graph_source: &str,
+    input_path: PathBuf,
+    outfile: PathBuf,
+    output: OutputArgs,
+    parallelism: NonZeroUsize,
+    args: Vec<PassArg>,
+) -> Result<(), NprsError> {
+    let output_pattern = FramePattern::parse(&outfile);
+
+    let format = match output.output_format {
+        Some(format) => format,
+        None if is_stdio(&outfile) => return Err(NprsError::MissingOutputFormat),
+        None => ImageFormat::from_path(&outfile)?,
+    };
+
+    let source = FrameSource::open(&input_path)?;
+
+    let raw_render_graph = RawRenderGraph::parse(graph_source, args)?;
+
+    let resolved_args: Vec<String> = raw_render_graph.resolved_args().iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+
+    let mut settings = OutputSettings::new(format, output)?;
+
+    let input_path = input_path.canonicalize().unwrap_or(input_path);
+
+    settings.options.metadata.text = BTreeMap::from([
+        (RUN_VERSION_KEY, env!("CARGO_PKG_VERSION").to_string()),
+        (RUN_GRAPH_KEY, graph_source.to_string()),
+        (RUN_ARGS_KEY, resolved_args.join("\n")),
+        (RUN_INPUT_KEY, input_path.to_string_lossy().to_string()),
+        (RUN_ALPHA_KEY, settings.alpha.to_possible_value().unwrap().get_name().to_string()),
+        (RUN_DEPTH_KEY, settings.depth.to_possible_value().unwrap().get_name().to_string()),
+    ].map(|(key, value)| (key.to_string(), value)));
+
+    if !format.stores_text() {
+        eprintln!("warning: {:?} images can't store the render graph, so the output can't be rerun.", format);
+    }
+
+    let sink = match output_pattern {
+        Some(pattern) => FrameSink::Pattern(pattern),
+        None if format == ImageFormat::Y4m => FrameSink::Y4m { outfile, header: source.y4m_header(), writer: None },
+        None => FrameSink::Animation { outfile, frames: Vec::new(), loop_count: source.loop_count() },
+    };
+
+    // Fail before rendering anything when every frame would have to go into a single image.
+    if matches!(sink, FrameSink::Animation { .. }) && !format.supports_animation() && source.has_multiple_frames() {
+        return Err(ImageError::NotAnimated(format).into());
+    }
+
+    sequence::process(raw_render_graph, source, sink, settings, parallelism)
+}
+
+/// Whether `path` stands for stdin or stdout.
+fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// How rendered frames are converted and encoded.
+struct OutputSettings {
+    format: ImageFormat,
+    alpha: OutputAlpha,
+    depth: OutputDepth,
+    transfer: TransferFunction,
+    options: WriteOptions,
+}
+
+impl OutputSettings {
+    /// Settings for `format`, filling in anything not given in `output` with the defaults for that
+    /// format.
+    fn new(format: ImageFormat, output: OutputArgs) -> Result<OutputSettings, NprsError> {
+        let is_exr = format == ImageFormat::Exr;
+        let is_pfm = format == ImageFormat::Pfm;
+
+        // OpenEXR stores premultiplied RGBA.
+        let alpha = output.alpha.unwrap_or(if is_exr { OutputAlpha::Premultiplied } else { OutputAlpha::None });
+
+        // Formats are written as deep as they go, so OpenEXR and PFM keep everything above 1.0.
+        let supported = OutputDepth::supported(format);
+        let depth = output.depth.unwrap_or(supported[supported.len() - 1]);
+
+        if !supported.contains(&depth) {
+            return Err(NprsError::UnsupportedDepth(format, depth.to_possible_value().unwrap().get_name().to_string()));
+        }
+
+        let mut options = WriteOptions::default();
+
+        // OpenEXR and PFM store linear light, everything else is written as sRGB.
+        let transfer = if is_exr || is_pfm {
+            options.metadata.gamma = Some(1.0);
+            TransferFunction::Linear
+        } else {
+            options.metadata.srgb = Some(RenderingIntent::Perceptual);
+            TransferFunction::Srgb
+        };
+
+        Ok(OutputSettings { format, alpha, depth, transfer, options })
+    }
+
+    /// Writes the linear light frames of `animation` to `writer`.
+    fn write(&self, animation: &Animation<4, f32, Rgba<f32>>, writer: impl Write) -> Result<(), ImageError> {
+        match self.depth {
+            OutputDepth::Eight => write_output::<u8>(animation, writer, self.format, self.alpha, self.transfer, &self.options),
+            OutputDepth::Sixteen => write_output::<f16>(animation, writer, self.format, self.alpha, self.transfer, &self.options),
+            OutputDepth::Float => write_output::<f32>(animation, writer, self.format, self.alpha, self.transfer, &self.options),
+        }
+    }
+}
+
+/// Writes the linear light frames of `animation` with the given alpha handling, encoded with
+/// `transfer` and stored as `F`.
+fn write_output<F: PixelFormat>(
+    animation: &Animation<4, f32, Rgba<f32>>,
+    writer: impl Write,
+    format: ImageFormat,
+    alpha: OutputAlpha,
+    transfer: TransferFunction,
+    options: &WriteOptions,
+) -> Result<(), ImageError> {
+    match alpha {
+        OutputAlpha::None => {
+            convert_frames(animation, |image| {
+                let mut image_rgb = image.map(|pixel| pixel.rgb() * pixel.a);
+                image_rgb.encode_transfer(transfer);
+                image_rgb.to_format::<F, Rgb<F>>()
+            }).write_to(writer, format, options)
+        },
+        OutputAlpha::Straight => {
+            convert_frames(animation, |image| {
+                let mut image = image.clone();
+                image.encode_transfer(transfer);
+                image.to_format::<F, Rgba<F>>()
+            }).write_to(writer, format, options)
+        },
+        OutputAlpha::Premultiplied => {
+            convert_frames(animation, |image| {
+                let mut image_premultiplied = image.map(|pixel| Rgba::new(pixel.r * pixel.a, pixel.g * pixel.a, pixel.b * pixel.a, pixel.a));
+                image_premultiplied.encode_transfer(transfer);
+                image_premultiplied.to_format::<F, Rgba<F>>()
+            }).write_to(writer, format, options)
+        },
+    }
+}
+
+/// Applies `f` to every frame of `animation`, keeping the timings.
+fn convert_frames<const CHANNELS: usize, F, P>(
+    animation: &Animation<4, f32, Rgba<f32>>,
+    f: impl Fn(&Image<4, f32, Rgba<f32>>) -> Image<CHANNELS, F, P>,
+) -> Animation<CHANNELS, F, P>
+where
+    F: PixelFormat,
+    P: Pixel<CHANNELS, Format = F>,
+{
+    Animation {
+        frames: animation.frames.iter()
+            .map(|frame| Frame { image: f(&frame.image), delay: frame.delay })
+            .collect(),
+        loop_count: animation.loop_count,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_threshold, CompareMetric, ImageFormat, NprsError, OutputArgs, OutputDepth, OutputSettings};
 
-    let input = Image::<4, f32, Rgba<f32>>::read(args.input)?;
+    #[test]
+    fn thresholds() {
+        assert!(check_threshold(CompareMetric::Psnr, None, 0.0, 1.0).is_ok());
+        assert!(check_threshold(CompareMetric::Psnr, None, 1e-6, 1.0).is_err());
+        assert!(check_threshold(CompareMetric::Psnr, Some(45.0), 1e-6, 1.0).is_ok());
+        assert!(check_threshold(CompareMetric::Mse, Some(1e-7), 1e-6, 1.0).is_err());
+        assert!(check_threshold(CompareMetric::Ssim, Some(0.99), 1e-3, 0.98).is_err());
+    }
 
-    let (mut render_graph, display_node) = RawRenderGraph::read(args.render_graph, args.args)?.build(input)?;
+    #[test]
+    fn non_finite_metrics_fail() {
+        for metric in [CompareMetric::Mse, CompareMetric::Psnr, CompareMetric::Ssim] {
+            for threshold in [None, Some(0.5)] {
+                assert!(check_threshold(metric, threshold, f32::NAN, f32::NAN).is_err());
+                assert!(check_threshold(metric, threshold, f32::INFINITY, 0.0).is_err());
+            }
+        }
 
-    render_graph.verify()?;
-    render_graph.render();
+        assert!(check_threshold(CompareMetric::Ssim, Some(0.5), 0.0, f32::NAN).is_err());
+    }
 
-    let image = render_graph.pop_image(display_node).unwrap();
+    #[test]
+    fn depths_must_suit_the_format() {
+        let settings = |format, depth| OutputSettings::new(format, OutputArgs { output_format: Some(format), alpha: None, depth });
 
-    let image_f16 = image.map(|pixel| pixel.rgb() * pixel.a).to_format::<f16, Rgb<f16>>();
-    image_f16.write(args.outfile)?;
+        for (format, deepest) in [(ImageFormat::Png, OutputDepth::Sixteen), (ImageFormat::Jpeg, OutputDepth::Eight), (ImageFormat::Exr, OutputDepth::Float)] {
+            assert!(settings(format, None).unwrap().depth == deepest);
+        }
 
-    Ok(())
+        for (format, depth) in [(ImageFormat::Png, OutputDepth::Float), (ImageFormat::Gif, OutputDepth::Sixteen), (ImageFormat::Pfm, OutputDepth::Eight)] {
+            assert!(matches!(settings(format, Some(depth)), Err(NprsError::UnsupportedDepth(..))));
+        }
+    }
 }
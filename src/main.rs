@@ -1,6 +1,11 @@
-fn main() {
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
     match nprs::run_cli() {
-        Ok(_) => (),
-        Err(err) => println!("error: {}", err),
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => {
+            println!("error: {}", err);
+            ExitCode::FAILURE
+        },
     }
 }
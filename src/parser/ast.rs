@@ -15,6 +15,11 @@ pub enum Statement {
     Display {
         pass: String,
     },
+    /// A graph-wide setting, like `#encoding = Linear`.
+    Setting {
+        name: String,
+        value: Box<Expr>,
+    },
     Error,
 }
 
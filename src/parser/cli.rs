@@ -27,16 +27,23 @@ impl clap::builder::TypedValueParser for PassArgParser {
         value: &std::ffi::OsStr,
     ) -> Result<Self::Value, clap::Error> {
         let v = value.to_str().ok_or(clap::Error::new(clap::error::ErrorKind::InvalidUtf8))?;
-        let mut parts = v.trim().split("=");
+        v.parse().map_err(clap::Error::new)
+    }
+}
+
+impl std::str::FromStr for PassArg {
+    type Err = clap::error::ErrorKind;
 
-        let name = parts.next().ok_or(clap::Error::new(clap::error::ErrorKind::NoEquals))?;
-        let value_str = parts.next().ok_or(clap::Error::new(clap::error::ErrorKind::InvalidValue))?;
+    /// Parses an argument formatted `NAME=VALUE`.
+    fn from_str(v: &str) -> Result<Self, Self::Err> {
+        let (name, value_str) = v.trim().split_once("=").ok_or(clap::error::ErrorKind::NoEquals)?;
 
         let mut errors = Vec::new();
-        let value = super::grammar::ExprParser::new().parse(&mut errors, value_str).unwrap();
+        let value = super::grammar::ExprParser::new().parse(&mut errors, value_str)
+            .map_err(|_| clap::error::ErrorKind::ValueValidation)?;
 
         if !errors.is_empty() {
-            return Err(clap::Error::new(clap::error::ErrorKind::ValueValidation));
+            return Err(clap::error::ErrorKind::ValueValidation);
         }
 
         Ok(PassArg {
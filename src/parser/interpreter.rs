@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use thiserror::Error;
 
@@ -10,6 +10,9 @@ pub struct Interpreter {
     pub passes: HashMap<String, Box<dyn Pass>>,
     pub edges: HashMap<String, Vec<String>>,
     pub display: Option<String>,
+    pub settings: HashMap<String, ParsedValue>,
+    /// The values every evaluated argument resolved to.
+    pub resolved_args: BTreeMap<String, ParsedValue>,
     symbols: HashMap<String, ParsedValue>,
     args: HashMap<String, Expr>,
 }
@@ -75,6 +78,36 @@ impl ParsedValue {
     }
 }
 
+/// Formats the value as an nprs expression that evaluates back to it.
+impl std::fmt::Display for ParsedValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParsedValue::Int(v) => write!(f, "{}", v),
+            ParsedValue::Float(v) => if v.fract() == 0.0 {
+                write!(f, "{:.1}", v)
+            } else {
+                write!(f, "{}", v)
+            },
+            ParsedValue::Path(path) => write!(f, "\"{}\"", path),
+            ParsedValue::Bool(v) => write!(f, "{}", v),
+            ParsedValue::UnitStruct(name) => write!(f, "{}", name),
+            ParsedValue::Struct { name, fields } => {
+                let is_tuple = (0..fields.len()).all(|i| fields.contains_key(&i.to_string()));
+
+                if is_tuple {
+                    let fields: Vec<String> = (0..fields.len()).map(|i| fields[&i.to_string()].to_string()).collect();
+                    write!(f, "{}({})", name, fields.join(", "))
+                } else {
+                    // Sorted, so that the output doesn't depend on hash map order.
+                    let mut fields: Vec<String> = fields.iter().map(|(field, value)| format!("{}: {}", field, value)).collect();
+                    fields.sort();
+                    write!(f, "{} {{ {} }}", name, fields.join(", "))
+                }
+            },
+        }
+    }
+}
+
 impl Interpreter {
     pub fn new(args: Vec<PassArg>) -> Self {
         let mut args_map = HashMap::new();
@@ -87,6 +120,8 @@ impl Interpreter {
             passes: HashMap::new(),
             edges: HashMap::new(),
             display: None,
+            settings: HashMap::new(),
+            resolved_args: BTreeMap::new(),
             symbols: HashMap::new(),
             args: args_map,
         }
@@ -128,6 +163,10 @@ impl Interpreter {
 
                 self.display = Some(pass);
             },
+            Statement::Setting { name, value: expr } => {
+                let value = self.run_expr(*expr)?;
+                self.settings.insert(name, value);
+            },
             Statement::Error => unreachable!(),
         }
 
@@ -157,18 +196,21 @@ impl Interpreter {
                 }
             },
             Expr::Argument { name, default } => {
-                match self.args.get(&name) {
+                let value = match self.args.get(&name) {
                     Some(expr) => {
-                        self.run_expr(expr.clone())
+                        self.run_expr(expr.clone())?
                     },
                     None => {
                         if let Some(default) = default {
-                            self.run_expr(*default)
+                            self.run_expr(*default)?
                         } else {
-                            Err(InterpreterError::MissingArgument(name))
+                            return Err(InterpreterError::MissingArgument(name));
                         }
                     },
-                }
+                };
+
+                self.resolved_args.insert(name, value.clone());
+                Ok(value)
             },
             Expr::TupleStruct { name, fields } => {
                 let mut field_values = HashMap::new();
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use ast::Statement;
 use cli::PassArg;
@@ -6,18 +6,20 @@ use interpreter::{Interpreter, InterpreterError, ParsedValue};
 use thiserror::Error;
 use lalrpop_util::lalrpop_mod;
 
-use crate::{image::{pixel::rgba::Rgba, Image, ImageError}, pass::Pass, render_graph::{NodeId, RenderGraph}};
+use crate::{image::{color_management::ColorEncoding, pixel::rgba::Rgba, Image, ImageError}, pass::Pass, render_graph::{NodeId, RenderGraph}};
 
 pub mod ast;
 pub mod interpreter;
 pub mod cli;
 pub mod parse_primitives;
-lalrpop_mod!(pub grammar, "/parser/grammar.rs");
+lalrpop_mod!(#[allow(clippy::all)] pub grammar, "/parser/grammar.rs");
 
 pub struct RawRenderGraph {
     passes: HashMap<String, Box<dyn Pass>>,
     edges: HashMap<String, Vec<String>>,
-    display: String
+    display: String,
+    encoding: ColorEncoding,
+    resolved_args: BTreeMap<String, ParsedValue>,
 }
 
 #[derive(Debug, Error)]
@@ -37,16 +39,27 @@ pub enum RenderGraphReadError {
     /// Duplicate pass name.
     #[error("duplicate pass name '{0}'")]
     DuplicateName(String),
+    /// Unknown graph setting.
+    #[error("unknown graph setting '{0}'")]
+    UnknownSetting(String),
+    /// A graph setting with an invalid value.
+    #[error(transparent)]
+    ParseValue(#[from] ParseValueError),
     #[error("invalid syntax")]
     Parse,
 }
 
 impl RawRenderGraph {
     pub fn read<P: AsRef<std::path::Path>>(path: P, args: Vec<PassArg>) -> Result<RawRenderGraph, RenderGraphReadError> {
+        let data = std::fs::read_to_string(path)?;
+        Self::parse(&data, args)
+    }
+
+    /// Parses a render graph from the source of a .nprs file.
+    pub fn parse(data: &str, args: Vec<PassArg>) -> Result<RawRenderGraph, RenderGraphReadError> {
         let mut errors = Vec::new();
 
-        let data = std::fs::read_to_string(path)?;
-        let stmts: Vec<Box<Statement>> = grammar::StatementsParser::new().parse(&mut errors, &data).unwrap();
+        let stmts: Vec<Box<Statement>> = grammar::StatementsParser::new().parse(&mut errors, data).unwrap();
 
         let has_err = !errors.is_empty();
         for err in errors {
@@ -64,15 +77,33 @@ impl RawRenderGraph {
             return Err(RenderGraphReadError::MissingDisplay);
         };
 
+        let mut encoding = ColorEncoding::default();
+
+        for (name, value) in interpreter.settings {
+            match name.as_str() {
+                "encoding" => encoding = ColorEncoding::from_parsed_value(value)?,
+                _ => return Err(RenderGraphReadError::UnknownSetting(name)),
+            }
+        }
 
         Ok(RawRenderGraph {
             passes: interpreter.passes,
             edges: interpreter.edges,
             display,
+            encoding,
+            resolved_args: interpreter.resolved_args,
         })
     }
+
+    /// The value of every argument used by the graph, whether it was supplied or fell back to its
+    /// default.
+    pub fn resolved_args(&self) -> &BTreeMap<String, ParsedValue> {
+        &self.resolved_args
+    }
+
     pub fn build(self, input: Image<4, f32, Rgba<f32>>) -> Result<(RenderGraph, NodeId), RenderGraphReadError> {
         let mut render_graph = RenderGraph::new(input);
+        render_graph.set_encoding(self.encoding);
 
         let mut nodes = HashMap::new();
 
@@ -103,6 +134,8 @@ impl RawRenderGraph {
             return Err(RenderGraphReadError::UndefinedPass(self.display));
         };
 
+        render_graph.keep_image(*display_node);
+
         Ok((render_graph, *display_node))
     }
 }
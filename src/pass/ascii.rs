@@ -1,6 +1,6 @@
 use std::f32::consts::PI;
 
-use glam::UVec2;
+use glam::{IVec2, UVec2};
 use nprs_derive::{FromParsedValue, ParsePass};
 use rayon::iter::ParallelIterator;
 
@@ -57,11 +57,6 @@ impl Pass for Ascii {
         
         let downscaled_res = (source.resolution().as_vec2() / self.char_size as f32).ceil().as_uvec2();
 
-        let mut downscaled_lum = Image::<1, f32, Luma<f32>>::new_fill(
-            downscaled_res,
-            Luma::BLACK,
-        );
-
         let mut downscaled_tfm_histogram = Image::<4, u8, Rgba<u8>>::new_fill(
             downscaled_res,
             Rgba::BLACK,
@@ -72,11 +67,20 @@ impl Pass for Ascii {
             Luma::BLACK,
         );
 
-        source.iter_pixels_with_positions().for_each(|(pixel, pos)| {
-            let pos_downscaled = pos / self.char_size;
-            let mut col = downscaled_lum.get_mut(pos_downscaled);
-            col.v += self.lum.luminance(pixel.r, pixel.g, pixel.b) / (self.char_size * self.char_size) as f32;
-        });
+        // Each cell averages its luminance over the full cell, so partial cells at the edges are
+        // darker.
+        let lum_table = source
+            .map(|pixel| Luma { v: self.lum.luminance(pixel.r, pixel.g, pixel.b) })
+            .summed_area_table();
+
+        let cell_size = IVec2::splat(self.char_size as i32);
+        let cell_area = (self.char_size * self.char_size) as f32;
+
+        let downscaled_lum = Image::<1, f32, Luma<f32>>::new_fill(downscaled_res, Luma::BLACK)
+            .map_with_positions(|_, pos| {
+                let min = (pos * self.char_size).as_ivec2();
+                lum_table.sum(min, min + cell_size) / cell_area
+            });
 
         sobel.iter_pixels_with_positions().for_each(|(pixel, pos)| {
             let pos_downscaled = pos / self.char_size;
@@ -1,7 +1,7 @@
 use glam::{Mat2, Vec2};
 use nprs_derive::{FromParsedValue, ParsePass};
 
-use crate::{image::{pixel::{rgba::Rgba, Pixel}, sampler::Sampler, Image}, pixel::Rgb, render_graph::ANY_IMAGE, SubPass};
+use crate::{image::{color_management::ColorEncoding, pixel::{rgba::Rgba, Pixel}, sampler::{Filter, Sampler, WrapMode2D}, Image}, pixel::Rgb, render_graph::ANY_IMAGE, SubPass};
 
 use super::{luminance::LuminanceMethod, Pass};
 
@@ -20,6 +20,18 @@ pub struct Blend {
     /// Scale of the second image.
     #[nprs(default = Vec2::ONE)]
     scale_b: Vec2,
+    /// Filter used to sample the first image.
+    #[nprs(default = Filter::Linear)]
+    filter_a: Filter,
+    /// Filter used to sample the second image.
+    #[nprs(default = Filter::Linear)]
+    filter_b: Filter,
+    /// How the first image is sampled outside its edges.
+    #[nprs(default = WrapMode2D::REPEAT)]
+    wrap_mode_a: WrapMode2D,
+    /// How the second image is sampled outside its edges.
+    #[nprs(default = WrapMode2D::REPEAT)]
+    wrap_mode_b: WrapMode2D,
     #[nprs(default = false)]
     invert_a: bool,
     #[nprs(default = false)]
@@ -38,6 +50,10 @@ impl Default for Blend {
             rotate_b: 0.0,
             scale_a: Vec2::ONE,
             scale_b: Vec2::ONE,
+            filter_a: Filter::Linear,
+            filter_b: Filter::Linear,
+            wrap_mode_a: WrapMode2D::REPEAT,
+            wrap_mode_b: WrapMode2D::REPEAT,
             invert_a: false,
             invert_b: false,
             invert: false,
@@ -83,16 +99,27 @@ impl Pass for Blend {
         vec![ANY_IMAGE, ANY_IMAGE]
     }
 
+    fn color_encoding(&self) -> Option<ColorEncoding> {
+        Some(ColorEncoding::Linear)
+    }
+
     fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
         let im_a = aux_images[0];
         let im_b = aux_images[1];
 
+        let sampler_a = Sampler { wrap_mode: self.wrap_mode_a, filter: self.filter_a };
+        let sampler_b = Sampler { wrap_mode: self.wrap_mode_b, filter: self.filter_b };
+
+        // Each target pixel covers the inverse of the scale in pixels of the sampled image.
+        let footprint_a = (1.0 / self.scale_a.abs()).max_element();
+        let footprint_b = (1.0 / self.scale_b.abs()).max_element();
+
         target.for_each_with_positions(|pixel, pos| {
             let pos_a = Mat2::from_scale_angle(1.0 / self.scale_a, -self.rotate_a) * pos.as_vec2();
             let pos_b = Mat2::from_scale_angle(1.0 / self.scale_b, -self.rotate_b) * pos.as_vec2();
 
-            let mut a_rgba = im_a.sample_absolute(pos_a, Sampler::LINEAR_REPEAT);
-            let mut b_rgba = im_b.sample_absolute(pos_b, Sampler::LINEAR_REPEAT);
+            let mut a_rgba = im_a.sample_absolute_with_footprint(pos_a, footprint_a, sampler_a);
+            let mut b_rgba = im_b.sample_absolute_with_footprint(pos_b, footprint_b, sampler_b);
 
             let a = if self.invert_a {
                 a_rgba.rgb().invert()
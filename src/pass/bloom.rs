@@ -1,6 +1,6 @@
 use nprs_derive::{FromParsedValue, ParsePass};
 
-use crate::{pass::luminance::Luminance, pixel::Rgba, render_graph::ANY_IMAGE, Image, Pass, SubPass};
+use crate::{image::color_management::ColorEncoding, pass::luminance::Luminance, pixel::Rgba, render_graph::ANY_IMAGE, Image, Pass, SubPass};
 
 use super::{blur::gaussian_blur::GaussianBlur, luminance::LuminanceMethod};
 
@@ -23,6 +23,10 @@ impl Pass for Bloom {
         vec![ANY_IMAGE]
     }
 
+    fn color_encoding(&self) -> Option<ColorEncoding> {
+        Some(ColorEncoding::Linear)
+    }
+
     fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
         let source = aux_images[0];
 
@@ -1,7 +1,7 @@
-use glam::UVec2;
+use glam::IVec2;
 use nprs_derive::{FromParsedValue, ParsePass};
 
-use crate::{image::{pixel::rgba::Rgba, Image}, pass::{Pass, SubPass}, render_graph::ANY_IMAGE};
+use crate::{image::{color_management::ColorEncoding, pixel::{rgba::Rgba, Pixel}, sampler::WrapMode2D, Image}, pass::{Pass, SubPass}, render_graph::ANY_IMAGE};
 
 /// A pass that performs a box blur on the `target` image.
 #[derive(ParsePass, FromParsedValue)]
@@ -9,13 +9,39 @@ use crate::{image::{pixel::rgba::Rgba, Image}, pass::{Pass, SubPass}, render_gra
 pub struct BoxBlur {
     /// The size of the kernel.
     kernel_size: usize,
+
+    /// How pixels outside the image are sampled.
+    wrap_mode: WrapMode2D,
 }
 
 impl BoxBlur {
     pub fn new(kernel_radius: usize) -> Self {
         let kernel_size = 2 * kernel_radius + 1;
 
-        Self { kernel_size }
+        Self { kernel_size, wrap_mode: WrapMode2D::CLAMP }
+    }
+
+    /// How pixels outside the image are sampled.
+    ///
+    /// Defaults to [`WrapMode2D::CLAMP`]
+    pub fn wrap_mode(mut self, wrap_mode: WrapMode2D) -> Self {
+        self.wrap_mode = wrap_mode;
+        self
+    }
+
+    /// Averages the window around each pixel through a summed-area table, so the cost doesn't
+    /// depend on the size of the kernel.
+    fn blur(&self, image: &Image<4, f32, Rgba<f32>>) -> Image<4, f32, Rgba<f32>> {
+        let radius = (self.kernel_size / 2) as u32;
+        let size = IVec2::splat(self.kernel_size as i32);
+
+        // The image is padded by the radius, so every window lies inside the table.
+        let padded = Image::new_fill(image.resolution() + 2 * radius, Rgba::<f32>::BLACK)
+            .map_with_positions(|_, pos| image.load_wrapped(pos.as_ivec2() - radius as i32, self.wrap_mode));
+
+        let table = padded.summed_area_table();
+
+        image.map_with_positions(|_, pos| table.mean(pos.as_ivec2(), pos.as_ivec2() + size))
     }
 }
 
@@ -28,32 +54,31 @@ impl Pass for BoxBlur {
         vec![ANY_IMAGE]
     }
 
-    fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
-        let kernel_area = self.kernel_size * self.kernel_size;
-        let w = 1.0 / kernel_area as f32;
+    fn color_encoding(&self) -> Option<ColorEncoding> {
+        Some(ColorEncoding::Linear)
+    }
 
-        let source = aux_images[0];
-        *target = source.convolve(&vec![w; kernel_area], UVec2::splat(self.kernel_size as u32));
+    fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
+        *target = self.blur(aux_images[0]);
     }
 }
 
 impl SubPass for BoxBlur {
     /// Applies this pass as a subpass, blurring the `target` in-place.
     fn apply_subpass(&self, target: &mut Image<4, f32, Rgba<f32>>, _aux_images: &[&Image<4, f32, Rgba<f32>>]) {
-        let kernel_area = self.kernel_size * self.kernel_size;
-        let w = 1.0 / kernel_area as f32;
-
-        *target = target.convolve(&vec![w; kernel_area], UVec2::splat(self.kernel_size as u32));
+        *target = self.blur(target);
     }
 }
 
 #[derive(FromParsedValue)]
 pub struct BoxBlurBuilder {
-    kernel_radius: usize
+    kernel_radius: usize,
+    #[nprs(default = WrapMode2D::CLAMP)]
+    wrap_mode: WrapMode2D,
 }
 
 impl From<BoxBlurBuilder> for BoxBlur {
     fn from(builder: BoxBlurBuilder) -> Self {
-        BoxBlur::new(builder.kernel_radius)
+        BoxBlur::new(builder.kernel_radius).wrap_mode(builder.wrap_mode)
     }
 }
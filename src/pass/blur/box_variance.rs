@@ -0,0 +1,154 @@
+use glam::IVec2;
+use nprs_derive::{FromParsedValue, ParsePass};
+
+use crate::{image::{color_management::ColorEncoding, integral::SummedAreaTable, pixel::{rgba::Rgba, Pixel}, sampler::WrapMode2D, Image}, pass::{Pass, SubPass}, render_graph::ANY_IMAGE};
+
+/// A pass that finds the variance of each color channel in a box around each pixel of the
+/// `target` image. The output is opaque.
+#[derive(ParsePass, FromParsedValue)]
+#[nprs(from = BoxVarianceBuilder)]
+pub struct BoxVariance {
+    /// The size of the kernel.
+    kernel_size: usize,
+
+    /// How pixels outside the image are sampled.
+    wrap_mode: WrapMode2D,
+
+    /// Whether to output the standard deviation rather than the variance.
+    standard_deviation: bool,
+}
+
+impl BoxVariance {
+    pub fn new(kernel_radius: usize) -> Self {
+        let kernel_size = 2 * kernel_radius + 1;
+
+        Self { kernel_size, wrap_mode: WrapMode2D::CLAMP, standard_deviation: false }
+    }
+
+    /// How pixels outside the image are sampled.
+    ///
+    /// Defaults to [`WrapMode2D::CLAMP`]
+    pub fn wrap_mode(mut self, wrap_mode: WrapMode2D) -> Self {
+        self.wrap_mode = wrap_mode;
+        self
+    }
+
+    /// Whether to output the standard deviation rather than the variance.
+    ///
+    /// Defaults to `false`
+    pub fn standard_deviation(mut self, standard_deviation: bool) -> Self {
+        self.standard_deviation = standard_deviation;
+        self
+    }
+
+    /// Like [`BoxBlur`](super::box_blur::BoxBlur), but through a table that also holds the sums
+    /// of squares.
+    fn variance(&self, image: &Image<4, f32, Rgba<f32>>) -> Image<4, f32, Rgba<f32>> {
+        let radius = (self.kernel_size / 2) as u32;
+        let size = IVec2::splat(self.kernel_size as i32);
+
+        let padded = Image::new_fill(image.resolution() + 2 * radius, Rgba::<f32>::BLACK)
+            .map_with_positions(|_, pos| image.load_wrapped(pos.as_ivec2() - radius as i32, self.wrap_mode));
+
+        let table = SummedAreaTable::with_variance(&padded);
+
+        image.map_with_positions(|_, pos| {
+            let [r, g, b, _] = table.variance(pos.as_ivec2(), pos.as_ivec2() + size).channels().map(|v| {
+                if self.standard_deviation { v.sqrt() } else { v }
+            });
+
+            Rgba::new(r, g, b, 1.0)
+        })
+    }
+}
+
+impl Pass for BoxVariance {
+    fn name(&self) -> &'static str {
+        Self::PASS_NAME
+    }
+
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec![ANY_IMAGE]
+    }
+
+    fn color_encoding(&self) -> Option<ColorEncoding> {
+        Some(ColorEncoding::Linear)
+    }
+
+    /// The variances are statistics, not colors.
+    fn outputs_color(&self) -> bool {
+        false
+    }
+
+    fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
+        *target = self.variance(aux_images[0]);
+    }
+}
+
+impl SubPass for BoxVariance {
+    /// Applies this pass as a subpass, replacing the `target` with its variance.
+    fn apply_subpass(&self, target: &mut Image<4, f32, Rgba<f32>>, _aux_images: &[&Image<4, f32, Rgba<f32>>]) {
+        *target = self.variance(target);
+    }
+}
+
+#[derive(FromParsedValue)]
+pub struct BoxVarianceBuilder {
+    kernel_radius: usize,
+    #[nprs(default = WrapMode2D::CLAMP)]
+    wrap_mode: WrapMode2D,
+    #[nprs(default = false)]
+    standard_deviation: bool,
+}
+
+impl From<BoxVarianceBuilder> for BoxVariance {
+    fn from(builder: BoxVarianceBuilder) -> Self {
+        BoxVariance::new(builder.kernel_radius)
+            .wrap_mode(builder.wrap_mode)
+            .standard_deviation(builder.standard_deviation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::UVec2;
+
+    use super::*;
+
+    #[test]
+    fn flat_images_have_no_variance() {
+        let mut image = Image::new_fill(UVec2::new(7, 5), Rgba::new(0.3, 0.6, 0.9, 0.5));
+        BoxVariance::new(2).apply_subpass(&mut image, &[]);
+
+        for pixel in image.iter_pixels() {
+            assert_eq!(*pixel, Rgba::new(0.0, 0.0, 0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn matches_direct_variance() {
+        let resolution = UVec2::new(9, 6);
+        let source = Image::new_fill(resolution, Rgba::<f32>::BLACK)
+            .map_with_positions(|_, pos| {
+                let v = ((pos.x * 7 + pos.y * 13) % 11) as f32 / 10.0;
+                Rgba::new(v, 1.0 - v, v * v, 1.0)
+            });
+
+        let pass = BoxVariance::new(1).standard_deviation(true);
+        let mut target = source.clone();
+        pass.apply(&mut target, &[&source]);
+
+        for (pixel, pos) in target.iter_pixels_with_positions() {
+            let window: Vec<_> = (-1..=1)
+                .flat_map(|y| (-1..=1).map(move |x| IVec2::new(x, y)))
+                .map(|offset| source.load_wrapped(pos.as_ivec2() + offset, WrapMode2D::CLAMP))
+                .collect();
+
+            for c in 0..3 {
+                let mean = window.iter().map(|p| p.channels()[c]).sum::<f32>() / 9.0;
+                let variance = window.iter().map(|p| (p.channels()[c] - mean).powi(2)).sum::<f32>() / 9.0;
+                assert!((pixel.channels()[c] - variance.sqrt()).abs() < 1e-4, "{pos} {c}");
+            }
+        }
+    }
+}
@@ -1,43 +1,44 @@
 use std::f32::consts::PI;
 
-use glam::UVec2;
 use nprs_derive::{FromParsedValue, ParsePass};
 
-use crate::{image::{pixel::rgba::Rgba, Image}, pass::{Pass, SubPass}, render_graph::ANY_IMAGE};
+use crate::{image::{color_management::ColorEncoding, pixel::rgba::Rgba, sampler::WrapMode2D, Image}, pass::{Pass, SubPass}, render_graph::ANY_IMAGE};
 
 /// A pass that performs a gaussian blur on the `target` image.
 #[derive(ParsePass, FromParsedValue)]
 #[nprs(from = GaussianBlurBuilder)]
 pub struct GaussianBlur {
-    /// The gaussian kernel.
+    /// The one dimensional gaussian kernel, applied along both axes.
     kernel: Vec<f32>,
 
-    /// The size of the gaussian kernel.
-    kernel_size: usize,
+    /// How pixels outside the image are sampled.
+    wrap_mode: WrapMode2D,
 }
 
 impl GaussianBlur {
     pub fn new(sigma: f32, kernel_radius: usize) -> Self {
         let kernel_size = 2 * kernel_radius + 1;
 
-        let mut kernel = Vec::new();
-        let mut kernel_sum = 0.0;
-
-        for x in -(kernel_size as i32 / 2)..=(kernel_size as i32 / 2) {
-            for y in -(kernel_size as i32 / 2)..=(kernel_size as i32 / 2) {
-                let g = gaussian(sigma, x as f32, y as f32);
-                kernel_sum += g;
-                kernel.push(g);
-            }
-        }
+        let mut kernel: Vec<f32> = (-(kernel_size as i32 / 2)..=(kernel_size as i32 / 2))
+            .map(|x| gaussian(sigma, x as f32))
+            .collect();
 
+        let kernel_sum: f32 = kernel.iter().sum();
         kernel.iter_mut().for_each(|v| *v /= kernel_sum);
         
         Self {
             kernel,
-            kernel_size,
+            wrap_mode: WrapMode2D::CLAMP,
         }
     }
+
+    /// How pixels outside the image are sampled.
+    ///
+    /// Defaults to [`WrapMode2D::CLAMP`]
+    pub fn wrap_mode(mut self, wrap_mode: WrapMode2D) -> Self {
+        self.wrap_mode = wrap_mode;
+        self
+    }
 }
 
 impl Pass for GaussianBlur {
@@ -49,20 +50,24 @@ impl Pass for GaussianBlur {
         vec![ANY_IMAGE]
     }
 
+    fn color_encoding(&self) -> Option<ColorEncoding> {
+        Some(ColorEncoding::Linear)
+    }
+
     fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
         let source = aux_images[0];
-        *target = source.convolve(&self.kernel, UVec2::splat(self.kernel_size as u32));
+        *target = source.convolve_separable(&self.kernel, &self.kernel, self.wrap_mode);
     }
 }
 
 impl SubPass for GaussianBlur {
     fn apply_subpass(&self, target: &mut Image<4, f32, Rgba<f32>>, _aux_images: &[&Image<4, f32, Rgba<f32>>]) {
-        *target = target.convolve(&self.kernel, UVec2::splat(self.kernel_size as u32));
+        *target = target.convolve_separable(&self.kernel, &self.kernel, self.wrap_mode);
     }
 }
 
-fn gaussian(sigma: f32, x: f32, y: f32) -> f32 {
-    (1.0 / f32::sqrt(2.0 * PI * sigma * sigma)) * f32::exp(-(x * x + y * y) / (2.0 * sigma * sigma))
+fn gaussian(sigma: f32, x: f32) -> f32 {
+    (1.0 / f32::sqrt(2.0 * PI * sigma * sigma)) * f32::exp(-(x * x) / (2.0 * sigma * sigma))
 }
 
 #[derive(FromParsedValue)]
@@ -70,10 +75,12 @@ pub struct GaussianBlurBuilder {
     sigma: f32,
     #[nprs(default = (__sigma * 2.45).floor() as usize)]
     kernel_radius: usize,
+    #[nprs(default = WrapMode2D::CLAMP)]
+    wrap_mode: WrapMode2D,
 }
 
 impl From<GaussianBlurBuilder> for GaussianBlur {
     fn from(builder: GaussianBlurBuilder) -> Self {
-        GaussianBlur::new(builder.sigma, builder.kernel_radius)
+        GaussianBlur::new(builder.sigma, builder.kernel_radius).wrap_mode(builder.wrap_mode)
     }
 }
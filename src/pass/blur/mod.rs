@@ -1,2 +1,3 @@
 pub mod box_blur;
+pub mod box_variance;
 pub mod gaussian_blur;
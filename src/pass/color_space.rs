@@ -0,0 +1,58 @@
+use nprs_derive::{FromParsedValue, ParsePass};
+
+use crate::{image::{color_management::ColorEncoding, pixel::{color::ColorSpace as Space, rgba::Rgba}, Image}, render_graph::ANY_IMAGE};
+
+use super::{Pass, SubPass};
+
+/// A pass that converts the colors of the `target` image from one color space to another, so that
+/// later passes can work on e.g. the lightness and hue of an image separately. Alpha is left
+/// untouched.
+///
+/// Images in a space other than [`Space::Srgb`] or [`Space::LinearRgb`] hold data rather than
+/// colors, so the render graph leaves them as they are until they are converted back.
+#[derive(ParsePass, FromParsedValue)]
+pub struct ColorSpace {
+    /// The space of the source image. Defaults to [`Space::Srgb`].
+    #[nprs(default = Space::Srgb)]
+    from: Space,
+    /// The space to convert to.
+    to: Space,
+}
+
+impl ColorSpace {
+    pub fn new(from: Space, to: Space) -> Self {
+        Self { from, to }
+    }
+}
+
+impl Pass for ColorSpace {
+    fn name(&self) -> &'static str {
+        Self::PASS_NAME
+    }
+
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec![ANY_IMAGE]
+    }
+
+    /// Converting from an RGB space asks for the source in the matching encoding, and converting
+    /// back to one from data gives the result in the matching encoding.
+    fn color_encoding(&self) -> Option<ColorEncoding> {
+        self.from.encoding().or(self.to.encoding())
+    }
+
+    /// The output is only a color image if it is in the encoding the graph will assume, which
+    /// excludes converting between [`Space::Srgb`] and [`Space::LinearRgb`].
+    fn outputs_color(&self) -> bool {
+        self.to.encoding().is_some() && self.to.encoding() == self.color_encoding()
+    }
+
+    fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
+        *target = aux_images[0].map(|pixel| pixel.convert_space(self.from, self.to));
+    }
+}
+
+impl SubPass for ColorSpace {
+    fn apply_subpass(&self, target: &mut Image<4, f32, Rgba<f32>>, _aux_images: &[&Image<4, f32, Rgba<f32>>]) {
+        target.for_each(|pixel| *pixel = pixel.convert_space(self.from, self.to));
+    }
+}
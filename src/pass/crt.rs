@@ -1,7 +1,7 @@
 use glam::{Vec2, Vec2Swizzles};
 use nprs_derive::{FromParsedValue, ParsePass};
 
-use crate::{image::sampler::Sampler, pixel::Rgba, render_graph::ANY_IMAGE, Image, Pass};
+use crate::{image::sampler::{Filter, Sampler}, pixel::Rgba, render_graph::ANY_IMAGE, Image, Pass};
 
 #[derive(ParsePass, FromParsedValue)]
 pub struct Crt {
@@ -20,6 +20,22 @@ pub struct Crt {
     /// Brightness of the CRT lines.
     #[nprs(default = 0.0)]
     line_brightness: f32,
+    /// Keep the alpha of the source image instead of making the output opaque.
+    #[nprs(default = false)]
+    preserve_alpha: bool,
+    /// Filter used to sample the warped source image.
+    #[nprs(default = Filter::NearestNeighbor)]
+    filter: Filter,
+}
+
+impl Crt {
+    /// Bends `uv` outwards, as if projected onto the curved screen.
+    fn warp(&self, uv: Vec2) -> Vec2 {
+        let mut crt_uv = uv * 2.0 - 1.0;
+        let offset = crt_uv.yx() / self.curvature;
+        crt_uv += crt_uv * offset * offset;
+        crt_uv * 0.5 + 0.5
+    }
 }
 
 impl Pass for Crt {
@@ -34,14 +50,19 @@ impl Pass for Crt {
     fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
         let source = aux_images[0];
         let res = source.resolution().as_vec2();
+        let pixel_size = 1.0 / target.resolution().as_vec2();
+        let sampler = Sampler { filter: self.filter, ..Sampler::NEAREST_BLACK };
 
         target.for_each_with_uvs(|pixel, uv| {
-            let mut crt_uv = uv * 2.0 - 1.0;
-            let offset = crt_uv.yx() / self.curvature;
-            crt_uv += crt_uv * offset * offset;
-            crt_uv = crt_uv * 0.5 + 0.5;
+            let mut crt_uv = self.warp(uv);
+
+            // The distance between neighbouring samples, in source pixels.
+            let dx = (self.warp(uv + Vec2::new(pixel_size.x, 0.0)) - crt_uv) * res;
+            let dy = (self.warp(uv + Vec2::new(0.0, pixel_size.y)) - crt_uv) * res;
+            let footprint = dx.length().max(dy.length());
 
-            let mut col = source.sample(crt_uv, Sampler::NEAREST_BLACK);
+            let mut col = source.sample_with_footprint(crt_uv, footprint, sampler);
+            let alpha = col.a;
 
             crt_uv = crt_uv * 2.0 - 1.0;
             let mut vignette = self.vignette_width / res;
@@ -60,7 +81,7 @@ impl Pass for Crt {
             pixel.r = col.r;
             pixel.g = col.g;
             pixel.b = col.b;
-            pixel.a = 1.0;
+            pixel.a = if self.preserve_alpha { alpha } else { 1.0 };
         });
     }
 }
@@ -1,6 +1,6 @@
 use glam::Vec2;
 
-use crate::{image::{pixel::rgba::Rgba, sampler::Sampler, Image}, pass::SubPass};
+use crate::{image::{pixel::rgba::Rgba, sampler::{Filter, Sampler, WrapMode2D}, Image}, pass::SubPass};
 
 use super::gaussian;
 
@@ -13,6 +13,12 @@ pub struct FDoGBlur1 {
 
     /// DoG Sharpness.
     pub tau: f32,
+
+    /// Filter used to sample the tangent flow map and the luminance.
+    pub filter: Filter,
+
+    /// How samples outside the tangent flow map and the luminance are wrapped.
+    pub wrap_mode: WrapMode2D,
 }
 
 impl SubPass for FDoGBlur1 {
@@ -21,15 +27,17 @@ impl SubPass for FDoGBlur1 {
         let tfm = aux_images[1];
         let pixel_size = 1.0 / target.resolution().as_vec2();
 
+        let sampler = Sampler { wrap_mode: self.wrap_mode, filter: self.filter };
+
         target.for_each_with_uvs(|pixel, uv| {
-            let t = tfm.sample(uv, Sampler::LINEAR_CLAMP);
+            let t = tfm.sample(uv, sampler);
             let mut n = Vec2::new(t.g, -t.r);
             let nabs = n.abs();
             let ds = 1.0 / (if nabs.x > nabs.y { nabs.x } else { nabs.y });
             n *= pixel_size;
 
             // let mut col = Vec2::splat(0.0);
-            let mut col = Vec2::splat(lum.sample(uv, Sampler::LINEAR_CLAMP).r);
+            let mut col = Vec2::splat(lum.sample(uv, sampler).r);
             let mut kernel_sum = Vec2::ONE;
 
             let kernel_size = if self.sigma_e * 2.0 > 1.0 { (self.sigma_e * 2.0).floor() as i32 } else { 1 };
@@ -38,8 +46,8 @@ impl SubPass for FDoGBlur1 {
                 let g1 = gaussian(self.sigma_e, x as f32);
                 let g2 = gaussian(self.sigma_e * self.k, x as f32);
 
-                let c1 = lum.sample(uv - x as f32 * n, Sampler::LINEAR_CLAMP).r;
-                let c2 = lum.sample(uv + x as f32 * n, Sampler::LINEAR_CLAMP).r;
+                let c1 = lum.sample(uv - x as f32 * n, sampler).r;
+                let c2 = lum.sample(uv + x as f32 * n, sampler).r;
 
                 col.x += (c1 + c2) * g1;
                 kernel_sum.x += 2.0 * g1;
@@ -6,7 +6,7 @@ use glam::{Vec2, Vec4, Vec4Swizzles};
 use nprs_derive::{FromParsedValue, ParsePass};
 use threshold::FDoGBlur2Theshold;
 
-use crate::{image::{pixel::rgba::Rgba, Image}, render_graph::ANY_IMAGE};
+use crate::{image::{buffer::{BufferFormat, PixelLayout, Precision}, pixel::rgba::Rgba, sampler::{Filter, WrapMode2D}, Image}, render_graph::ANY_IMAGE};
 
 use super::{tfm::TangentFlowMap, Pass, SubPass};
 
@@ -30,6 +30,8 @@ impl DifferenceOfGaussians {
                 sigma_e: 2.0,
                 k: 1.6,
                 tau: 100.0,
+                filter: Filter::Linear,
+                wrap_mode: WrapMode2D::CLAMP,
             },
             threshold: FDoGBlur2Theshold {
                 sigma_m: 2.0,
@@ -39,10 +41,14 @@ impl DifferenceOfGaussians {
                     white_point: 0.5,
                 },
                 invert: false,
+                filter: Filter::Linear,
+                wrap_mode: WrapMode2D::CLAMP,
             },
             aa: FDoGAntiAlias {
                 sigma_a: 2.0,
                 integral_convolution_stepsizes: Vec2::ONE,
+                filter: Filter::Linear,
+                wrap_mode: WrapMode2D::CLAMP,
             },
         }
     }
@@ -170,6 +176,26 @@ impl DifferenceOfGaussians {
         self
     }
 
+    /// The filter used to sample the tangent flow map and the intermediate images.
+    ///
+    /// Defaults to `Linear`
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.blur1.filter = filter;
+        self.threshold.filter = filter;
+        self.aa.filter = filter;
+        self
+    }
+
+    /// How the tangent flow map and the intermediate images are sampled outside their edges.
+    ///
+    /// Defaults to [`WrapMode2D::CLAMP`]
+    pub fn wrap_mode(mut self, wrap_mode: WrapMode2D) -> Self {
+        self.blur1.wrap_mode = wrap_mode;
+        self.threshold.wrap_mode = wrap_mode;
+        self.aa.wrap_mode = wrap_mode;
+        self
+    }
+
     pub fn integral_convolution_stepsizes(mut self, stepsizes: Vec4) -> Self {
         self.threshold.integral_convolution_stepsizes = stepsizes.xy();
         self.aa.integral_convolution_stepsizes = stepsizes.zw();
@@ -191,6 +217,11 @@ impl Pass for DifferenceOfGaussians {
         vec![ANY_IMAGE, TangentFlowMap::PASS_NAME]
     }
 
+    /// The edges are the same in every channel and opaque.
+    fn output_format(&self) -> BufferFormat {
+        BufferFormat::new(PixelLayout::Luma, Precision::F32)
+    }
+
     fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
         let source = aux_images[0];
         let tfm = aux_images[1];
@@ -248,6 +279,10 @@ pub struct DifferenceOfGaussiansBuilder {
     invert: bool,
     #[nprs(alias = edge_smooth_deviation)]
     sigma_a: f32,
+    #[nprs(default = Filter::Linear)]
+    filter: Filter,
+    #[nprs(default = WrapMode2D::CLAMP)]
+    wrap_mode: WrapMode2D,
 }
 
 impl From<DifferenceOfGaussiansBuilder> for DifferenceOfGaussians {
@@ -261,5 +296,7 @@ impl From<DifferenceOfGaussiansBuilder> for DifferenceOfGaussians {
             .threshold_mode(builder.threshold_mode)
             .invert(builder.invert)
             .edge_smooth_deviation(builder.sigma_a)
+            .filter(builder.filter)
+            .wrap_mode(builder.wrap_mode)
     }
 }
@@ -19,6 +19,9 @@ pub struct BasicDifferenceOfGaussians {
     white_point: f32,
     #[nprs(default = false)]
     invert: bool,
+    /// How pixels outside the luminance image are sampled.
+    #[nprs(default = WrapMode2D::CLAMP)]
+    wrap_mode: WrapMode2D,
 }
 
 impl Pass for BasicDifferenceOfGaussians {
@@ -38,7 +41,7 @@ impl Pass for BasicDifferenceOfGaussians {
             let mut kernel_sum = Vec2::ZERO;
 
             for x in -self.kernel_size..=self.kernel_size {
-                let lum = source.load_wrapped(pos.as_ivec2() + IVec2::new(x, 0), WrapMode2D::CLAMP).r;
+                let lum = source.load_wrapped(pos.as_ivec2() + IVec2::new(x, 0), self.wrap_mode).r;
                 let gauss = Vec2::new(gaussian(self.stdev, x as f32), gaussian(self.stdev * self.stdev_scale, x as f32));
 
                 blur += lum * gauss;
@@ -56,7 +59,7 @@ impl Pass for BasicDifferenceOfGaussians {
             let mut kernel_sum = Vec2::ZERO;
 
             for y in -self.kernel_size..=self.kernel_size {
-                let lum = source.load_wrapped(pos.as_ivec2() + IVec2::new(0, y), WrapMode2D::CLAMP);
+                let lum = source.load_wrapped(pos.as_ivec2() + IVec2::new(0, y), self.wrap_mode);
                 let gauss = Vec2::new(gaussian(self.stdev, y as f32), gaussian(self.stdev * self.stdev_scale, y as f32));
 
                 blur += Vec2::new(lum.r, lum.g) * gauss;
@@ -1,6 +1,6 @@
 use glam::Vec2;
 
-use crate::{image::{pixel::rgba::Rgba, sampler::Sampler, Image}, pass::SubPass};
+use crate::{image::{pixel::rgba::Rgba, sampler::{Filter, Sampler, WrapMode2D}, Image}, pass::SubPass};
 
 use super::{gaussian, FDoGThresholdMode};
 
@@ -13,6 +13,12 @@ pub struct FDoGBlur2Theshold {
 
     /// Whether or not to invert the output.
     pub invert: bool,
+
+    /// Filter used to sample the tangent flow map and the blurred image.
+    pub filter: Filter,
+
+    /// How samples outside the tangent flow map and the blurred image are wrapped.
+    pub wrap_mode: WrapMode2D,
 }
 
 impl SubPass for FDoGBlur2Theshold {
@@ -21,12 +27,14 @@ impl SubPass for FDoGBlur2Theshold {
         let source = target.clone();
         let pixel_size = 1.0 / target.resolution().as_vec2();
 
+        let sampler = Sampler { wrap_mode: self.wrap_mode, filter: self.filter };
+
         target.for_each_with_uvs(|pixel, uv| {
             let kernel_size = self.sigma_m * 2.0;
             let mut w = 1.0;
             let mut g = pixel.a;
 
-            let t = tfm.sample(uv, Sampler::LINEAR_CLAMP);
+            let t = tfm.sample(uv, sampler);
             let v = Vec2::new(t.r, t.g) * pixel_size;
 
             let mut st0 = uv;
@@ -34,13 +42,13 @@ impl SubPass for FDoGBlur2Theshold {
 
             for d in 1..(kernel_size.floor() as i32) {
                 st0 += v0 * self.integral_convolution_stepsizes.x;
-                let c = source.sample(st0, Sampler::LINEAR_CLAMP).a;
+                let c = source.sample(st0, sampler).a;
                 let g1 = gaussian(self.sigma_m, d as f32);
 
                 g += g1 * c;
                 w += g1;
 
-                let v = tfm.sample(st0, Sampler::LINEAR_CLAMP);
+                let v = tfm.sample(st0, sampler);
                 v0 = Vec2::new(v.r, v.g) * pixel_size;
             }
 
@@ -49,13 +57,13 @@ impl SubPass for FDoGBlur2Theshold {
 
             for d in 1..(kernel_size.floor() as i32) {
                 st1 -= v1 * self.integral_convolution_stepsizes.y;
-                let c = source.sample(st1, Sampler::LINEAR_CLAMP).a;
+                let c = source.sample(st1, sampler).a;
                 let g1 = gaussian(self.sigma_m, d as f32);
 
                 g += g1 * c;
                 w += g1;
 
-                let v = tfm.sample(st1, Sampler::LINEAR_CLAMP);
+                let v = tfm.sample(st1, sampler);
                 v1 = Vec2::new(v.r, v.g) * pixel_size;
             }
 
@@ -0,0 +1,103 @@
+use nprs_derive::{FromParsedValue, ParsePass};
+
+use crate::{image::{pixel::rgba::Rgba, Image}, render_graph::ANY_IMAGE};
+
+use super::{luminance::LuminanceMethod, Pass, SubPass};
+
+/// A pass that finds the distance from every pixel of the `target` image to the nearest pixel
+/// past a luminance threshold, such as the edges of a difference of gaussians.
+///
+/// The red channel holds the distance, and the green and blue channels hold the uv of the nearest
+/// seed pixel. Where there is no seed at all every channel is `0.0`, including alpha.
+#[derive(ParsePass, FromParsedValue)]
+pub struct DistanceField {
+    /// Pixels with a luminance above this are seeds.
+    #[nprs(default = 0.5)]
+    threshold: f32,
+    #[nprs(default = LuminanceMethod::Standard)]
+    lum: LuminanceMethod,
+    /// Make the pixels below the threshold the seeds instead.
+    #[nprs(default = false)]
+    invert: bool,
+    #[nprs(default = DistanceMethod::Exact)]
+    method: DistanceMethod,
+    /// The distance, in pixels, that maps to `1.0`.
+    #[nprs(default = 1.0)]
+    max_distance: f32,
+}
+
+#[derive(FromParsedValue, Clone, Copy, PartialEq, Eq)]
+pub enum DistanceMethod {
+    /// The exact Euclidean distance transform.
+    Exact,
+    /// Jump flooding, which is approximate but may be faster on large images.
+    JumpFlood,
+}
+
+impl DistanceField {
+    fn distance_field(&self, target: &mut Image<4, f32, Rgba<f32>>, source: &Image<4, f32, Rgba<f32>>) {
+        let is_seed = |pixel: &Rgba<f32>| (self.lum.luminance(pixel.r, pixel.g, pixel.b) > self.threshold) != self.invert;
+
+        let nearest = match self.method {
+            DistanceMethod::Exact => source.distance_transform(is_seed),
+            DistanceMethod::JumpFlood => source.jump_flood(is_seed),
+        };
+
+        let resolution = source.resolution().as_vec2();
+
+        *target = source.map_with_positions(|_, pos| match nearest.nearest(pos) {
+            Some(seed) => {
+                let uv = (seed.as_vec2() + 0.5) / resolution;
+                Rgba::new(nearest.distance(pos) / self.max_distance, uv.x, uv.y, 1.0)
+            },
+            None => Rgba::new(0.0, 0.0, 0.0, 0.0),
+        });
+    }
+}
+
+impl Pass for DistanceField {
+    fn name(&self) -> &'static str {
+        Self::PASS_NAME
+    }
+
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec![ANY_IMAGE]
+    }
+
+    fn outputs_color(&self) -> bool {
+        false
+    }
+
+    fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
+        self.distance_field(target, aux_images[0]);
+    }
+}
+
+impl SubPass for DistanceField {
+    fn apply_subpass(&self, target: &mut Image<4, f32, Rgba<f32>>, _aux_images: &[&Image<4, f32, Rgba<f32>>]) {
+        let source = target.clone();
+        self.distance_field(target, &source);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::UVec2;
+
+    use crate::{image::{pixel::rgba::Rgba, Image}, pass::{luminance::LuminanceMethod, Pass}};
+
+    use super::{DistanceField, DistanceMethod};
+
+    #[test]
+    fn images_without_seeds_stay_finite() {
+        let source = Image::new_fill(UVec2::new(8, 6), Rgba::new(0.1, 0.1, 0.1, 1.0));
+
+        for method in [DistanceMethod::Exact, DistanceMethod::JumpFlood] {
+            let mut target = source.clone();
+            let pass = DistanceField { threshold: 0.5, lum: LuminanceMethod::Standard, invert: false, method, max_distance: 1.0 };
+            pass.apply(&mut target, &[&source]);
+
+            assert!(target.iter_pixels().all(|pixel| *pixel == Rgba::new(0.0, 0.0, 0.0, 0.0)), "{:?}", target.load(UVec2::ZERO));
+        }
+    }
+}
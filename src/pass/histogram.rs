@@ -0,0 +1,316 @@
+use glam::{UVec2, Vec2};
+use nprs_derive::{FromParsedValue, ParsePass};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{image::{histogram::Histogram, pixel::{luma::Luma, rgba::Rgba}, Image}, render_graph::ANY_IMAGE};
+
+use super::{luminance::LuminanceMethod, Pass, SubPass};
+
+/// A pass that spreads the luminance of the `target` image evenly over the full range, which
+/// brings out detail in low-contrast images.
+#[derive(ParsePass, FromParsedValue)]
+pub struct HistogramEqualize {
+    /// The number of histogram bins.
+    #[nprs(default = 256)]
+    bins: usize,
+    #[nprs(default = LuminanceMethod::Standard)]
+    lum: LuminanceMethod,
+    /// Equalize the red, green and blue channels on their own instead of the luminance, which
+    /// shifts hues.
+    #[nprs(default = false)]
+    per_channel: bool,
+}
+
+impl HistogramEqualize {
+    pub fn new(lum: LuminanceMethod) -> Self {
+        Self { bins: 256, lum, per_channel: false }
+    }
+
+    fn equalize(&self, target: &mut Image<4, f32, Rgba<f32>>, source: &Image<4, f32, Rgba<f32>>) {
+        let bins = self.bins.max(1);
+
+        if self.per_channel {
+            let [r, g, b, _] = source.histograms(bins).map(|h| Mapping::new(h.equalization()));
+
+            target.for_each_with_positions(|pixel, pos| {
+                let source = source.load(pos);
+                *pixel = Rgba::new(r.apply(source.r), g.apply(source.g), b.apply(source.b), source.a);
+            });
+
+            return;
+        }
+
+        let mapping = Mapping::new(source.luminance_histogram(bins, self.lum).equalization());
+
+        target.for_each_with_positions(|pixel, pos| {
+            let source = source.load(pos);
+            let l = self.lum.luminance(source.r, source.g, source.b);
+            *pixel = with_luminance(source, l, mapping.apply(l));
+        });
+    }
+}
+
+impl Pass for HistogramEqualize {
+    fn name(&self) -> &'static str {
+        Self::PASS_NAME
+    }
+
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec![ANY_IMAGE]
+    }
+
+    fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
+        self.equalize(target, aux_images[0]);
+    }
+}
+
+impl SubPass for HistogramEqualize {
+    fn apply_subpass(&self, target: &mut Image<4, f32, Rgba<f32>>, _aux_images: &[&Image<4, f32, Rgba<f32>>]) {
+        let source = target.clone();
+        self.equalize(target, &source);
+    }
+}
+
+/// A pass that equalizes the luminance of the `target` image within a grid of tiles, limiting
+/// how much contrast each tile can gain (contrast-limited adaptive histogram equalization).
+///
+/// Each pixel blends the equalization of the four nearest tiles, so there are no seams between
+/// them.
+#[derive(ParsePass, FromParsedValue)]
+pub struct Clahe {
+    /// The number of tiles along each axis.
+    #[nprs(default = UVec2::splat(8))]
+    tiles: UVec2,
+    /// The most a histogram bin may hold, as a multiple of the average bin of a tile. Lower values
+    /// give less contrast and noise, and `1.0` leaves the image almost unchanged.
+    #[nprs(default = 2.0)]
+    clip_limit: f32,
+    /// The number of histogram bins.
+    #[nprs(default = 256)]
+    bins: usize,
+    #[nprs(default = LuminanceMethod::Standard)]
+    lum: LuminanceMethod,
+}
+
+impl Clahe {
+    pub fn new(lum: LuminanceMethod) -> Self {
+        Self { tiles: UVec2::splat(8), clip_limit: 2.0, bins: 256, lum }
+    }
+
+    /// The number of tiles along each axis.
+    ///
+    /// Defaults to `8` by `8`.
+    pub fn tiles(mut self, tiles: UVec2) -> Self {
+        self.tiles = tiles;
+        self
+    }
+
+    /// The most a histogram bin may hold, as a multiple of the average bin of a tile.
+    ///
+    /// Defaults to `2.0`.
+    pub fn clip_limit(mut self, clip_limit: f32) -> Self {
+        self.clip_limit = clip_limit;
+        self
+    }
+
+    fn equalize(&self, target: &mut Image<4, f32, Rgba<f32>>, source: &Image<4, f32, Rgba<f32>>) {
+        let resolution = source.resolution();
+        let tiles = self.tiles.clamp(UVec2::ONE, resolution.max(UVec2::ONE));
+        let bins = self.bins.max(1);
+
+        // Tiles split the image as evenly as they can, so their sizes differ by at most a pixel.
+        let edges_x = tile_edges(tiles.x, resolution.x);
+        let edges_y = tile_edges(tiles.y, resolution.y);
+
+        let luminance: Image<1, f32, Luma<f32>> = source.map(|pixel| Luma { v: self.lum.luminance(pixel.r, pixel.g, pixel.b) });
+
+        let mappings: Vec<Mapping> = (0..tiles.x * tiles.y)
+            .into_par_iter()
+            .map(|i| {
+                let (x, y) = ((i % tiles.x) as usize, (i / tiles.x) as usize);
+                let min = UVec2::new(edges_x[x], edges_y[y]);
+                let max = UVec2::new(edges_x[x + 1], edges_y[y + 1]);
+
+                let values = (min.y..max.y)
+                    .flat_map(|y| (min.x..max.x).map(move |x| UVec2::new(x, y)))
+                    .map(|pos| luminance.load(pos).v);
+
+                let mut histogram = Histogram::from_values(bins, values);
+                let area = histogram.total() as f32;
+                histogram.clip((self.clip_limit * area / bins as f32).max(1.0) as u32);
+
+                Mapping::new(histogram.cumulative())
+            })
+            .collect();
+
+        let mapping = |tile: UVec2| &mappings[(tile.y * tiles.x + tile.x) as usize];
+        let centres_x = tile_centres(&edges_x);
+        let centres_y = tile_centres(&edges_y);
+
+        target.for_each_with_positions(|pixel, pos| {
+            let source = source.load(pos);
+            let l = luminance.load(pos).v;
+
+            // The nearest tiles on either side of the pixel, and how far it is between their centres.
+            let centre = pos.as_vec2() + 0.5;
+            let (x0, x1, fx) = nearest_tiles(&centres_x, centre.x);
+            let (y0, y1, fy) = nearest_tiles(&centres_y, centre.y);
+            let (t0, t1, f) = (UVec2::new(x0, y0), UVec2::new(x1, y1), Vec2::new(fx, fy));
+
+            let top = lerp(mapping(t0).apply(l), mapping(UVec2::new(t1.x, t0.y)).apply(l), f.x);
+            let bottom = lerp(mapping(UVec2::new(t0.x, t1.y)).apply(l), mapping(t1).apply(l), f.x);
+
+            *pixel = with_luminance(source, l, lerp(top, bottom, f.y));
+        });
+    }
+}
+
+impl Pass for Clahe {
+    fn name(&self) -> &'static str {
+        Self::PASS_NAME
+    }
+
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec![ANY_IMAGE]
+    }
+
+    fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
+        self.equalize(target, aux_images[0]);
+    }
+}
+
+impl SubPass for Clahe {
+    fn apply_subpass(&self, target: &mut Image<4, f32, Rgba<f32>>, _aux_images: &[&Image<4, f32, Rgba<f32>>]) {
+        let source = target.clone();
+        self.equalize(target, &source);
+    }
+}
+
+/// The edges of `tiles` tiles splitting `length` pixels, from `0` to `length`.
+fn tile_edges(tiles: u32, length: u32) -> Vec<u32> {
+    (0..=tiles as u64).map(|i| (i * length as u64 / tiles as u64) as u32).collect()
+}
+
+fn tile_centres(edges: &[u32]) -> Vec<f32> {
+    edges.windows(2).map(|edge| (edge[0] + edge[1]) as f32 / 2.0).collect()
+}
+
+/// The tiles whose centres are on either side of `position`, and how far `position` is from the
+/// first to the second. Positions past the outermost centres use the outermost tile alone.
+fn nearest_tiles(centres: &[f32], position: f32) -> (u32, u32, f32) {
+    let next = centres.partition_point(|&centre| centre <= position);
+
+    if next == 0 {
+        return (0, 0, 0.0);
+    }
+
+    if next == centres.len() {
+        let last = next as u32 - 1;
+        return (last, last, 0.0);
+    }
+
+    let f = (position - centres[next - 1]) / (centres[next] - centres[next - 1]);
+    (next as u32 - 1, next as u32, f)
+}
+
+/// A lookup from values in `[0, 1]` to the value of their histogram bin.
+struct Mapping {
+    values: Vec<f32>,
+}
+
+impl Mapping {
+    fn new(values: Vec<f32>) -> Mapping {
+        Mapping { values }
+    }
+
+    fn apply(&self, value: f32) -> f32 {
+        let bins = self.values.len();
+        let bin = if value.is_nan() { 0 } else { ((value * bins as f32) as usize).min(bins - 1) };
+        self.values[bin]
+    }
+}
+
+/// Scales the color of `pixel` from luminance `from` to luminance `to`, keeping its hue.
+fn with_luminance(pixel: Rgba<f32>, from: f32, to: f32) -> Rgba<f32> {
+    if from <= f32::EPSILON {
+        return Rgba::new(to, to, to, pixel.a);
+    }
+
+    let scale = to / from;
+    Rgba::new(pixel.r * scale, pixel.g * scale, pixel.b * scale, pixel.a)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::UVec2;
+
+    use crate::{image::{pixel::rgba::Rgba, Image}, pass::{luminance::LuminanceMethod, Pass}};
+
+    use super::{nearest_tiles, tile_centres, tile_edges, Clahe, HistogramEqualize};
+
+    #[test]
+    fn tiles_split_evenly() {
+        for (tiles, length) in [(8, 64), (3, 10), (7, 7), (5, 1_000_003)] {
+            let edges = tile_edges(tiles, length);
+
+            assert_eq!(edges.len(), tiles as usize + 1);
+            assert_eq!((edges[0], edges[tiles as usize]), (0, length));
+
+            let sizes: Vec<u32> = edges.windows(2).map(|edge| edge[1] - edge[0]).collect();
+            assert!(sizes.iter().max().unwrap() - sizes.iter().min().unwrap() <= 1, "{sizes:?}");
+        }
+    }
+
+    #[test]
+    fn nearest_tiles_are_either_side() {
+        let centres = tile_centres(&tile_edges(3, 10));
+        assert_eq!(centres, [1.5, 4.5, 8.0]);
+
+        assert_eq!(nearest_tiles(&centres, 0.5), (0, 0, 0.0));
+        assert_eq!(nearest_tiles(&centres, 9.5), (2, 2, 0.0));
+        assert_eq!(nearest_tiles(&centres, 4.5), (1, 2, 0.0));
+        assert_eq!(nearest_tiles(&centres, 6.25), (1, 2, 0.5));
+
+        for i in 0..100 {
+            let position = i as f32 / 10.0;
+            let (a, b, f) = nearest_tiles(&centres, position);
+
+            assert!((0.0..=1.0).contains(&f), "{position}: {f}");
+            assert!(a <= b && b - a <= 1, "{position}: {a} {b}");
+
+            if a != b {
+                let blended = centres[a as usize] + f * (centres[b as usize] - centres[a as usize]);
+                assert!((blended - position).abs() < 1e-5, "{position}: {blended}");
+            }
+        }
+    }
+
+    #[test]
+    fn clahe_keeps_constant_images_constant() {
+        let source = Image::new_fill(UVec2::new(64, 48), Rgba::new(0.3, 0.3, 0.3, 1.0));
+        let mut target = source.clone();
+
+        Clahe::new(LuminanceMethod::Standard).apply(&mut target, &[&source]);
+
+        let first = target.load(UVec2::ZERO);
+        assert!(target.iter_pixels().all(|pixel| *pixel == first), "{first:?}");
+    }
+
+    #[test]
+    fn equalization_stretches_two_values_to_black_and_white() {
+        let pixels = (0..16).map(|i| if i % 3 == 0 { Rgba::new(0.4, 0.4, 0.4, 1.0) } else { Rgba::new(0.6, 0.6, 0.6, 1.0) }).collect();
+        let source = Image::new(UVec2::new(4, 4), pixels);
+        let mut target = source.clone();
+
+        HistogramEqualize::new(LuminanceMethod::Standard).apply(&mut target, &[&source]);
+
+        for (pixel, source) in target.iter_pixels().zip(source.iter_pixels()) {
+            let expected = if source.r < 0.5 { 0.0 } else { 1.0 };
+            assert!((pixel.r - expected).abs() < 1e-5 && pixel.r == pixel.g && pixel.g == pixel.b, "{source:?} -> {pixel:?}");
+        }
+    }
+}
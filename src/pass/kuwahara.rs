@@ -1,7 +1,7 @@
 use glam::{Mat2, Vec2, Vec3, Vec4, Vec4Swizzles as _};
 use nprs_derive::{FromParsedValue, ParsePass};
 
-use crate::{image::{pixel::{rgba::Rgba, Pixel}, sampler::WrapMode2D, Image}, pass::tfm::TangentFlowMap, render_graph::ANY_IMAGE};
+use crate::{image::{color_management::ColorEncoding, pixel::{rgba::Rgba, Pixel}, sampler::WrapMode2D, Image}, pass::tfm::TangentFlowMap, render_graph::ANY_IMAGE};
 
 use super::Pass;
 
@@ -19,6 +19,8 @@ pub struct Kuwahara {
     zeta: Option<f32>,
     #[nprs(default = 1)]
     passes: u32,
+    #[nprs(default = WrapMode2D::CLAMP)]
+    wrap_mode: WrapMode2D,
 }
 
 impl Kuwahara {
@@ -32,6 +34,7 @@ impl Kuwahara {
             zero_crossing: 0.58,
             zeta: None,
             passes: 1,
+            wrap_mode: WrapMode2D::CLAMP,
         }
     }
 
@@ -78,6 +81,14 @@ impl Kuwahara {
         self.passes = passes;
         self
     }
+
+    /// How pixels outside the image are sampled.
+    ///
+    /// Defaults to [`WrapMode2D::CLAMP`]
+    pub fn wrap_mode(mut self, wrap_mode: WrapMode2D) -> Self {
+        self.wrap_mode = wrap_mode;
+        self
+    }
 }
 
 impl Pass for Kuwahara {
@@ -89,6 +100,10 @@ impl Pass for Kuwahara {
         vec![ANY_IMAGE, TangentFlowMap::PASS_NAME]
     }
 
+    fn color_encoding(&self) -> Option<ColorEncoding> {
+        Some(ColorEncoding::Linear)
+    }
+
     fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
         let source = aux_images[0];
         let tfm = aux_images[1];
@@ -129,7 +144,7 @@ impl Pass for Kuwahara {
                 for x in -max_x..=max_x {
                     let p = Vec2::new(x as f32, y as f32);
                     let mut v = sr * p;
-                    let mut c: Vec3 = source.load_wrapped(pos.as_ivec2() + p.as_ivec2(), WrapMode2D::CLAMP).rgb().into();
+                    let mut c: Vec3 = source.load_wrapped(pos.as_ivec2() + p.as_ivec2(), self.wrap_mode).rgb().into();
                     
                     if v.dot(v) <= 0.25 {
                         c = c.clamp(Vec3::ZERO, Vec3::ONE);
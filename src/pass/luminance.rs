@@ -1,6 +1,6 @@
 use nprs_derive::{FromParsedValue, ParsePass};
 
-use crate::{image::{pixel::rgba::Rgba, Image}, render_graph::ANY_IMAGE};
+use crate::{image::{buffer::{BufferFormat, PixelLayout, Precision}, pixel::rgba::Rgba, Image}, render_graph::ANY_IMAGE};
 
 use super::{Pass, SubPass};
 
@@ -8,11 +8,14 @@ use super::{Pass, SubPass};
 #[derive(ParsePass, FromParsedValue)]
 pub struct Luminance {
     method: LuminanceMethod,
+    /// Keep the alpha of the source image instead of making the output opaque.
+    #[nprs(default = false)]
+    preserve_alpha: bool,
 }
 
 impl Luminance {
     pub fn new(method: LuminanceMethod) -> Self {
-        Self { method }
+        Self { method, preserve_alpha: false }
     }
 }
 
@@ -25,6 +28,11 @@ impl Pass for Luminance {
         vec![ANY_IMAGE]
     }
 
+    fn output_format(&self) -> BufferFormat {
+        let layout = if self.preserve_alpha { PixelLayout::LumaAlpha } else { PixelLayout::Luma };
+        BufferFormat::new(layout, Precision::F32)
+    }
+
     fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
         let source = aux_images[0];
 
@@ -34,7 +42,7 @@ impl Pass for Luminance {
             pixel.r = l;
             pixel.g = l;
             pixel.b = l;
-            pixel.a = 1.0;
+            pixel.a = if self.preserve_alpha { main_pixel.a } else { 1.0 };
         });
     }
 }
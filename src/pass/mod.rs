@@ -1,6 +1,6 @@
 use thiserror::Error;
 
-use crate::{image::{pixel::rgba::Rgba, Image}, parser::{interpreter::ParsedValue, ParseValueError}};
+use crate::{image::{buffer::BufferFormat, color_management::ColorEncoding, pixel::rgba::Rgba, Image}, parser::{interpreter::ParsedValue, ParseValueError}};
 
 pub mod tfm;
 pub mod luminance;
@@ -15,15 +15,44 @@ mod sharpness;
 mod palette_swap;
 mod ascii;
 mod crt;
+mod resize;
+mod histogram;
+mod color_space;
+mod distance_field;
 
 /// A render pass that represents a node in the render graph.
-pub trait Pass {
+///
+/// Passes on independent branches of a graph may be applied at the same time from different
+/// threads.
+pub trait Pass: Send + Sync {
     /// The name of this [`Pass`].
     fn name(&self) -> &'static str;
 
     /// The passes this [`Pass`] will be guaranteed to run after.
     fn dependencies(&self) -> Vec<&'static str>;
 
+    /// The encoding this [`Pass`] expects its auxiliary images in. Its output is assumed to be in
+    /// the same encoding.
+    ///
+    /// Passes that average or mix colors should work in [`ColorEncoding::Linear`]. Returns `None`
+    /// by default, which leaves the choice to the render graph.
+    fn color_encoding(&self) -> Option<ColorEncoding> {
+        None
+    }
+
+    /// Whether the output of this [`Pass`] holds colors, rather than data like vectors or
+    /// tensors. Only color images are converted between encodings.
+    fn outputs_color(&self) -> bool {
+        true
+    }
+
+    /// The format the output of this [`Pass`] is stored in until the passes depending on it have
+    /// run. Passes with fewer useful channels than RGBA, like a luminance, should pick a smaller
+    /// format to save memory. Defaults to [`BufferFormat::RGBA_F32`], which keeps everything.
+    fn output_format(&self) -> BufferFormat {
+        BufferFormat::RGBA_F32
+    }
+
     /// Apply this [`Pass`] to the `target` image, given the requisite auxiliary images from graph
     /// connections.
     fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]);
@@ -0,0 +1,76 @@
+use glam::UVec2;
+use nprs_derive::{FromParsedValue, ParsePass};
+
+use crate::{image::{color_management::ColorEncoding, pixel::rgba::Rgba, resize::ResizeFilter, Image}, render_graph::ANY_IMAGE};
+
+use super::{Pass, SubPass};
+
+/// A pass that resamples an image to a new resolution. Passes that depend on it run at the new
+/// resolution.
+#[derive(ParsePass, FromParsedValue)]
+pub struct Resize {
+    size: ResizeSize,
+    #[nprs(default = ResizeFilter::Lanczos3)]
+    filter: ResizeFilter,
+}
+
+/// The resolution a [`Resize`] pass resamples to.
+#[derive(FromParsedValue, Clone, Copy)]
+pub enum ResizeSize {
+    /// An exact resolution, in pixels.
+    Resolution(UVec2),
+    /// A factor applied to both sides of the input.
+    Scale(f32),
+    /// A width in pixels, keeping the aspect ratio of the input.
+    Width(u32),
+    /// A height in pixels, keeping the aspect ratio of the input.
+    Height(u32),
+}
+
+impl ResizeSize {
+    /// The resolution an image of `resolution` is resized to.
+    pub fn resolve(self, resolution: UVec2) -> UVec2 {
+        let resolution = resolution.as_vec2();
+
+        let resized = match self {
+            ResizeSize::Resolution(size) => return size,
+            ResizeSize::Scale(scale) => resolution * scale,
+            ResizeSize::Width(width) => resolution * (width as f32 / resolution.x),
+            ResizeSize::Height(height) => resolution * (height as f32 / resolution.y),
+        };
+
+        resized.round().as_uvec2().max(UVec2::ONE)
+    }
+}
+
+impl Resize {
+    pub fn new(size: ResizeSize, filter: ResizeFilter) -> Self {
+        Self { size, filter }
+    }
+}
+
+impl Pass for Resize {
+    fn name(&self) -> &'static str {
+        Self::PASS_NAME
+    }
+
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec![ANY_IMAGE]
+    }
+
+    fn color_encoding(&self) -> Option<ColorEncoding> {
+        Some(ColorEncoding::Linear)
+    }
+
+    fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
+        let source = aux_images[0];
+        *target = source.resize(self.size.resolve(source.resolution()), self.filter);
+    }
+}
+
+impl SubPass for Resize {
+    /// Applies this pass as a subpass, resizing the `target` in-place.
+    fn apply_subpass(&self, target: &mut Image<4, f32, Rgba<f32>>, _aux_images: &[&Image<4, f32, Rgba<f32>>]) {
+        *target = target.resize(self.size.resolve(target.resolution()), self.filter);
+    }
+}
@@ -6,7 +6,9 @@ use crate::{image::sampler::WrapMode2D, pixel::{Rgb, Rgba}, render_graph::ANY_IM
 #[derive(ParsePass, FromParsedValue)]
 #[nprs(from = SharpnessBuilder)]
 pub struct Sharpness {
-    kernel: Vec<f32>
+    kernel: Vec<f32>,
+    /// How pixels outside the image are sampled.
+    wrap_mode: WrapMode2D,
 }
 
 impl Sharpness {
@@ -20,7 +22,15 @@ impl Sharpness {
             0.0, neighbor, 0.0,
         ];
 
-        Sharpness { kernel }
+        Sharpness { kernel, wrap_mode: WrapMode2D::CLAMP }
+    }
+
+    /// How pixels outside the image are sampled.
+    ///
+    /// Defaults to [`WrapMode2D::CLAMP`]
+    pub fn wrap_mode(mut self, wrap_mode: WrapMode2D) -> Self {
+        self.wrap_mode = wrap_mode;
+        self
     }
 }
 
@@ -36,14 +46,16 @@ impl Pass for Sharpness {
     fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
         let source = aux_images[0];
 
-        *target = source.convolve(&self.kernel, UVec2::new(3, 3));
+        *target = source.convolve_wrapped(&self.kernel, UVec2::new(3, 3), self.wrap_mode);
     }
 }
 
 #[derive(ParsePass, FromParsedValue)]
 #[nprs(from = SharpnessBuilder)]
 pub struct ContrastAdaptiveSharpness {
-    sharpness: f32
+    sharpness: f32,
+    /// How pixels outside the image are sampled.
+    wrap_mode: WrapMode2D,
 }
 
 impl Pass for ContrastAdaptiveSharpness {
@@ -61,15 +73,15 @@ impl Pass for ContrastAdaptiveSharpness {
         *target = source.map_with_positions(|pixel, pos| {
             let p = pos.as_ivec2();
 
-            let a = source.load_wrapped(p + IVec2::new(-1, -1), WrapMode2D::CLAMP).rgb();
-            let b = source.load_wrapped(p + IVec2::new(0, -1), WrapMode2D::CLAMP).rgb();
-            let c = source.load_wrapped(p + IVec2::new(1, -1), WrapMode2D::CLAMP).rgb();
-            let d = source.load_wrapped(p + IVec2::new(-1, 0), WrapMode2D::CLAMP).rgb();
+            let a = source.load_wrapped(p + IVec2::new(-1, -1), self.wrap_mode).rgb();
+            let b = source.load_wrapped(p + IVec2::new(0, -1), self.wrap_mode).rgb();
+            let c = source.load_wrapped(p + IVec2::new(1, -1), self.wrap_mode).rgb();
+            let d = source.load_wrapped(p + IVec2::new(-1, 0), self.wrap_mode).rgb();
             let e = pixel.rgb();
-            let f = source.load_wrapped(p + IVec2::new(1, 0), WrapMode2D::CLAMP).rgb();
-            let g = source.load_wrapped(p + IVec2::new(-1, 1), WrapMode2D::CLAMP).rgb();
-            let h = source.load_wrapped(p + IVec2::new(0, 1), WrapMode2D::CLAMP).rgb();
-            let i = source.load_wrapped(p + IVec2::new(1, 1), WrapMode2D::CLAMP).rgb();
+            let f = source.load_wrapped(p + IVec2::new(1, 0), self.wrap_mode).rgb();
+            let g = source.load_wrapped(p + IVec2::new(-1, 1), self.wrap_mode).rgb();
+            let h = source.load_wrapped(p + IVec2::new(0, 1), self.wrap_mode).rgb();
+            let i = source.load_wrapped(p + IVec2::new(1, 1), self.wrap_mode).rgb();
 
             let mut min_rgb = min3(min3(d, e, f), b, h);
             let min_rgb2 = min3(min3(min_rgb, a, c), g, i);
@@ -106,11 +118,13 @@ fn max3(x: Rgb<f32>, y: Rgb<f32>, z: Rgb<f32>) -> Rgb<f32> {
 #[derive(FromParsedValue)]
 struct SharpnessBuilder {
     amount: f32,
+    #[nprs(default = WrapMode2D::CLAMP)]
+    wrap_mode: WrapMode2D,
 }
 
 impl From<SharpnessBuilder> for Sharpness {
     fn from(value: SharpnessBuilder) -> Self {
-        Sharpness::new(value.amount)
+        Sharpness::new(value.amount).wrap_mode(value.wrap_mode)
     }
 }
 
@@ -118,6 +132,6 @@ impl From<SharpnessBuilder> for ContrastAdaptiveSharpness {
     fn from(value: SharpnessBuilder) -> Self {
         let amount = 10.0 + (7.0 - 10.0) * value.amount.clamp(0.0, 1.0);
         let sharpness = -(1.0 / amount);
-        ContrastAdaptiveSharpness { sharpness }
+        ContrastAdaptiveSharpness { sharpness, wrap_mode: value.wrap_mode }
     }
 }
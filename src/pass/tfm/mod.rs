@@ -41,6 +41,10 @@ impl Pass for TangentFlowMap {
         vec![ANY_IMAGE]
     }
 
+    fn outputs_color(&self) -> bool {
+        false
+    }
+
     fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
         self.sobel_pre_blur.apply(target, aux_images);
         self.sobel.apply_subpass(target, aux_images);
@@ -15,6 +15,10 @@ impl Pass for Sobel {
         vec![ANY_IMAGE]
     }
 
+    fn outputs_color(&self) -> bool {
+        false
+    }
+
     fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
         let source = aux_images[0];
 
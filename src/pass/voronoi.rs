@@ -4,7 +4,7 @@ use rand::Rng;
 use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
 use voronoi::Point;
 
-use crate::{image::{pixel::rgba::Rgba, sampler::{Sampler, WrapMode2D}, Image}, pass::{luminance::Luminance, tfm::TangentFlowMap}, render_graph::ANY_IMAGE};
+use crate::{image::{pixel::rgba::Rgba, sampler::{Filter, Sampler, WrapMode2D}, Image}, pass::{luminance::Luminance, tfm::TangentFlowMap}, render_graph::ANY_IMAGE};
 
 use super::Pass;
 
@@ -73,6 +73,14 @@ pub struct RelaxedVoronoi {
     /// Whether or not to invert centroid weights. Recommended for stippling and not recommended
     /// for mosaics.
     invert: bool,
+
+    /// Filter used to sample the weights when placing the initial points.
+    #[nprs(default = Filter::Linear)]
+    filter: Filter,
+
+    /// How pixels outside the source and the weights are sampled.
+    #[nprs(default = WrapMode2D::CLAMP)]
+    wrap_mode: WrapMode2D,
 }
 
 impl RelaxedVoronoi {
@@ -91,6 +99,8 @@ impl RelaxedVoronoi {
             },
             weight_scale: 10.0,
             invert: true,
+            filter: Filter::Linear,
+            wrap_mode: WrapMode2D::CLAMP,
         }
     }
 
@@ -104,6 +114,8 @@ impl RelaxedVoronoi {
             mode: VoronoiMode::Mosaic,
             weight_scale: 0.5,
             invert: false,
+            filter: Filter::Linear,
+            wrap_mode: WrapMode2D::CLAMP,
         }
     }
 
@@ -181,6 +193,22 @@ impl RelaxedVoronoi {
         self
     }
 
+    /// The filter used to sample the weights when placing the initial points.
+    ///
+    /// Defaults to `Linear`
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// How pixels outside the source and the weights are sampled.
+    ///
+    /// Defaults to [`WrapMode2D::CLAMP`]
+    pub fn wrap_mode(mut self, wrap_mode: WrapMode2D) -> Self {
+        self.wrap_mode = wrap_mode;
+        self
+    }
+
     /// Weights the centroids of voronoi regions based on frequency of the image.
     /// For stippling, this accentuates edge lines.
     /// For the mosaic, this creates smaller tiles near edges, leading to clearer edge lines.
@@ -220,6 +248,7 @@ impl Pass for RelaxedVoronoi {
 
         let mut rng = rand::thread_rng();
         let res = target.resolution();
+        let sampler = Sampler { wrap_mode: self.wrap_mode, filter: self.filter };
 
         // Initialize seed points
         let mut seeds = Vec::new();
@@ -227,9 +256,9 @@ impl Pass for RelaxedVoronoi {
             let u: f32 = rng.gen();
             let v: f32 = rng.gen();
             let l = if let VoronoiRelaxWeightMode::Luminance = self.relax_mode {
-                weights.sample(Vec2::new(u, v), Sampler::LINEAR_CLAMP).r
+                weights.sample(Vec2::new(u, v), sampler).r
             } else {
-                weights.sample(Vec2::new(u, v), Sampler::LINEAR_CLAMP).b
+                weights.sample(Vec2::new(u, v), sampler).b
             };
 
             if l < rng.gen() {
@@ -253,6 +282,7 @@ impl Pass for RelaxedVoronoi {
                     self.invert,
                     self.weight_scale,
                     self.relax_mode == VoronoiRelaxWeightMode::Luminance,
+                    self.wrap_mode,
                 )
             }).collect_into_vec(&mut seeds);
         }
@@ -305,10 +335,11 @@ impl Pass for RelaxedVoronoi {
                         self.invert,
                         self.weight_scale,
                         self.relax_mode == VoronoiRelaxWeightMode::Luminance,
+                    self.wrap_mode,
                     );
 
                     let c = Vec2::new(f64::try_from(centroid.x).unwrap() as f32, f64::try_from(centroid.y).unwrap() as f32);
-                    let color = source.load_wrapped(c.round().as_ivec2(), WrapMode2D::CLAMP);
+                    let color = source.load_wrapped(c.round().as_ivec2(), self.wrap_mode);
 
                     // Draw the polygon
                     let bbox = polygon_raster_bbox(&sorted);
@@ -509,6 +540,7 @@ fn weighted_centroid(
     invert: bool,
     scale: f32,
     luminance: bool,
+    wrap_mode: WrapMode2D,
 ) -> Point {
     let bbox = polygon_raster_bbox(poly);
 
@@ -526,9 +558,9 @@ fn weighted_centroid(
 
             for x in x1..x2 - 1 {
                 let mut weight = if luminance {
-                    weights.load_wrapped(IVec2::new(x, y), WrapMode2D::CLAMP).r
+                    weights.load_wrapped(IVec2::new(x, y), wrap_mode).r
                 } else {
-                    weights.load_wrapped(IVec2::new(x, y), WrapMode2D::CLAMP).b
+                    weights.load_wrapped(IVec2::new(x, y), wrap_mode).b
                 };
 
                 if invert {
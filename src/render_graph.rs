@@ -1,9 +1,9 @@
-use std::{collections::{HashMap, HashSet, VecDeque}, ops::Deref};
+use std::{borrow::Cow, collections::{HashMap, HashSet, VecDeque}, num::NonZeroUsize, ops::Deref, panic::{self, AssertUnwindSafe}, sync::{mpsc, Arc}, thread};
 
 use glam::UVec2;
 use thiserror::Error;
 
-use crate::{image::{pixel::{rgba::Rgba, Pixel}, Image}, pass::Pass};
+use crate::{image::{buffer::Buffer, color_management::ColorEncoding, pixel::{rgba::Rgba, Pixel}, resize::ResizeFilter, Image}, pass::Pass};
 
 /// The string representing the main image dependency.
 pub const MAIN_IMAGE: &str = "main";
@@ -13,40 +13,101 @@ pub const MAIN_IMAGE: &str = "main";
 pub const ANY_IMAGE: &str = "*";
 
 pub struct RenderGraph {
-    pub images: HashMap<NodeId, Image<4, f32, Rgba<f32>>>,
+    /// The rendered images, each stored in the [`Pass::output_format`] of its pass.
+    pub images: HashMap<NodeId, Arc<Buffer>>,
 
     /// The edges of the graph, where a node is directed towards its dependencies.
     pub edges: HashMap<NodeId, Vec<NodeId>>,
 
     pub passes: HashMap<NodeId, Box<dyn Pass>>,
     pub names: HashSet<&'static str>,
-    
+
+    /// The encoding each rendered image is stored in.
+    encodings: HashMap<NodeId, ColorEncoding>,
+    /// The encoding used by passes that don't declare one.
+    encoding: ColorEncoding,
+
+    /// The passes to render, in an order where every pass comes after its dependencies. Built by
+    /// [`Self::verify`].
+    plan: Vec<NodeId>,
+    /// The most passes rendered at once.
+    parallelism: NonZeroUsize,
+    /// Nodes whose images are kept after rendering, besides the root and the source.
+    kept: HashSet<NodeId>,
+
     root: NodeId,
     node_count: NodeId,
     resolution: UVec2,
 }
 
 impl RenderGraph {
+    /// Creates a new [`RenderGraph`] with `image` as its source, assumed to be
+    /// [`ColorEncoding::Display`] encoded until [`Self::set_source_encoding`] says otherwise.
     pub fn new(image: Image<4, f32, Rgba<f32>>) -> Self {
         let resolution = image.resolution();
 
         let mut images = HashMap::new();
-        images.insert(NodeId::SOURCE, image);
+        images.insert(NodeId::SOURCE, Arc::new(Buffer::from(image)));
 
         let mut names = HashSet::new();
         names.insert(MAIN_IMAGE);
 
+        let mut encodings = HashMap::new();
+        encodings.insert(NodeId::SOURCE, ColorEncoding::Display);
+
         RenderGraph {
             images,
             edges: HashMap::new(),
             passes: HashMap::new(),
             names,
+            encodings,
+            encoding: ColorEncoding::default(),
+            plan: Vec::new(),
+            parallelism: NonZeroUsize::MIN,
+            kept: HashSet::new(),
             root: NodeId(0),
             node_count: NodeId(1),
             resolution,
         }
     }
 
+    /// Sets the most passes [`Self::render`] runs at once. Independent branches of the graph are
+    /// rendered side by side, while each pass still spreads its own pixels over every core.
+    /// Defaults to `1`, rendering one pass after another.
+    pub fn set_parallelism(&mut self, parallelism: NonZeroUsize) {
+        self.parallelism = parallelism;
+    }
+
+    /// Sets the encoding of the source image.
+    /// Keeps the image of `node` once the graph is rendered. Other intermediate images are dropped
+    /// as soon as every pass using them has run.
+    pub fn keep_image(&mut self, node: NodeId) {
+        self.kept.insert(node);
+    }
+
+    pub fn set_source_encoding(&mut self, encoding: ColorEncoding) {
+        self.encodings.insert(NodeId::SOURCE, encoding);
+    }
+
+    /// Sets the encoding given to passes that don't declare one with [`Pass::color_encoding`].
+    pub fn set_encoding(&mut self, encoding: ColorEncoding) {
+        self.encoding = encoding;
+    }
+
+    /// The encoding the image of `node` is stored in.
+    pub fn image_encoding(&self, node: NodeId) -> ColorEncoding {
+        match self.encodings.get(&node) {
+            Some(encoding) => *encoding,
+            None => self.pass_encoding(node),
+        }
+    }
+
+    fn pass_encoding(&self, node: NodeId) -> ColorEncoding {
+        self.passes.get(&node)
+            .and_then(|pass| pass.color_encoding())
+            .unwrap_or(self.encoding)
+    }
+
     pub fn connections(&self, node: NodeId) -> &[NodeId] {
         match self.edges.get(&node) {
             Some(e) => e,
@@ -55,12 +116,14 @@ impl RenderGraph {
     }
 
     pub fn add_edge(&mut self, from: NodeId, to: NodeId) {
+        self.plan.clear();
         self.edges.entry(from).and_modify(|edges| edges.push(to)).or_insert_with(|| vec![to]);
     }
 
     /// Adds a [`Pass`] to this [`RenderGraph`], returning its corresponding [`NodeId`].
     pub fn add_node(&mut self, node: Box<dyn Pass>, dependencies: &[NodeId]) -> NodeId {
         let id = self.node_count;
+        self.plan.clear();
 
         for dependency in dependencies {
             self.add_edge(id, *dependency);
@@ -189,50 +252,247 @@ impl RenderGraph {
             }
         }
 
-        // Prepare auxiliary images
-        for node in self.passes.keys() {
-            if !self.images.contains_key(node) {
-                self.images.insert(*node, Image::<4, f32, Rgba<f32>>::new_fill(self.resolution, Rgba::<f32>::BLACK));
-            }
-        }
+        self.plan = self.execution_plan();
 
         Ok(())
     }
 
-    fn render_node(&mut self, node: NodeId) {
-        let mut aux_images = Vec::new();
+    /// Orders the passes the root depends on so that each comes after all of its dependencies,
+    /// visiting dependencies in the order they were connected. Shared dependencies only appear
+    /// once.
+    fn execution_plan(&self) -> Vec<NodeId> {
+        let mut plan = Vec::new();
+        let mut visited = HashSet::new();
 
-        if let Some(connections) = self.edges.get(&node) {
-            for dependency in connections.clone().into_iter() {
-                self.render_node(dependency);
+        // Nodes still to visit, and whether their dependencies have been pushed yet.
+        let mut stack = vec![(self.root, false)];
 
-                unsafe {
-                    aux_images.push(&*(self.images.get(&dependency).unwrap() as *const _));
+        while let Some((node, expanded)) = stack.pop() {
+            if expanded {
+                if self.passes.contains_key(&node) {
+                    plan.push(node);
+                }
+
+                continue;
+            }
+
+            if !visited.insert(node) {
+                continue;
+            }
+
+            stack.push((node, true));
+
+            for &dependency in self.connections(node).iter().rev() {
+                if !visited.contains(&dependency) {
+                    stack.push((dependency, false));
                 }
             }
         }
 
-        // If the node doesn't correspond to any pass, that means it is the 'source' node and
-        // we don't need to do anything.
-        let Some(pass) = self.passes.get(&node) else {
-            return;
+        plan
+    }
+
+    /// The passes in the order [`Self::render`] runs them, or nothing if the graph hasn't been
+    /// verified.
+    pub fn plan(&self) -> &[NodeId] {
+        &self.plan
+    }
+
+    /// Replaces the source image, so that the graph can be rendered again without being rebuilt.
+    /// The new source keeps the encoding of the old one.
+    pub fn set_source(&mut self, image: Image<4, f32, Rgba<f32>>) {
+        self.resolution = image.resolution();
+
+        self.images.clear();
+        self.images.insert(NodeId::SOURCE, Arc::new(Buffer::from(image)));
+        self.encodings.retain(|node, _| *node == NodeId::SOURCE);
+    }
+
+    /// Takes out everything the pass of `node` needs to render, given the `passes` of the graph.
+    /// Its dependencies must already have been rendered.
+    fn job<'a>(&mut self, node: NodeId, passes: &'a HashMap<NodeId, Box<dyn Pass>>) -> Job<'a> {
+        let pass = passes.get(&node).unwrap().as_ref();
+        let encoding = pass.color_encoding().unwrap_or(self.encoding);
+
+        // The previous image of the target is reused when it was kept as RGBA `f32`.
+        let target = match self.images.remove(&node).map(Arc::try_unwrap) {
+            Some(Ok(Buffer::RgbaF32(image))) => image,
+            _ => Image::new_fill(self.resolution, Rgba::BLACK),
         };
 
-        let target = self.images.get_mut(&node).unwrap();
+        // Dependencies stored in a different encoding than the pass wants are converted.
+        let dependencies = self.connections(node).iter()
+            .map(|dependency| {
+                let dependency_encoding = *self.encodings.get(dependency).unwrap();
+                let is_color = passes.get(dependency).is_none_or(|pass| pass.outputs_color());
+                let conversion = (is_color && dependency_encoding != encoding).then_some((dependency_encoding, encoding));
+
+                (self.images.get(dependency).unwrap().clone(), conversion)
+            })
+            .collect();
+
+        self.encodings.insert(node, encoding);
 
-        pass.apply(target, &aux_images);
+        Job { node, pass, target, dependencies }
     }
 
+    /// Renders every pass in the [`Self::plan`] once, starting each as soon as its dependencies
+    /// are done.
     pub fn render(&mut self) {
-        self.render_node(self.root);
+        // The passes are taken out so that running jobs can borrow them while the graph takes in
+        // their results.
+        let passes = std::mem::take(&mut self.passes);
+        let result = self.render_plan(&passes);
+        self.passes = passes;
+
+        if let Err(payload) = result {
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Renders the plan with up to [`Self::set_parallelism`] passes at once, returning the panic of
+    /// the first pass that panicked.
+    fn render_plan(&mut self, passes: &HashMap<NodeId, Box<dyn Pass>>) -> thread::Result<()> {
+        // How many dependencies each pass is still waiting on, and the passes waiting on each.
+        let mut waiting: HashMap<NodeId, usize> = HashMap::new();
+        let mut dependents: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+        let mut pass_dependencies: HashMap<NodeId, HashSet<NodeId>> = HashMap::new();
+
+        for &node in &self.plan {
+            let dependencies: HashSet<NodeId> = self.connections(node).iter()
+                .copied()
+                .filter(|dependency| passes.contains_key(dependency))
+                .collect();
+
+            waiting.insert(node, dependencies.len());
+
+            for &dependency in &dependencies {
+                dependents.entry(dependency).or_default().push(node);
+            }
+
+            pass_dependencies.insert(node, dependencies);
+        }
+
+        // How many passes have yet to start using each image.
+        let mut consumers: HashMap<NodeId, usize> = dependents.iter()
+            .map(|(node, dependents)| (*node, dependents.len()))
+            .collect();
+
+        let mut ready: VecDeque<NodeId> = self.plan.iter().copied().filter(|node| waiting[node] == 0).collect();
+
+        thread::scope(|scope| {
+            let (finished_sender, finished) = mpsc::channel();
+            let mut running = 0;
+
+            for _ in 0..self.plan.len() {
+                while running < self.parallelism.get() {
+                    let Some(node) = ready.pop_front() else {
+                        break;
+                    };
+
+                    let job = self.job(node, passes);
+                    let finished_sender = finished_sender.clone();
+
+                    // Images nothing else uses are dropped once the job holding them finishes.
+                    for dependency in &pass_dependencies[&node] {
+                        let count = consumers.get_mut(dependency).unwrap();
+                        *count -= 1;
+
+                        if *count == 0 && *dependency != self.root && !self.kept.contains(dependency) {
+                            self.images.remove(dependency);
+                        }
+                    }
+
+                    scope.spawn(move || {
+                        let _ = finished_sender.send(panic::catch_unwind(AssertUnwindSafe(|| job.run())));
+                    });
+
+                    running += 1;
+                }
+
+                let (node, output) = finished.recv().unwrap()?;
+                running -= 1;
+
+                self.images.insert(node, Arc::new(output));
+
+                for dependent in dependents.get(&node).into_iter().flatten() {
+                    let count = waiting.get_mut(dependent).unwrap();
+                    *count -= 1;
+
+                    if *count == 0 {
+                        ready.push_back(*dependent);
+                    }
+                }
+            }
+
+            Ok(())
+        })
     }
 
     pub fn main_image(mut self) -> Image<4, f32, Rgba<f32>> {
-        self.images.remove(&NodeId::SOURCE).unwrap()
+        Arc::unwrap_or_clone(self.images.remove(&NodeId::SOURCE).unwrap()).into_rgba()
+    }
+
+    /// The rendered image of `node`, in the format its pass stores it in. See [`Buffer::to_rgba`].
+    pub fn image(&self, node: NodeId) -> Option<&Buffer> {
+        self.images.get(&node).map(Arc::as_ref)
     }
 
     pub fn pop_image(&mut self, node: NodeId) -> Option<Image<4, f32, Rgba<f32>>> {
-        self.images.remove(&node)
+        self.images.remove(&node).map(|buffer| Arc::unwrap_or_clone(buffer).into_rgba())
+    }
+}
+
+/// The encodings to convert an image from and to.
+type Conversion = (ColorEncoding, ColorEncoding);
+
+/// A pass that is ready to render, along with the images it needs.
+struct Job<'a> {
+    node: NodeId,
+    pass: &'a dyn Pass,
+    target: Image<4, f32, Rgba<f32>>,
+    /// The image of each dependency, and the encodings to convert it between if it needs to be.
+    dependencies: Vec<(Arc<Buffer>, Option<Conversion>)>,
+}
+
+impl Job<'_> {
+    /// Applies the pass, returning its output in its [`Pass::output_format`].
+    fn run(self) -> (NodeId, Buffer) {
+        let Job { node, pass, mut target, dependencies } = self;
+
+        // Passes render at the resolution of their first dependency, which may have been resized.
+        let resolution = dependencies.first().map_or(target.resolution(), |(buffer, _)| buffer.resolution());
+
+        if target.resolution() != resolution {
+            target = Image::new_fill(resolution, Rgba::BLACK);
+        }
+
+        // Dependencies are given to passes as RGBA `f32` at the resolution of the target, so any
+        // stored in another format, encoding or resolution are converted on a copy.
+        let aux_images: Vec<Cow<Image<4, f32, Rgba<f32>>>> = dependencies.iter()
+            .map(|(buffer, conversion)| match (buffer.as_rgba(), conversion) {
+                (Some(image), None) if image.resolution() == resolution => Cow::Borrowed(image),
+                _ => {
+                    let mut image = buffer.to_rgba();
+
+                    if let Some((from, to)) = conversion {
+                        image.convert_encoding(*from, *to);
+                    }
+
+                    if image.resolution() != resolution {
+                        image = image.resize(resolution, ResizeFilter::Bilinear);
+                    }
+
+                    Cow::Owned(image)
+                },
+            })
+            .collect();
+
+        let aux_images: Vec<&Image<4, f32, Rgba<f32>>> = aux_images.iter().map(|image| image.as_ref()).collect();
+
+        pass.apply(&mut target, &aux_images);
+
+        (node, Buffer::from_rgba(target, pass.output_format()))
     }
 }
 
@@ -297,3 +557,166 @@ pub enum RenderGraphVerifyError {
     #[error("graph has mismatched edge and dependency (pass '{0}' depends on '{1}', was given '{2}' at index {3})")]
     MismatchedDependency(String, String, String, usize),
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{num::NonZeroUsize, sync::atomic::{AtomicUsize, Ordering}};
+
+    use glam::UVec2;
+
+    use crate::{image::{color_management::ColorEncoding, pixel::rgba::Rgba, resize::ResizeFilter, Image}, pass::Pass};
+
+    use super::{NodeId, RenderGraph, ANY_IMAGE};
+
+    /// Sums its inputs, then scales and offsets the result, counting how often it is applied.
+    struct Affine {
+        inputs: usize,
+        scale: f32,
+        offset: f32,
+        applied: &'static AtomicUsize,
+    }
+
+    impl Pass for Affine {
+        fn name(&self) -> &'static str {
+            "Affine"
+        }
+
+        fn dependencies(&self) -> Vec<&'static str> {
+            vec![ANY_IMAGE; self.inputs]
+        }
+
+        fn color_encoding(&self) -> Option<ColorEncoding> {
+            Some(ColorEncoding::Linear)
+        }
+
+        fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
+            self.applied.fetch_add(1, Ordering::Relaxed);
+
+            target.for_each_with_positions(|pixel, pos| {
+                let sum: f32 = aux_images.iter().map(|image| image.load(pos).r).sum();
+                let v = sum * self.scale + self.offset;
+                *pixel = Rgba::new(v, v, v, 1.0);
+            });
+        }
+    }
+
+    /// Doubles the resolution of its input.
+    struct Upscale;
+
+    impl Pass for Upscale {
+        fn name(&self) -> &'static str {
+            "Upscale"
+        }
+
+        fn dependencies(&self) -> Vec<&'static str> {
+            vec![ANY_IMAGE]
+        }
+
+        fn apply(&self, target: &mut Image<4, f32, Rgba<f32>>, aux_images: &[&Image<4, f32, Rgba<f32>>]) {
+            *target = aux_images[0].resize(aux_images[0].resolution() * 2, ResizeFilter::Nearest);
+        }
+    }
+
+    fn affine(inputs: usize, scale: f32, offset: f32, applied: &'static AtomicUsize) -> Box<dyn Pass> {
+        Box::new(Affine { inputs, scale, offset, applied })
+    }
+
+    fn source(resolution: UVec2) -> Image<4, f32, Rgba<f32>> {
+        let pixels = (0..resolution.x * resolution.y).map(|i| Rgba::new(i as f32, 0.0, 0.0, 1.0)).collect();
+        Image::new(resolution, pixels)
+    }
+
+    /// `top -> source; left -> top; right -> top; join -> left, right;`
+    fn diamond(applied: &'static AtomicUsize) -> (RenderGraph, [NodeId; 4]) {
+        let mut graph = RenderGraph::new(source(UVec2::new(4, 3)));
+        graph.set_source_encoding(ColorEncoding::Linear);
+
+        let top = graph.add_node(affine(1, 1.0, 1.0, applied), &[NodeId::SOURCE]);
+        let left = graph.add_node(affine(1, 1.0, 2.0, applied), &[top]);
+        let right = graph.add_node(affine(1, 3.0, 0.0, applied), &[top]);
+        let join = graph.add_node(affine(2, 1.0, 0.0, applied), &[left, right]);
+
+        (graph, [top, left, right, join])
+    }
+
+    #[test]
+    fn plan_runs_shared_dependencies_once_and_first() {
+        static APPLIED: AtomicUsize = AtomicUsize::new(0);
+
+        let (mut graph, [top, left, right, join]) = diamond(&APPLIED);
+
+        assert!(graph.plan().is_empty());
+        graph.verify().unwrap();
+        assert_eq!(graph.plan(), [top, left, right, join]);
+
+        graph.render();
+        assert_eq!(APPLIED.load(Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    fn parallel_renders_match_serial_ones() {
+        static APPLIED: AtomicUsize = AtomicUsize::new(0);
+
+        let mut outputs = Vec::new();
+
+        for parallelism in [1, 2, 4] {
+            let (mut graph, [.., join]) = diamond(&APPLIED);
+            graph.set_parallelism(NonZeroUsize::new(parallelism).unwrap());
+            graph.verify().unwrap();
+            graph.render();
+
+            outputs.push(graph.image(join).unwrap().to_rgba());
+        }
+
+        // `left + right` is `(s + 1 + 2) + 3 * (s + 1)`.
+        for (pixel, s) in outputs[0].iter_pixels().zip(0..) {
+            assert_eq!(pixel.r, 4.0 * s as f32 + 6.0);
+        }
+
+        for output in &outputs[1..] {
+            assert!(output.iter_pixels().eq(outputs[0].iter_pixels()));
+        }
+    }
+
+    #[test]
+    fn only_kept_images_outlive_their_consumers() {
+        static APPLIED: AtomicUsize = AtomicUsize::new(0);
+
+        for parallelism in [1, 4] {
+            let (mut graph, [top, left, right, join]) = diamond(&APPLIED);
+            graph.set_parallelism(NonZeroUsize::new(parallelism).unwrap());
+            graph.keep_image(left);
+            graph.verify().unwrap();
+
+            // Rendering again starts from an empty set of intermediate images.
+            for _ in 0..2 {
+                graph.render();
+
+                assert!(graph.image(top).is_none() && graph.image(right).is_none());
+                assert!(graph.image(left).is_some() && graph.image(join).is_some());
+                assert!(graph.image(NodeId::SOURCE).is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn inputs_are_resampled_to_the_first() {
+        static APPLIED: AtomicUsize = AtomicUsize::new(0);
+
+        let mut graph = RenderGraph::new(source(UVec2::new(4, 3)));
+        graph.set_source_encoding(ColorEncoding::Linear);
+
+        let upscaled = graph.add_node(Box::new(Upscale), &[NodeId::SOURCE]);
+        let offset = graph.add_node(affine(1, 1.0, 0.5, &APPLIED), &[NodeId::SOURCE]);
+        let join = graph.add_node(affine(2, 1.0, 0.0, &APPLIED), &[upscaled, offset]);
+
+        graph.verify().unwrap();
+        graph.render();
+
+        let output = graph.image(join).unwrap().to_rgba();
+        assert_eq!(output.resolution(), UVec2::new(8, 6));
+
+        // Nearest upscaling of a source with `s` at each pixel, plus `s + 0.5` resampled.
+        assert_eq!(output.load(UVec2::ZERO).r, 0.5);
+    }
+}
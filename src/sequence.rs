@@ -0,0 +1,458 @@
+//! Runs a render graph over every frame of an input, decoding, rendering and encoding frames on
+//! separate threads.
+
+use std::{fs::{self, File}, io::{BufRead, BufReader, BufWriter, Read, Write}, num::NonZeroUsize, path::{Path, PathBuf}, sync::mpsc::{self, Receiver, SyncSender}, thread, time::Duration, vec};
+
+use crate::{
+    image::{animation::{Animation, Frame}, color_management::{ColorEncoding, TransferFunction}, pixel::rgba::Rgba, y4m::{self, Y4mHeader, Y4mReader, Y4mWriter}, Image, ImageError, ImageFormat},
+    is_stdio, NprsError, OutputSettings, RawRenderGraph,
+};
+
+/// How many frames may wait between two stages of the pipeline.
+const PIPELINE_DEPTH: usize = 2;
+
+/// A printf-style path pattern for numbered frames, like `frames/%04d.png`.
+pub(crate) struct FramePattern {
+    pattern: String,
+    prefix: String,
+    suffix: String,
+    /// The minimum number of digits, padded with zeros.
+    width: usize,
+}
+
+impl FramePattern {
+    /// Parses the single `%d` or `%0Nd` in `path`, where `%%` stands for a literal `%`. Returns
+    /// `None` if there isn't one.
+    pub(crate) fn parse(path: &Path) -> Option<FramePattern> {
+        let path = path.to_str()?;
+
+        let mut prefix = String::new();
+        let mut chars = path.char_indices().peekable();
+
+        while let Some((i, c)) = chars.next() {
+            if c != '%' {
+                prefix.push(c);
+                continue;
+            }
+
+            if chars.next_if(|(_, c)| *c == '%').is_some() {
+                prefix.push('%');
+                continue;
+            }
+
+            let spec_end = path[i + 1..].find('d')? + i + 1;
+            let spec = &path[i + 1..spec_end];
+
+            if !spec.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+
+            let suffix = &path[spec_end + 1..];
+
+            // Only one frame number is allowed.
+            if suffix.replace("%%", "").contains('%') {
+                return None;
+            }
+
+            return Some(FramePattern {
+                pattern: path.to_string(),
+                prefix,
+                suffix: suffix.replace("%%", "%"),
+                width: spec.parse().unwrap_or(0),
+            });
+        }
+
+        None
+    }
+
+    pub(crate) fn path(&self, index: u32) -> PathBuf {
+        PathBuf::from(format!("{}{:0width$}{}", self.prefix, index, self.suffix, width = self.width))
+    }
+
+    /// The number of the first frame, which is the lowest one that exists.
+    fn first_index(&self) -> Option<u32> {
+        let file_start = self.prefix.rfind(std::path::is_separator).map_or(0, |i| i + 1);
+        let (dir, file_prefix) = self.prefix.split_at(file_start);
+
+        // Frame numbers in a directory name can't be found by listing a single directory, so
+        // those sequences have to start from `0` to `4`.
+        if self.suffix.contains(std::path::is_separator) {
+            return (0..=4).find(|&index| self.path(index).exists());
+        }
+
+        let dir = if dir.is_empty() { Path::new(".") } else { Path::new(dir) };
+
+        fs::read_dir(dir).ok()?
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter_map(|name| {
+                let digits = name.strip_prefix(file_prefix)?.strip_suffix(self.suffix.as_str())?;
+
+                if digits.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+                    return None;
+                }
+
+                // Only numbers written the way the pattern writes them, so `7` doesn't match
+                // `%04d`.
+                let index: u32 = digits.parse().ok()?;
+                (format!("{:0width$}", index, width = self.width) == digits).then_some(index)
+            })
+            .min()
+    }
+}
+
+impl std::fmt::Display for FramePattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+/// A rendered or decoded frame, along with its position in the sequence.
+struct SequenceFrame {
+    index: u32,
+    image: Image<4, f32, Rgba<f32>>,
+    delay: Duration,
+}
+
+/// Where the frames given to the render graph come from.
+pub(crate) enum FrameSource {
+    /// Numbered image files, read one at a time.
+    Pattern { pattern: FramePattern, next: u32 },
+    /// A YUV4MPEG2 stream, read one frame at a time.
+    Y4m { reader: Y4mReader<Box<dyn BufRead + Send>>, next: u32 },
+    /// The frames of an image that was read whole, which is a single frame for anything but
+    /// animated GIFs and PNGs.
+    Animation {
+        frames: vec::IntoIter<Frame<4, f32, Rgba<f32>>>,
+        loop_count: u32,
+        transfer: TransferFunction,
+        next: u32,
+    },
+}
+
+impl FrameSource {
+    /// Opens `path`, which may be `-` for stdin or a [`FramePattern`] if no file of that name exists.
+    pub(crate) fn open(path: &Path) -> Result<FrameSource, NprsError> {
+        if !is_stdio(path) && !path.exists() {
+            if let Some(pattern) = FramePattern::parse(path) {
+                let first = pattern.first_index().ok_or(NprsError::MissingFrames(pattern.to_string()))?;
+                return Ok(FrameSource::Pattern { pattern, next: first });
+            }
+        }
+
+        let reader: Box<dyn Read + Send> = if is_stdio(path) {
+            Box::new(std::io::stdin())
+        } else {
+            Box::new(File::open(path).map_err(ImageError::from)?)
+        };
+
+        let mut reader = BufReader::new(reader);
+
+        // Streams are read frame by frame, so they never have to fit in memory.
+        if reader.fill_buf().map_err(ImageError::from)?.starts_with(y4m::MAGIC) {
+            let reader = Y4mReader::new(Box::new(reader) as Box<dyn BufRead + Send>)?;
+            return Ok(FrameSource::Y4m { reader, next: 1 });
+        }
+
+        let (animation, metadata) = Animation::read_from(reader)?;
+
+        Ok(FrameSource::Animation {
+            frames: animation.frames.into_iter(),
+            loop_count: animation.loop_count,
+            transfer: metadata.transfer_function(),
+            next: 1,
+        })
+    }
+
+    /// The number of times the input animation plays, where `0` loops forever.
+    pub(crate) fn loop_count(&self) -> u32 {
+        match self {
+            FrameSource::Animation { loop_count, .. } => *loop_count,
+            _ => 0,
+        }
+    }
+
+    /// Whether the source is known to hold more than one frame. YUV4MPEG2 streams don't say how
+    /// many frames they hold, so they only count once a second frame has been read.
+    pub(crate) fn has_multiple_frames(&self) -> bool {
+        match self {
+            FrameSource::Pattern { pattern, next } => pattern.path(*next + 1).exists(),
+            FrameSource::Y4m { .. } => false,
+            FrameSource::Animation { frames, .. } => frames.len() > 1,
+        }
+    }
+
+    /// The stream header of a YUV4MPEG2 input.
+    pub(crate) fn y4m_header(&self) -> Option<Y4mHeader> {
+        match self {
+            FrameSource::Y4m { reader, .. } => Some(reader.header().clone()),
+            _ => None,
+        }
+    }
+
+    /// Reads the next frame, decoded to linear light.
+    fn next_frame(&mut self) -> Result<Option<SequenceFrame>, ImageError> {
+        let (index, mut image, delay, transfer) = match self {
+            FrameSource::Pattern { pattern, next } => {
+                let path = pattern.path(*next);
+
+                if !path.exists() {
+                    return Ok(None);
+                }
+
+                let (image, metadata) = Image::read_with_metadata(path)?;
+                (*next, image, Duration::ZERO, metadata.transfer_function())
+            },
+            FrameSource::Y4m { reader, next } => {
+                let Some(image) = reader.read_frame()? else {
+                    return Ok(None);
+                };
+
+                let (num, den) = reader.header().frame_rate;
+                let delay = if num == 0 { Duration::ZERO } else { Duration::from_secs_f64(den as f64 / num as f64) };
+
+                (*next, image, delay, TransferFunction::Srgb)
+            },
+            FrameSource::Animation { frames, transfer, next, .. } => {
+                let Some(frame) = frames.next() else {
+                    return Ok(None);
+                };
+
+                (*next, frame.image, frame.delay, *transfer)
+            },
+        };
+
+        match self {
+            FrameSource::Pattern { next, .. } | FrameSource::Y4m { next, .. } | FrameSource::Animation { next, .. } => *next += 1,
+        }
+
+        image.decode_transfer(transfer);
+
+        Ok(Some(SequenceFrame { index, image, delay }))
+    }
+
+    /// Sends every frame to `frames`, stopping early if the receiver hangs up.
+    fn decode(mut self, frames: SyncSender<SequenceFrame>) -> Result<(), NprsError> {
+        while let Some(frame) = self.next_frame()? {
+            if frames.send(frame).is_err() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Where rendered frames are written.
+pub(crate) enum FrameSink {
+    /// Numbered image files, written as each frame is rendered.
+    Pattern(FramePattern),
+    /// A YUV4MPEG2 stream, written as each frame is rendered.
+    Y4m {
+        outfile: PathBuf,
+        /// The header of the input stream, whose frame rate and sampling are kept.
+        header: Option<Y4mHeader>,
+        writer: Option<Y4mWriter<Box<dyn Write + Send>>>,
+    },
+    /// A single file, written once every frame has been rendered.
+    Animation {
+        outfile: PathBuf,
+        frames: Vec<Frame<4, f32, Rgba<f32>>>,
+        loop_count: u32,
+    },
+}
+
+impl FrameSink {
+    fn push(&mut self, frame: SequenceFrame, settings: &OutputSettings) -> Result<(), NprsError> {
+        match self {
+            FrameSink::Pattern(pattern) => {
+                let writer = BufWriter::new(File::create(pattern.path(frame.index)).map_err(ImageError::from)?);
+                settings.write(&Animation::from(frame.image), writer)?;
+            },
+            FrameSink::Y4m { outfile, header, writer } => {
+                let writer = match writer {
+                    Some(writer) => writer,
+                    None => {
+                        let mut header = header.take().unwrap_or_else(|| {
+                            let mut header = Y4mHeader::new(frame.image.resolution());
+
+                            if !frame.delay.is_zero() {
+                                header.frame_rate = (1000, frame.delay.as_millis().max(1) as u32);
+                            }
+
+                            header
+                        });
+                        header.resolution = frame.image.resolution();
+
+                        writer.insert(Y4mWriter::new(create_output(outfile)?, header)?)
+                    },
+                };
+
+                // YUV4MPEG2 has no alpha, and stores display-referred values.
+                let mut image = frame.image.map(|pixel| pixel.rgb() * pixel.a);
+                image.encode_transfer(TransferFunction::Srgb);
+
+                writer.write_frame(&image)?;
+            },
+            FrameSink::Animation { frames, .. } => frames.push(Frame { image: frame.image, delay: frame.delay }),
+        }
+
+        Ok(())
+    }
+
+    /// Writes every frame received from `frames`, returning the sink so it can be finished once
+    /// every stage has succeeded.
+    fn encode(mut self, frames: Receiver<SequenceFrame>, settings: &OutputSettings) -> Result<FrameSink, NprsError> {
+        for frame in frames {
+            self.push(frame, settings)?;
+        }
+
+        Ok(self)
+    }
+
+    fn finish(self, settings: &OutputSettings) -> Result<(), NprsError> {
+        match self {
+            FrameSink::Pattern(_) => (),
+            FrameSink::Y4m { writer, .. } => {
+                if let Some(mut writer) = writer {
+                    writer.flush()?;
+                }
+            },
+            FrameSink::Animation { outfile, frames, loop_count } => {
+                // Inputs that aren't known to be animated up front are checked again before the
+                // output file is created, so a failed run doesn't leave an empty file.
+                if frames.len() > 1 && !settings.format.supports_animation() {
+                    return Err(ImageError::NotAnimated(settings.format).into());
+                }
+
+                settings.write(&Animation { frames, loop_count }, create_output(&outfile)?)?;
+            },
+        }
+
+        Ok(())
+    }
+}
+
+fn create_output(outfile: &Path) -> Result<Box<dyn Write + Send>, ImageError> {
+    if is_stdio(outfile) {
+        Ok(Box::new(BufWriter::new(std::io::stdout())))
+    } else {
+        Ok(Box::new(BufWriter::new(File::create(outfile)?)))
+    }
+}
+
+/// Renders every frame of `source` and writes the results to `sink`.
+///
+/// The graph is built once from the first frame, and each following frame replaces its source.
+/// Frames are decoded and encoded on their own threads while the next one renders, with up to
+/// `parallelism` passes rendering at once.
+pub(crate) fn process(
+    raw_render_graph: RawRenderGraph,
+    source: FrameSource,
+    sink: FrameSink,
+    settings: OutputSettings,
+    parallelism: NonZeroUsize,
+) -> Result<(), NprsError> {
+    let (decoded_sender, decoded) = mpsc::sync_channel(PIPELINE_DEPTH);
+    let (rendered, rendered_receiver) = mpsc::sync_channel(PIPELINE_DEPTH);
+
+    let settings = &settings;
+
+    let (decode_result, render_result, encode_result) = thread::scope(|scope| {
+        let decoder = scope.spawn(move || source.decode(decoded_sender));
+        let encoder = scope.spawn(move || sink.encode(rendered_receiver, settings));
+
+        let render_result = render(raw_render_graph, parallelism, decoded, rendered);
+
+        (decoder.join().unwrap(), render_result, encoder.join().unwrap())
+    });
+
+    decode_result?;
+
+    if render_result? == 0 {
+        return Err(ImageError::NoFrames.into());
+    }
+
+    encode_result?.finish(settings)
+}
+
+/// Renders each frame received from `decoded`, returning how many were rendered.
+fn render(
+    raw_render_graph: RawRenderGraph,
+    parallelism: NonZeroUsize,
+    decoded: Receiver<SequenceFrame>,
+    rendered: SyncSender<SequenceFrame>,
+) -> Result<usize, NprsError> {
+    let Ok(first) = decoded.recv() else {
+        return Ok(0);
+    };
+
+    // The graph is given linear light, and converts it for passes that want display-referred data.
+    let (mut render_graph, display_node) = raw_render_graph.build(first.image)?;
+    render_graph.set_source_encoding(ColorEncoding::Linear);
+
+    render_graph.set_parallelism(parallelism);
+
+    render_graph.verify()?;
+
+    let (mut index, mut delay) = (first.index, first.delay);
+    let mut count = 0;
+
+    loop {
+        render_graph.render();
+        count += 1;
+
+        let encoding = render_graph.image_encoding(display_node);
+        let mut image = render_graph.image(display_node).unwrap().to_rgba();
+        image.convert_encoding(encoding, ColorEncoding::Linear);
+
+        if rendered.send(SequenceFrame { index, image, delay }).is_err() {
+            break;
+        }
+
+        match decoded.recv() {
+            Ok(frame) => {
+                render_graph.set_source(frame.image);
+                (index, delay) = (frame.index, frame.delay);
+            },
+            Err(_) => break,
+        }
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::FramePattern;
+
+    #[test]
+    fn parses_patterns() {
+        let pattern = FramePattern::parse(Path::new("out/100%%_%04d.png")).unwrap();
+        assert_eq!(pattern.path(12), Path::new("out/100%_0012.png"));
+        assert_eq!(pattern.path(123456), Path::new("out/100%_123456.png"));
+
+        assert!(FramePattern::parse(Path::new("out/frame.png")).is_none());
+        assert!(FramePattern::parse(Path::new("out/%d_%d.png")).is_none());
+        assert!(FramePattern::parse(Path::new("out/%x.png")).is_none());
+    }
+
+    #[test]
+    fn sequences_start_at_the_lowest_frame() {
+        let dir = std::env::temp_dir().join(format!("nprs-first-index-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        for name in ["frame_1001.png", "frame_1002.png", "frame_17.png", "frame_0999.txt", "other_0001.png"] {
+            fs::write(dir.join(name), []).unwrap();
+        }
+
+        let pattern = |pattern: &str| FramePattern::parse(&dir.join(pattern)).unwrap().first_index();
+
+        assert_eq!(pattern("frame_%04d.png"), Some(1001));
+        assert_eq!(pattern("frame_%d.png"), Some(17));
+        assert_eq!(pattern("frame_%d.jpg"), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}